@@ -0,0 +1,70 @@
+//
+// Access to the `/admin/*` pages. They need the `ADMIN_TOKEN` secret as a
+// bearer token (`Authorization: Bearer <token>`); without the secret set they
+// don't exist at all.
+//
+
+/// Outcome of checking a request against the admin token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Granted,
+    /// Missing or wrong token.
+    Denied,
+    /// No `ADMIN_TOKEN` configured: the admin pages are off.
+    Disabled,
+}
+
+impl Access {
+    /// Status for a refused request: 401 asks for the token, 404 hides pages
+    /// that are turned off.
+    pub fn status(self) -> Option<u16> {
+        match self {
+            Access::Granted => None,
+            Access::Denied => Some(401),
+            Access::Disabled => Some(404),
+        }
+    }
+}
+
+/// Check an `Authorization` header value against the configured token.
+pub fn authorize(authorization: Option<&str>, token: Option<&str>) -> Access {
+    let Some(token) = token.map(str::trim).filter(|t| !t.is_empty()) else {
+        return Access::Disabled;
+    };
+    let offered = authorization
+        .and_then(|h| h.trim().strip_prefix("Bearer "))
+        .map(str::trim)
+        .unwrap_or("");
+    if constant_time_eq(offered.as_bytes(), token.as_bytes()) {
+        Access::Granted
+    } else {
+        Access::Denied
+    }
+}
+
+/// Compare without an early exit, so timing doesn't reveal how much of a
+/// guess was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_the_configured_bearer_token() {
+        let token = Some("s3cret-token");
+        assert_eq!(
+            authorize(Some("Bearer s3cret-token"), token),
+            Access::Granted
+        );
+        assert_eq!(authorize(Some("Bearer s3cret"), token), Access::Denied);
+        assert_eq!(authorize(Some("s3cret-token"), token), Access::Denied);
+        assert_eq!(authorize(None, token), Access::Denied);
+        assert_eq!(authorize(Some("Bearer "), Some(" ")), Access::Disabled);
+        assert_eq!(authorize(Some("Bearer x"), None), Access::Disabled);
+        assert_eq!(Access::Denied.status(), Some(401));
+        assert_eq!(Access::Disabled.status(), Some(404));
+    }
+}
@@ -2,19 +2,40 @@
 // Native dev runner — exercise the full pipeline (or a single rating) against
 // live sites without deploying to Cloudflare. Built only with `--features native`.
 //
-//   cargo run --features native --bin b30-dev -- menu [out.html]
+//   cargo run --features native --bin b30-dev -- menu [out.html [global|style]]
 //   cargo run --features native --bin b30-dev -- rating "Sierra Nevada Pale Ale"
+//   cargo run --features native --bin b30-dev -- audit
+//   cargo run --features native --bin b30-dev -- diagnostics
+//   cargo run --features native --bin b30-dev -- print print.html qr
+//   cargo run --features native --bin b30-dev -- screen screen.html
+//   cargo run --features native --bin b30-dev -- flight "n=4&drinks=2&price=20"
+//   cargo run --features native --bin b30-dev -- tried-import checkins.csv
 //   cargo run --features native --bin b30-dev -- refresh-fixtures
 //
 // It reuses the exact same pure parsers/renderer the worker uses; only the HTTP
 // (reqwest) differs from the wasm `worker::Fetch` path.
 //
 
+use lib::budget::{self, Budget, RatingPlan};
+use lib::clock::{self, VenueClock, VenueTimezone};
+use lib::diagnostics::{self, Report};
 use lib::error::{AppError, AppResult};
-use lib::model::{sort_rated, RatedBeer};
-use lib::{render, taphunter, untappd};
+use lib::flight::{self, FlightRequest};
+use lib::metrics::{Phase, RequestLog};
+use lib::model::{
+    sort_rated_with, BeerEntry, CategoryOrder, LookupOutcome, LookupStats, RatedBeer, RatingResult,
+    SortOptions, WithinCategory,
+};
+use lib::recommend::{self, TasteProfile};
+use lib::retry::{self, Attempt, RateLimiter, RetryPolicy};
+use lib::taphunter::UrlStrategy;
+use lib::taxonomy::{GroupBy, StyleView, Taxonomy};
+use lib::tried::{self, TriedList};
+use lib::{render, schema, taphunter, untappd};
+use std::cell::RefCell;
 use std::fs;
 use std::io::Write;
+use std::time::{Duration, Instant};
 
 const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
 
@@ -25,60 +46,279 @@ fn client() -> reqwest::blocking::Client {
         .expect("failed to build HTTP client")
 }
 
+/// One try at a request, whatever status comes back.
+fn exchange(req: reqwest::blocking::RequestBuilder) -> Attempt {
+    match req.send() {
+        Ok(resp) => {
+            let status = resp.status().as_u16();
+            let retry_after = resp
+                .headers()
+                .get("Retry-After")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            match resp.text() {
+                Ok(body) => Attempt::Response {
+                    status,
+                    retry_after,
+                    body,
+                },
+                Err(e) => Attempt::Transport(format!("Failed to read response: {e}")),
+            }
+        }
+        Err(e) => Attempt::Transport(e.to_string()),
+    }
+}
+
+thread_local! {
+    static LIMITER: RefCell<RateLimiter> = RefCell::new(RateLimiter::default());
+}
+
+fn now_ms() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+/// The worker's retry policy and per-host pacing, with blocking sleeps.
+/// `request` builds a fresh request for each try.
+fn send(url: &str, request: impl Fn() -> reqwest::blocking::RequestBuilder) -> AppResult<String> {
+    let host = retry::host_of(url);
+    let sleep = |ms: u64| std::thread::sleep(Duration::from_millis(ms));
+    retry::with_retries(
+        &RetryPolicy::default(),
+        &host,
+        now_ms() ^ url.len() as u64,
+        chrono::Utc::now(),
+        || {
+            sleep(LIMITER.with(|l| l.borrow_mut().reserve(&host, now_ms())));
+            exchange(request())
+        },
+        sleep,
+    )
+}
+
 fn get_text(c: &reqwest::blocking::Client, url: &str) -> AppResult<String> {
-    c.get(url)
-        .send()
-        .and_then(|r| r.error_for_status())
-        .and_then(|r| r.text())
-        .map_err(|e| AppError::Network(format!("GET {url} failed: {e}")))
+    send(url, || c.get(url))
 }
 
 fn algolia_query(c: &reqwest::blocking::Client, search: &str) -> AppResult<String> {
-    let q = untappd::build_query(search);
-    c.post(&q.url)
-        .header("X-Algolia-Application-Id", &q.app_id)
-        .header("X-Algolia-API-Key", &q.api_key)
-        .header("Content-Type", "application/json")
-        .body(q.body)
-        .send()
-        .and_then(|r| r.text())
-        .map_err(|e| AppError::Network(format!("Algolia query failed: {e}")))
+    algolia_post(c, &untappd::build_query(search))
+}
+
+fn algolia_post(c: &reqwest::blocking::Client, q: &untappd::AlgoliaQuery) -> AppResult<String> {
+    send(&q.url, || {
+        c.post(&q.url)
+            .header("X-Algolia-Application-Id", &q.app_id)
+            .header("X-Algolia-API-Key", &q.api_key)
+            .header("Content-Type", "application/json")
+            .body(q.body.clone())
+    })
+}
+
+/// Same batched variant-by-variant search the worker runs (see
+/// `untappd::BatchLookup`), for `ids` (indexes into `entries`).
+fn resolve_ratings(
+    c: &reqwest::blocking::Client,
+    entries: &[BeerEntry],
+    ids: &[usize],
+    log: &mut RequestLog,
+) -> Vec<(usize, AppResult<RatingResult>)> {
+    let mut lookup = untappd::BatchLookup::new(ids.iter().map(|&idx| (idx, &entries[idx])));
+    while let Some(batch) = lookup.next_batch() {
+        let started = Instant::now();
+        let body = algolia_post(c, &batch);
+        log.algolia(elapsed_ms(started));
+        lookup.offer(body);
+    }
+    lookup.finish()
+}
+
+/// Venue clock in `$VENUE_TZ` (same format as the worker var), Pacific if unset.
+fn venue_clock() -> VenueClock {
+    let timezone = std::env::var("VENUE_TZ")
+        .ok()
+        .and_then(|tz| VenueTimezone::parse(&tz))
+        .unwrap_or(clock::PACIFIC);
+    VenueClock::system(timezone)
+}
+
+/// The worker's rating budget under `$SUBREQUEST_LIMIT` (see `budget`), to
+/// preview which taps a cold load would leave pending; unlimited if unset.
+fn rating_plan(entries: &[BeerEntry]) -> RatingPlan {
+    let limit = std::env::var("SUBREQUEST_LIMIT")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(u32::MAX);
+    let mut budget = Budget::new(limit);
+    budget.spend(budget::page_overhead());
+    RatingPlan::new(budget::by_visibility(entries, &sort_options()), budget)
+}
+
+/// Menu ordering from `$CATEGORY_ORDER` / `$WITHIN_CATEGORY_SORT` (same values
+/// as the worker vars).
+fn sort_options() -> SortOptions {
+    let var = |name| std::env::var(name).ok();
+    SortOptions {
+        categories: var("CATEGORY_ORDER")
+            .and_then(|v| CategoryOrder::parse(&v))
+            .unwrap_or_default(),
+        within: var("WITHIN_CATEGORY_SORT")
+            .and_then(|v| WithinCategory::parse(&v))
+            .unwrap_or_default(),
+    }
+}
+
+/// Style taxonomy extended by `$STYLE_TAXONOMY` (JSON, as the worker var), and
+/// the grouping from `$GROUP_BY`.
+fn style_layout() -> AppResult<(Taxonomy, StyleView)> {
+    let mut taxonomy = Taxonomy::builtin();
+    if let Ok(extra) = std::env::var("STYLE_TAXONOMY") {
+        taxonomy = taxonomy.extended(Taxonomy::from_json(&extra)?);
+    }
+    let view = StyleView {
+        group_by: std::env::var("GROUP_BY")
+            .ok()
+            .and_then(|v| GroupBy::parse(&v))
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+    Ok((taxonomy, view))
+}
+
+fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+fn fetch_entries(c: &reqwest::blocking::Client, log: &mut RequestLog) -> AppResult<Vec<BeerEntry>> {
+    let started = Instant::now();
+    let bigscreen = get_text(c, &taphunter::bigscreen_url());
+    log.phase(Phase::Bigscreen, elapsed_ms(started));
+    // As the worker, minus its KV-cached URL.
+    let (json_url, strategy) = bigscreen
+        .and_then(|html| taphunter::discover_json_url(&html))
+        .unwrap_or_else(|e| {
+            eprintln!("menu URL not on the bigscreen page: {e}");
+            (
+                taphunter::venue_json_url(taphunter::BEER_THIRTY_VENUE_ID),
+                UrlStrategy::VenueKey,
+            )
+        });
+    eprintln!("menu URL via {}: {json_url}", strategy.label());
+    let started = Instant::now();
+    let menu_json = get_text(c, &json_url)?;
+    log.phase(Phase::Menu, elapsed_ms(started));
+    for warning in schema::validate_menu(&menu_json)?.warnings {
+        eprintln!("schema drift: {warning}");
+    }
+    taphunter::parse_menu(&menu_json, &venue_clock())
+}
+
+/// The live menu with each tap's Untappd rating (uncached), logging misses.
+fn fetch_rated(c: &reqwest::blocking::Client, log: &mut RequestLog) -> AppResult<Vec<RatedBeer>> {
+    let entries = fetch_entries(c, log)?;
+    eprintln!("parsed {} taps", entries.len());
+
+    let started = Instant::now();
+    // No cache here: every tap the plan reaches is a miss, as on a cold worker.
+    let mut plan = rating_plan(&entries);
+    loop {
+        let chunk = plan.next_reads();
+        if chunk.is_empty() {
+            break;
+        }
+        for idx in chunk {
+            plan.missed(idx);
+        }
+    }
+    let ids = plan.take_lookups();
+    let pending = plan.deferred().len();
+    if pending > 0 {
+        eprintln!("{pending} taps pending (subrequest budget)");
+    }
+
+    let mut ratings = vec![(None, LookupOutcome::Pending); entries.len()];
+    for (idx, result) in resolve_ratings(c, &entries, &ids, log) {
+        ratings[idx] = match result {
+            Ok(r) => (
+                Some(RatingResult {
+                    fetched_at: Some(chrono::Utc::now()),
+                    ..r
+                }),
+                LookupOutcome::Fresh,
+            ),
+            Err(e) => {
+                let entry = &entries[idx];
+                eprintln!("  {} {} -> N/A ({e})", entry.brewery, entry.name);
+                log.error(&e);
+                (None, LookupOutcome::from_error(&e))
+            }
+        };
+    }
+    let rated: Vec<RatedBeer> = entries
+        .into_iter()
+        .zip(ratings)
+        .map(|(entry, (rating, outcome))| RatedBeer {
+            entry,
+            rating,
+            outcome,
+        })
+        .collect();
+    log.phase(Phase::Ratings, elapsed_ms(started));
+    log.lookups(&LookupStats::tally(&rated));
+    Ok(rated)
 }
 
 fn cmd_rating(search: &str) -> AppResult<()> {
     let c = client();
     let body = algolia_query(&c, search)?;
     match untappd::parse_rating(&body) {
-        Ok(r) => println!("rating={}  url={}", r.rating, r.url),
+        Ok(r) => println!(
+            "rating={}  count={}  url={}",
+            r.rating, r.rating_count, r.url
+        ),
         Err(e) => println!("no rating: {e}"),
     }
     Ok(())
 }
 
-fn cmd_menu(out: Option<&str>) -> AppResult<()> {
-    let c = client();
-    let bigscreen = get_text(&c, &taphunter::bigscreen_url())?;
-    let json_url = taphunter::parse_json_url(&bigscreen)?;
-    let menu_json = get_text(&c, &json_url)?;
-    let entries = taphunter::parse_menu(&menu_json)?;
-    eprintln!("parsed {} taps", entries.len());
+fn cmd_menu(out: Option<&str>, colors: Option<&str>) -> AppResult<()> {
+    let clock = venue_clock();
+    let mut options = render::RenderOptions {
+        today: Some(clock.today()),
+        timezone: Some(clock.timezone),
+        ..Default::default()
+    };
+    if let Some(colors) = colors {
+        options.heatmap.rating_colors = render::RatingColors::from_param(colors)
+            .ok_or_else(|| AppError::Client(format!("unknown colors mode: {colors}")))?;
+    }
 
-    let mut rated: Vec<RatedBeer> = Vec::with_capacity(entries.len());
-    for entry in entries {
-        let search = format!("{} {}", entry.brewery, entry.name);
-        let rating_html = match algolia_query(&c, &search).and_then(|b| untappd::parse_rating(&b)) {
-            Ok(r) => r.to_cell(),
-            Err(e) => {
-                eprintln!("  {search} -> N/A ({e})");
-                "N/A".to_string()
-            }
-        };
-        rated.push(RatedBeer { entry, rating_html });
+    let started = Instant::now();
+    let mut log = RequestLog::new("dev", "/", chrono::Utc::now());
+    let rated = fetch_rated(&client(), &mut log)?;
+    let rendering = Instant::now();
+    let (taxonomy, view) = style_layout()?;
+    let mut rated = view.apply(rated, &taxonomy);
+    let sort = sort_options();
+    sort_rated_with(&mut rated, &sort);
+
+    // `$TASTE` is a profile in query form, e.g. "like=hazy,gose&abv=4-7&ibu=50".
+    let profile = TasteProfile::from_query(&std::env::var("TASTE").unwrap_or_default());
+    if !profile.is_empty() {
+        let fits = recommend::fits(&rated, &profile, &taxonomy, None);
+        options.picks = recommend::top_picks(&rated, &fits);
+        if sort.within == WithinCategory::ForYou {
+            recommend::sort_for_you(&mut rated, fits);
+        }
     }
-    sort_rated(&mut rated);
-    let html = render::render(&rated);
+    let html = render::render_with(&rated, &options);
+    log.phase(Phase::Render, elapsed_ms(rendering));
+    log.finish(200, elapsed_ms(started));
+    // The worker's per-request log line, for comparing timings.
+    eprintln!("{}", log.to_json());
 
-    let resolved = rated.iter().filter(|b| b.rating_html != "N/A").count();
+    let resolved = rated
+        .iter()
+        .filter(|b| b.rating.as_ref().is_some_and(|r| r.score().is_some()))
+        .count();
     eprintln!("resolved {}/{} ratings", resolved, rated.len());
 
     match out {
@@ -94,6 +334,146 @@ fn cmd_menu(out: Option<&str>) -> AppResult<()> {
     Ok(())
 }
 
+/// Write the `/print` paper menu, optionally with Untappd QR codes.
+fn cmd_print(out: &str, qr: bool) -> AppResult<()> {
+    let rated = fetch_rated(&client(), &mut RequestLog::default())?;
+    let (taxonomy, view) = style_layout()?;
+    let mut rated = view.apply(rated, &taxonomy);
+    sort_rated_with(&mut rated, &sort_options());
+    let options = render::PrintOptions {
+        qr,
+        today: Some(venue_clock().today()),
+        ..Default::default()
+    };
+    fs::write(out, render::render_print(&rated, &options))
+        .map_err(|e| AppError::Internal(format!("write {out} failed: {e}")))?;
+    eprintln!("wrote {out}");
+    Ok(())
+}
+
+/// Write the `/screen` kiosk page for the live menu (tap order, default paging).
+fn cmd_screen(out: &str) -> AppResult<()> {
+    let mut rated = fetch_rated(&client(), &mut RequestLog::default())?;
+    rated.sort_by_key(|b| b.entry.tap_number);
+    let page = render::render_screen(&rated, &render::ScreenOptions::default());
+    fs::write(out, &page.html)
+        .map_err(|e| AppError::Internal(format!("write {out} failed: {e}")))?;
+    eprintln!("wrote {out} (ETag {})", page.etag);
+    Ok(())
+}
+
+/// Build a flight from the live menu; `query` takes the `/flight` parameters,
+/// e.g. "n=5&drinks=2&price=20".
+fn cmd_flight(query: &str) -> AppResult<()> {
+    let rated = fetch_rated(&client(), &mut RequestLog::default())?;
+    let (taxonomy, _) = style_layout()?;
+    let request = FlightRequest::from_query(query);
+    let Some(flight) = flight::build_flight(&rated, &taxonomy, &request) else {
+        println!("no flight fits those constraints");
+        return Ok(());
+    };
+    for p in &flight.pours {
+        println!(
+            "{:>3}  {:<28} {:<36} {:<28} {:>5}  {:>6} {:>7}",
+            p.beer.entry.tap_number,
+            p.beer.entry.brewery,
+            p.beer.entry.name,
+            p.style,
+            p.beer
+                .entry
+                .abv
+                .map_or("?".to_string(), |a| format!("{a}%")),
+            p.pour.map_or("?", |p| p.size.as_str()),
+            p.price().map_or("?".to_string(), |p| format!("${p:.2}")),
+        );
+    }
+    println!(
+        "total: {:.1} standard drinks, {}",
+        flight.total_drinks(),
+        flight
+            .total_price()
+            .map_or("price not listed for every pour".to_string(), |p| format!(
+                "${p:.2}"
+            ))
+    );
+    Ok(())
+}
+
+/// Print how each tap's rating was matched (which search variant, if any), to
+/// spot mismatches without rendering the menu.
+fn cmd_audit() -> AppResult<()> {
+    let c = client();
+    let mut log = RequestLog::default();
+    let entries = fetch_entries(&c, &mut log)?;
+    let ids: Vec<usize> = (0..entries.len()).collect();
+    let mut results = resolve_ratings(&c, &entries, &ids, &mut log);
+    results.sort_by_key(|(idx, _)| *idx);
+    for ((_, result), entry) in results.into_iter().zip(&entries) {
+        let (rating, via, url) = match result {
+            Ok(r) => {
+                let via = r.matched_by.map_or("unconfirmed", |v| v.label());
+                (r.rating, via, r.url)
+            }
+            Err(e) => ("N/A".to_string(), "-", e.to_string()),
+        };
+        println!(
+            "{:>3}  {:<28} {:<36} {:>5}  {:<16} {}",
+            entry.tap_number, entry.brewery, entry.name, rating, via, url
+        );
+    }
+    Ok(())
+}
+
+/// Run the `/admin/diagnostics` upstream checks from here; exits non-zero if
+/// any fail.
+fn cmd_diagnostics() -> AppResult<()> {
+    let c = client();
+    let started = Instant::now();
+    let (url_check, json_url) =
+        diagnostics::check_json_url(get_text(&c, &taphunter::bigscreen_url()));
+    let url_check = url_check.timed(elapsed_ms(started));
+
+    let started = Instant::now();
+    let menu_json = json_url.map(|url| get_text(&c, &url));
+    let fetched = menu_json.is_some();
+    let mut menu_check = diagnostics::check_menu(menu_json, &venue_clock());
+    if fetched {
+        menu_check = menu_check.timed(elapsed_ms(started));
+    }
+
+    let started = Instant::now();
+    let canary_check = diagnostics::check_canary(algolia_post(&c, &diagnostics::canary_query()))
+        .timed(elapsed_ms(started));
+
+    let report = Report {
+        checks: vec![url_check, menu_check, canary_check],
+    };
+    println!("{:#}", report.to_json());
+    if report.passed() {
+        Ok(())
+    } else {
+        Err(AppError::Internal("diagnostics failed".into()))
+    }
+}
+
+/// Convert an Untappd check-in export (CSV or JSON) into a stored tried list,
+/// printed for seeding KV offline:
+///   npx wrangler kv key put --binding b30 "tried:<token>" "$(b30-dev tried-import checkins.csv)"
+fn cmd_tried_import(path: &str) -> AppResult<()> {
+    let body = fs::read_to_string(path)
+        .map_err(|e| AppError::Client(format!("read {path} failed: {e}")))?;
+    let checkins = tried::parse_export(&body)?;
+    let mut list = TriedList::default();
+    list.import(&checkins);
+    eprintln!(
+        "{} check-ins, {} distinct beers",
+        checkins.len(),
+        list.len()
+    );
+    println!("{}", list.to_json());
+    Ok(())
+}
+
 fn cmd_refresh_fixtures() -> AppResult<()> {
     let c = client();
     let dir = "b30/fixtures";
@@ -124,10 +504,28 @@ fn main() {
                 "usage: rating \"<brewery> <name>\"".into(),
             )),
         },
-        Some("menu") => cmd_menu(args.get(2).map(String::as_str)),
+        Some("menu") => cmd_menu(
+            args.get(2).map(String::as_str),
+            args.get(3).map(String::as_str),
+        ),
+        Some("audit") => cmd_audit(),
+        Some("diagnostics") => cmd_diagnostics(),
+        Some("print") => cmd_print(
+            args.get(2).map_or("print.html", String::as_str),
+            args.get(3).is_some_and(|a| a == "qr"),
+        ),
+        Some("screen") => cmd_screen(args.get(2).map_or("screen.html", String::as_str)),
+        Some("flight") => cmd_flight(args.get(2).map_or("", String::as_str)),
+        Some("tried-import") => match args.get(2) {
+            Some(path) => cmd_tried_import(path),
+            None => Err(AppError::Client(
+                "usage: tried-import <checkins.csv|checkins.json>".into(),
+            )),
+        },
         Some("refresh-fixtures") => cmd_refresh_fixtures(),
         _ => Err(AppError::Client(
-            "usage: b30-dev <menu [out.html] | rating \"<query>\" | refresh-fixtures>".into(),
+            "usage: b30-dev <menu [out.html [global|style]] | rating \"<query>\" | audit | diagnostics | print [out.html [qr]] | screen [out.html] | flight [query] | tried-import <file> | refresh-fixtures>"
+                .into(),
         )),
     };
     if let Err(e) = result {
@@ -0,0 +1,315 @@
+//
+// Subrequest budget for a page load. Workers caps the subrequests (fetches and
+// KV operations) one invocation may make, at 50 on the free plan, and on a cold
+// cache every beer costs a KV read, a share of the Algolia batches and a KV
+// write. `RatingPlan` spends what the page leaves on the most visible taps
+// first; taps it can't afford render as pending, and a later load, with the
+// earlier ones cached, gets further down the menu.
+//
+
+use crate::model::{
+    sorted_order, BeerEntry, LookupOutcome, RatedBeer, SortOptions, WithinCategory,
+};
+use crate::retry::RetryPolicy;
+use crate::untappd::BATCH_LIMIT;
+
+/// Subrequests per invocation on the Workers free plan.
+pub const DEFAULT_SUBREQUEST_LIMIT: u32 = 50;
+
+/// Subrequests a menu page makes besides rating lookups: the bigscreen page
+/// and menu JSON (each retried up to the policy's limit), then eight KV
+/// operations: the cached menu URL and the snapshot (read and write each),
+/// the viewer's tried list and taste profile, and the stats flush.
+pub fn page_overhead() -> u32 {
+    2 * RetryPolicy::default().max_attempts + 8
+}
+
+/// Search variants per beer (see `untappd::search_plan`): the most rounds of
+/// Algolia batches a lookup takes.
+const LOOKUP_ROUNDS: u32 = 3;
+
+/// Subrequests left to spend in one invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    limit: u32,
+    spent: u32,
+}
+
+impl Budget {
+    pub fn new(limit: u32) -> Budget {
+        Budget { limit, spent: 0 }
+    }
+
+    pub fn spend(&mut self, n: u32) {
+        self.spent = self.spent.saturating_add(n);
+    }
+
+    pub fn spent(&self) -> u32 {
+        self.spent
+    }
+
+    pub fn left(&self) -> u32 {
+        self.limit.saturating_sub(self.spent)
+    }
+}
+
+/// Worst-case subrequests to look up and cache `misses` beers: every round of
+/// batches retried to the policy's limit, plus a cache write each.
+pub fn lookup_cost(misses: usize) -> u32 {
+    if misses == 0 {
+        return 0;
+    }
+    let batches = misses.div_ceil(BATCH_LIMIT) as u32;
+    LOOKUP_ROUNDS * batches * RetryPolicy::default().max_attempts + misses as u32
+}
+
+/// Indexes into `entries` in the order a visitor sees them: the menu's
+/// category order, then tap number (ratings, and so rating sorts, aren't
+/// known yet).
+pub fn by_visibility(entries: &[BeerEntry], sort: &SortOptions) -> Vec<usize> {
+    let unrated: Vec<RatedBeer> = entries
+        .iter()
+        .map(|entry| RatedBeer {
+            entry: entry.clone(),
+            rating: None,
+            outcome: LookupOutcome::Pending,
+        })
+        .collect();
+    let options = SortOptions {
+        categories: sort.categories.clone(),
+        within: WithinCategory::Tap,
+    };
+    sorted_order(&unrated, &options)
+}
+
+/// One page load's rating lookups, in visibility order. The caller reads the
+/// cache for each `next_reads` chunk, reports the misses, then looks up
+/// `take_lookups`; whatever is `deferred` renders as pending.
+///
+/// Chunks are as big as still leaves room, should every read miss, to look up
+/// and cache all the misses. Once not even one more lookup fits, the rest of
+/// the budget goes on plain cache reads: a hit still shows its rating, a miss
+/// is deferred.
+#[derive(Debug, Clone)]
+pub struct RatingPlan {
+    order: Vec<usize>,
+    /// Each entry's place in `order`.
+    position: Vec<usize>,
+    /// How much of `order` has been read.
+    read: usize,
+    /// How much of `order` was read with its lookup paid for.
+    lookup_reads: usize,
+    misses: Vec<usize>,
+    deferred: Vec<usize>,
+    budget: Budget,
+}
+
+impl RatingPlan {
+    /// `order` is a permutation of the entry indexes (see `by_visibility`).
+    pub fn new(order: Vec<usize>, budget: Budget) -> RatingPlan {
+        let mut position = vec![0; order.len()];
+        for (pos, &idx) in order.iter().enumerate() {
+            position[idx] = pos;
+        }
+        RatingPlan {
+            order,
+            position,
+            read: 0,
+            lookup_reads: 0,
+            misses: Vec::new(),
+            deferred: Vec::new(),
+            budget,
+        }
+    }
+
+    /// The next entries to read from the cache (empty when done), spending a
+    /// subrequest each.
+    pub fn next_reads(&mut self) -> Vec<usize> {
+        let unread = self.order.len() - self.read;
+        let left = self.budget.left();
+        let with_lookups = (0..=unread)
+            .rev()
+            .find(|&n| n as u32 + lookup_cost(self.misses.len() + n) <= left)
+            .unwrap_or(0);
+        // Reads with their lookups paid for all come before plain ones.
+        let n = if with_lookups > 0 && self.lookup_reads == self.read {
+            self.lookup_reads += with_lookups;
+            with_lookups
+        } else {
+            unread.min(left.saturating_sub(lookup_cost(self.misses.len())) as usize)
+        };
+        let chunk = self.order[self.read..self.read + n].to_vec();
+        self.read += n;
+        self.budget.spend(n as u32);
+        chunk
+    }
+
+    /// Record a cache miss (or an unreadable cached value) from `next_reads`.
+    pub fn missed(&mut self, idx: usize) {
+        if self.position[idx] < self.lookup_reads {
+            self.misses.push(idx);
+        } else {
+            self.deferred.push(idx);
+        }
+    }
+
+    /// The misses to look up now, in visibility order, spending their worst
+    /// case.
+    pub fn take_lookups(&mut self) -> Vec<usize> {
+        let mut misses = std::mem::take(&mut self.misses);
+        misses.sort_by_key(|&idx| self.position[idx]);
+        self.budget.spend(lookup_cost(misses.len()));
+        misses
+    }
+
+    /// Of `stale` cached ratings (see `freshness::needs_refresh`), the ones the
+    /// budget left over can refresh, most visible first, spending their worst
+    /// case. Call after `take_lookups`: showing a rating beats refreshing one.
+    pub fn refreshes(&mut self, mut stale: Vec<usize>) -> Vec<usize> {
+        stale.sort_by_key(|&idx| self.position[idx]);
+        let left = self.budget.left();
+        let n = (0..=stale.len())
+            .rev()
+            .find(|&n| lookup_cost(n) <= left)
+            .unwrap_or(0);
+        stale.truncate(n);
+        self.budget.spend(lookup_cost(n));
+        stale
+    }
+
+    /// Entries the budget didn't reach.
+    pub fn deferred(&self) -> Vec<usize> {
+        let mut deferred = self.deferred.clone();
+        deferred.extend_from_slice(&self.order[self.read..]);
+        deferred
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::CategoryOrder;
+    use std::collections::HashSet;
+
+    fn entry(tap: i32, category: &str) -> BeerEntry {
+        BeerEntry {
+            tap_number: tap,
+            brewery: format!("Brewery {tap}"),
+            brewery_full: String::new(),
+            name: format!("Beer {tap}"),
+            full_name: String::new(),
+            abv: Some(5.0),
+            ibu: None,
+            category: category.into(),
+            category_order: None,
+            origin: String::new(),
+            style: String::new(),
+            date_added: None,
+            pours: Vec::new(),
+        }
+    }
+
+    /// One page load against a cache holding `cached`: the subrequests it
+    /// spends, the entries it looks up, and the ones left pending.
+    fn load(entries: &[BeerEntry], cached: &HashSet<usize>) -> (u32, Vec<usize>, Vec<usize>) {
+        let mut budget = Budget::new(DEFAULT_SUBREQUEST_LIMIT);
+        budget.spend(page_overhead());
+        let order = by_visibility(entries, &SortOptions::default());
+        let mut plan = RatingPlan::new(order, budget);
+        loop {
+            let chunk = plan.next_reads();
+            if chunk.is_empty() {
+                break;
+            }
+            for idx in chunk {
+                if !cached.contains(&idx) {
+                    plan.missed(idx);
+                }
+            }
+        }
+        let lookups = plan.take_lookups();
+        (plan.budget().spent(), lookups, plan.deferred())
+    }
+
+    #[test]
+    fn lookup_cost_covers_retried_rounds_and_writes() {
+        assert_eq!(lookup_cost(0), 0);
+        assert_eq!(lookup_cost(1), 10);
+        assert_eq!(lookup_cost(50), 59);
+        assert_eq!(lookup_cost(51), 18 + 51);
+    }
+
+    #[test]
+    fn visibility_follows_category_then_tap_order() {
+        let entries = vec![
+            entry(3, "Stouts"),
+            entry(1, "Sours"),
+            entry(2, "IPAs"),
+            entry(4, "IPAs"),
+        ];
+        assert_eq!(
+            by_visibility(&entries, &SortOptions::default()),
+            vec![2, 3, 1, 0]
+        );
+        let venue = SortOptions {
+            categories: CategoryOrder::Venue,
+            ..SortOptions::default()
+        };
+        assert_eq!(by_visibility(&entries, &venue), vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn large_cold_menu_stays_within_the_limit() {
+        let entries: Vec<BeerEntry> = (1..=45).map(|tap| entry(tap, "IPAs")).collect();
+        let mut cached = HashSet::new();
+        let mut pending = Vec::new();
+        for _ in 0..4 {
+            let (spent, lookups, deferred) = load(&entries, &cached);
+            assert!(spent <= DEFAULT_SUBREQUEST_LIMIT, "spent {spent}");
+            // Lookups go to the top of the menu: the lowest taps not yet cached.
+            let mut expected: Vec<usize> =
+                (0..entries.len()).filter(|i| !cached.contains(i)).collect();
+            expected.truncate(lookups.len());
+            assert_eq!(lookups, expected);
+            cached.extend(lookups);
+            pending.push(deferred.len());
+        }
+        // First load: 13 lookups, then one plain cache read (a miss): 32 pending.
+        assert_eq!(pending[0], 32);
+        // Each load gets further; a warm cache leaves only what the budget
+        // can't even read.
+        assert!(pending.windows(2).all(|w| w[1] < w[0]), "{pending:?}");
+        let (_, lookups, deferred) = load(&entries, &(0..45).collect());
+        assert!(lookups.is_empty());
+        assert_eq!(deferred, (36..45).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn refreshes_get_what_lookups_leave() {
+        let entries: Vec<BeerEntry> = (1..=20).map(|tap| entry(tap, "IPAs")).collect();
+        let mut budget = Budget::new(DEFAULT_SUBREQUEST_LIMIT);
+        budget.spend(page_overhead());
+        let mut plan = RatingPlan::new(by_visibility(&entries, &SortOptions::default()), budget);
+        while !plan.next_reads().is_empty() {}
+        assert!(plan.take_lookups().is_empty());
+        assert!(plan.deferred().is_empty());
+        // 36 less 20 reads leaves 16: a refresh round costs 9 plus a write each.
+        let stale: Vec<usize> = (0..20).rev().collect();
+        assert_eq!(plan.refreshes(stale), (0..7).collect::<Vec<_>>());
+        assert_eq!(plan.budget().spent(), DEFAULT_SUBREQUEST_LIMIT);
+        assert!(plan.refreshes(vec![8]).is_empty());
+    }
+
+    #[test]
+    fn small_menu_is_looked_up_in_one_load() {
+        let entries: Vec<BeerEntry> = (1..=10).map(|tap| entry(tap, "IPAs")).collect();
+        let (spent, lookups, deferred) = load(&entries, &HashSet::new());
+        assert_eq!(lookups.len(), 10);
+        assert!(deferred.is_empty());
+        assert_eq!(spent, page_overhead() + 10 + lookup_cost(10));
+    }
+}
@@ -0,0 +1,215 @@
+//
+// The venue's wall clock. Cloudflare runs in UTC, so "today" for keg ages has
+// to be computed in the venue's timezone or every keg ages a day early each
+// evening Pacific. Everything takes an explicit `VenueClock` so tests can pin
+// "now". Timezones are a fixed standard offset plus an optional US DST rule,
+// which covers the venues we care about without shipping the tz database in
+// the wasm build.
+//
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Utc, Weekday};
+
+/// A venue timezone: standard UTC offset, optionally observing US daylight time
+/// (second Sunday in March 02:00 to first Sunday in November 02:00, local).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VenueTimezone {
+    /// Standard-time offset from UTC in minutes (Pacific: -480).
+    pub std_offset_minutes: i32,
+    /// Whether US daylight saving time applies.
+    pub us_dst: bool,
+}
+
+/// Beer Thirty's timezone (Santa Cruz, CA).
+pub const PACIFIC: VenueTimezone = VenueTimezone {
+    std_offset_minutes: -8 * 60,
+    us_dst: true,
+};
+
+pub const UTC: VenueTimezone = VenueTimezone {
+    std_offset_minutes: 0,
+    us_dst: false,
+};
+
+/// Named zones accepted by `VenueTimezone::parse`.
+const NAMED_ZONES: &[(&str, VenueTimezone)] = &[
+    ("America/Los_Angeles", PACIFIC),
+    (
+        "America/Denver",
+        VenueTimezone {
+            std_offset_minutes: -7 * 60,
+            us_dst: true,
+        },
+    ),
+    (
+        "America/Phoenix",
+        VenueTimezone {
+            std_offset_minutes: -7 * 60,
+            us_dst: false,
+        },
+    ),
+    (
+        "America/Chicago",
+        VenueTimezone {
+            std_offset_minutes: -6 * 60,
+            us_dst: true,
+        },
+    ),
+    (
+        "America/New_York",
+        VenueTimezone {
+            std_offset_minutes: -5 * 60,
+            us_dst: true,
+        },
+    ),
+    ("UTC", UTC),
+];
+
+impl VenueTimezone {
+    /// Parse a zone from config: one of the `NAMED_ZONES` IANA names, or a fixed
+    /// offset like "-08:00" / "+05:30" (no DST).
+    pub fn parse(value: &str) -> Option<VenueTimezone> {
+        let value = value.trim();
+        if let Some((_, tz)) = NAMED_ZONES.iter().find(|(name, _)| *name == value) {
+            return Some(*tz);
+        }
+        let (sign, rest) = match value.as_bytes().first()? {
+            b'+' => (1, &value[1..]),
+            b'-' => (-1, &value[1..]),
+            _ => return None,
+        };
+        let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+        let hours: i32 = hours.parse().ok()?;
+        let minutes: i32 = minutes.parse().ok()?;
+        if hours > 14 || minutes >= 60 {
+            return None;
+        }
+        Some(VenueTimezone {
+            std_offset_minutes: sign * (hours * 60 + minutes),
+            us_dst: false,
+        })
+    }
+
+    /// UTC offset in effect at `instant`.
+    pub fn offset_at(&self, instant: DateTime<Utc>) -> FixedOffset {
+        let std = self.std_offset_minutes * 60;
+        let dst = self.us_dst && self.in_us_dst(instant);
+        FixedOffset::east_opt(if dst { std + 3600 } else { std })
+            .expect("venue offsets are validated to within a day")
+    }
+
+    /// The venue's calendar date at `instant`.
+    pub fn local_date(&self, instant: DateTime<Utc>) -> NaiveDate {
+        instant.with_timezone(&self.offset_at(instant)).date_naive()
+    }
+
+    fn in_us_dst(&self, instant: DateTime<Utc>) -> bool {
+        let year = instant.year();
+        let std = Duration::minutes(i64::from(self.std_offset_minutes));
+        // 02:00 local standard time on the second Sunday in March...
+        let start = nth_sunday(year, 3, 2).and_hms_opt(2, 0, 0).unwrap() - std;
+        // ... until 02:00 local daylight time on the first Sunday in November.
+        let end = nth_sunday(year, 11, 1).and_hms_opt(2, 0, 0).unwrap() - std - Duration::hours(1);
+        let naive = instant.naive_utc();
+        naive >= start && naive < end
+    }
+}
+
+fn nth_sunday(year: i32, month: u32, n: u32) -> NaiveDate {
+    NaiveDate::from_weekday_of_month_opt(year, month, Weekday::Sun, n as u8)
+        .expect("every month has a first and second Sunday")
+}
+
+/// "Now" as seen from the venue. Construct with `system` in the runners and
+/// `at` in tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VenueClock {
+    pub now: DateTime<Utc>,
+    pub timezone: VenueTimezone,
+}
+
+impl VenueClock {
+    /// The real current time.
+    pub fn system(timezone: VenueTimezone) -> VenueClock {
+        VenueClock::at(Utc::now(), timezone)
+    }
+
+    /// A fixed instant (tests, replays).
+    pub fn at(now: DateTime<Utc>, timezone: VenueTimezone) -> VenueClock {
+        VenueClock { now, timezone }
+    }
+
+    /// The venue's calendar date right now.
+    pub fn today(&self) -> NaiveDate {
+        self.timezone.local_date(self.now)
+    }
+
+    /// The venue's calendar date at some other instant.
+    pub fn local_date(&self, instant: DateTime<Utc>) -> NaiveDate {
+        self.timezone.local_date(instant)
+    }
+
+    /// An instant on the venue's wall clock (for display).
+    pub fn local(&self, instant: DateTime<Utc>) -> DateTime<FixedOffset> {
+        instant.with_timezone(&self.timezone.offset_at(instant))
+    }
+}
+
+/// Parse an RFC 3339 UTC timestamp ("2026-05-20T22:16:40Z").
+pub fn parse_utc(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value.trim())
+        .ok()
+        .map(|dt| Utc.from_utc_datetime(&dt.naive_utc()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(value: &str) -> DateTime<Utc> {
+        parse_utc(value).unwrap()
+    }
+
+    #[test]
+    fn pacific_observes_us_daylight_time() {
+        let winter = PACIFIC.offset_at(utc("2026-01-15T12:00:00Z"));
+        let summer = PACIFIC.offset_at(utc("2026-07-15T12:00:00Z"));
+        assert_eq!(winter.local_minus_utc(), -8 * 3600);
+        assert_eq!(summer.local_minus_utc(), -7 * 3600);
+        // 2026: DST starts Mar 8 10:00Z and ends Nov 1 09:00Z.
+        assert_eq!(PACIFIC.offset_at(utc("2026-03-08T09:59:59Z")), winter);
+        assert_eq!(PACIFIC.offset_at(utc("2026-03-08T10:00:00Z")), summer);
+        assert_eq!(PACIFIC.offset_at(utc("2026-11-01T08:59:59Z")), summer);
+        assert_eq!(PACIFIC.offset_at(utc("2026-11-01T09:00:00Z")), winter);
+    }
+
+    #[test]
+    fn today_rolls_over_at_venue_midnight() {
+        // 23:30 Pacific on May 31 is already June 1 in UTC.
+        let before = VenueClock::at(utc("2026-06-01T06:30:00Z"), PACIFIC);
+        let after = VenueClock::at(utc("2026-06-01T07:30:00Z"), PACIFIC);
+        assert_eq!(
+            before.today(),
+            NaiveDate::from_ymd_opt(2026, 5, 31).unwrap()
+        );
+        assert_eq!(after.today(), NaiveDate::from_ymd_opt(2026, 6, 1).unwrap());
+        assert_eq!(
+            VenueClock::at(utc("2026-06-01T06:30:00Z"), UTC).today(),
+            NaiveDate::from_ymd_opt(2026, 6, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn parses_named_and_fixed_offset_zones() {
+        assert_eq!(VenueTimezone::parse("America/Los_Angeles"), Some(PACIFIC));
+        assert_eq!(
+            VenueTimezone::parse("+05:30"),
+            Some(VenueTimezone {
+                std_offset_minutes: 330,
+                us_dst: false
+            })
+        );
+        assert_eq!(VenueTimezone::parse("-8").unwrap().std_offset_minutes, -480);
+        assert_eq!(VenueTimezone::parse("Mars/Olympus"), None);
+        assert_eq!(VenueTimezone::parse("+25:00"), None);
+    }
+}
@@ -0,0 +1,249 @@
+//
+// Upstream diagnostics for `/admin/diagnostics`: can we still find the
+// TapHunter menu URL on the bigscreen page, does the menu JSON still have the
+// fields `parse_menu` reads, and does a canary Algolia search still return a
+// rating? The glue (or the dev runner) does the fetching; the checks here only
+// judge what came back, so each reports a specific reason when it fails.
+//
+
+use crate::clock::VenueClock;
+use crate::error::AppResult;
+use crate::untappd::{self, AlgoliaQuery};
+use crate::{schema, taphunter};
+use serde_json::{json, Value};
+
+/// A search that has had a solid Untappd rating for years.
+pub const CANARY_SEARCH: &str = "sierra nevada pale ale";
+
+/// One check's verdict.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    /// What was found, or why it failed.
+    pub detail: String,
+    /// How long the check's fetch took, when it made one.
+    pub ms: Option<u64>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: String) -> Check {
+        Check {
+            name,
+            passed: true,
+            detail,
+            ms: None,
+        }
+    }
+
+    fn fail(name: &'static str, detail: String) -> Check {
+        Check {
+            name,
+            passed: false,
+            detail,
+            ms: None,
+        }
+    }
+
+    pub fn timed(self, ms: u64) -> Check {
+        Check {
+            ms: Some(ms),
+            ..self
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "pass": self.passed,
+            "detail": self.detail,
+            "ms": self.ms,
+        })
+    }
+}
+
+/// All checks from one run.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub checks: Vec<Check>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.passed)
+    }
+
+    /// 200 when everything passed, else 503, so a monitor that only looks at
+    /// the status still alerts.
+    pub fn status(&self) -> u16 {
+        if self.passed() {
+            200
+        } else {
+            503
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "status": if self.passed() { "pass" } else { "fail" },
+            "checks": self.checks.iter().map(Check::to_json).collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// The bigscreen page fetched and its menu URL found on it. Only the page
+/// strategies count: the worker's KV and venue-key fallbacks would hide a
+/// redesign. Returns the URL for the menu check.
+pub fn check_json_url(bigscreen: AppResult<String>) -> (Check, Option<String>) {
+    const NAME: &str = "taphunter_json_url";
+    let html = match bigscreen {
+        Ok(html) => html,
+        Err(e) => {
+            return (
+                Check::fail(NAME, format!("bigscreen fetch failed: {e}")),
+                None,
+            )
+        }
+    };
+    match taphunter::discover_json_url(&html) {
+        Ok((url, strategy)) => (
+            Check::pass(NAME, format!("{url} (via {})", strategy.label())),
+            Some(url),
+        ),
+        Err(e) => (
+            Check::fail(
+                NAME,
+                format!("{e} in the bigscreen page ({} bytes)", html.len()),
+            ),
+            None,
+        ),
+    }
+}
+
+/// The menu JSON fetched, parsed by `parse_menu`, and free of schema drift
+/// (see `schema::validate_menu`).
+pub fn check_menu(menu_json: Option<AppResult<String>>, clock: &VenueClock) -> Check {
+    const NAME: &str = "taphunter_menu";
+    let menu_json = match menu_json {
+        None => return Check::fail(NAME, "skipped: no menu URL".into()),
+        Some(Err(e)) => return Check::fail(NAME, format!("menu fetch failed: {e}")),
+        Some(Ok(json)) => json,
+    };
+    let entries = match taphunter::parse_menu(&menu_json, clock) {
+        Ok(entries) => entries,
+        Err(e) => return Check::fail(NAME, e.to_string()),
+    };
+    if entries.is_empty() {
+        return Check::fail(NAME, "menu has no taps".into());
+    }
+
+    let report = match schema::validate_menu(&menu_json) {
+        Ok(report) => report,
+        Err(e) => return Check::fail(NAME, e.to_string()),
+    };
+    if !report.is_clean() {
+        return Check::fail(
+            NAME,
+            format!("schema drift: {}", report.warnings.join("; ")),
+        );
+    }
+    Check::pass(NAME, format!("{} taps, key fields present", entries.len()))
+}
+
+/// The Algolia search for `CANARY_SEARCH`.
+pub fn canary_query() -> AlgoliaQuery {
+    untappd::build_query(CANARY_SEARCH)
+}
+
+/// The canary search answered with a rated beer. A `Blocked` failure usually
+/// means Untappd rotated its public Algolia key.
+pub fn check_canary(body: AppResult<String>) -> Check {
+    const NAME: &str = "algolia_canary";
+    let rating = body.and_then(|body| untappd::parse_rating(&body));
+    match rating {
+        Ok(r) => match r.score() {
+            Some(score) => Check::pass(NAME, format!("\"{CANARY_SEARCH}\" rated {score:.2}")),
+            None => Check::fail(
+                NAME,
+                format!("\"{CANARY_SEARCH}\" matched but has no rating"),
+            ),
+        },
+        Err(e) => Check::fail(NAME, format!("\"{CANARY_SEARCH}\": {e}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::{self, VenueClock};
+    use crate::error::AppError;
+
+    fn clock() -> VenueClock {
+        VenueClock::at(
+            clock::parse_utc("2026-06-28T07:30:00Z").unwrap(),
+            clock::PACIFIC,
+        )
+    }
+
+    #[test]
+    fn finds_the_json_url_or_says_why_not() {
+        let (check, url) = check_json_url(Ok(
+            include_str!("fixtures/taphunter_bigscreen.html").to_string()
+        ));
+        assert!(check.passed);
+        assert!(url
+            .unwrap()
+            .starts_with("http://www.taphunter.com/bigscreen/json/"));
+        assert!(check.detail.ends_with("(via getJSON)"));
+
+        let (check, url) = check_json_url(Ok("<html>redesigned</html>".into()));
+        assert!(!check.passed);
+        assert_eq!(url, None);
+        assert!(check.detail.contains("Could not find the menu JSON URL"));
+
+        let (check, _) = check_json_url(Err(AppError::Network("timed out".into())));
+        assert!(check.detail.starts_with("bigscreen fetch failed"));
+    }
+
+    #[test]
+    fn menu_check_flags_renamed_fields() {
+        let json = include_str!("fixtures/taphunter_menu.json");
+        let check = check_menu(Some(Ok(json.to_string())), &clock());
+        assert!(check.passed, "{}", check.detail);
+        assert_eq!(check.detail, "30 taps, key fields present");
+
+        let renamed = json.replace("\"style_category\"", "\"styleCategory\"");
+        let check = check_menu(Some(Ok(renamed)), &clock());
+        assert!(!check.passed);
+        assert_eq!(
+            check.detail,
+            "schema drift: beer.style_category missing in 30 of 30 items"
+        );
+
+        assert!(!check_menu(Some(Ok("{}".into())), &clock()).passed);
+        assert!(!check_menu(Some(Ok("[]".into())), &clock()).passed);
+        assert_eq!(check_menu(None, &clock()).detail, "skipped: no menu URL");
+    }
+
+    #[test]
+    fn canary_needs_a_rated_match() {
+        let check = check_canary(Ok(include_str!("fixtures/algolia_beer_query.json").into()));
+        assert!(check.passed, "{}", check.detail);
+        let check = check_canary(Ok(include_str!("fixtures/algolia_no_results.json").into()));
+        assert!(!check.passed);
+        let check = check_canary(Ok(include_str!("fixtures/algolia_auth_error.json").into()));
+        assert!(check.detail.contains("Blocked"), "{}", check.detail);
+
+        let report = Report {
+            checks: vec![
+                Check::pass("a", "fine".into()).timed(12),
+                check_canary(Err(AppError::Network("down".into()))),
+            ],
+        };
+        assert_eq!(report.status(), 503);
+        let json = report.to_json();
+        assert_eq!(json["status"], "fail");
+        assert_eq!(json["checks"][0]["ms"], 12);
+        assert_eq!(json["checks"][1]["pass"], false);
+    }
+}
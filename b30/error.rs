@@ -37,6 +37,41 @@ impl std::fmt::Display for AppError {
     }
 }
 
+/// Seconds to tell clients to wait after we were `Blocked` upstream.
+pub const BLOCKED_RETRY_AFTER_SECONDS: u32 = 5 * 60;
+
+impl AppError {
+    /// HTTP status for a request that failed with this error. Upstream trouble
+    /// is a bad gateway, an upstream refusing us makes us unavailable for a
+    /// while, and only `Client` blames the request.
+    pub fn status(&self) -> u16 {
+        match self {
+            AppError::Client(_) => 400,
+            AppError::Network(_) | AppError::Parse(_) => 502,
+            AppError::Blocked(_) => 503,
+            AppError::NotFound => 404,
+            AppError::Internal(_) => 500,
+        }
+    }
+
+    /// Category name for logs and metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::Client(_) => "client",
+            AppError::Network(_) => "network",
+            AppError::Parse(_) => "parse",
+            AppError::Blocked(_) => "blocked",
+            AppError::NotFound => "not_found",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    /// `Retry-After` seconds, for the errors that are worth waiting out.
+    pub fn retry_after(&self) -> Option<u32> {
+        matches!(self, AppError::Blocked(_)).then_some(BLOCKED_RETRY_AFTER_SECONDS)
+    }
+}
+
 pub type AppResult<T> = Result<T, AppError>;
 
 #[cfg(target_arch = "wasm32")]
@@ -45,3 +80,24 @@ impl From<AppError> for worker::Error {
         worker::Error::from(error.to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_variants_to_http_statuses() {
+        assert_eq!(AppError::Client("bad".into()).status(), 400);
+        assert_eq!(AppError::Network("down".into()).status(), 502);
+        assert_eq!(AppError::Parse("shape".into()).status(), 502);
+        assert_eq!(AppError::Blocked("403".into()).status(), 503);
+        assert_eq!(AppError::NotFound.status(), 404);
+        assert_eq!(AppError::Internal("bug".into()).status(), 500);
+        assert_eq!(
+            AppError::Blocked("403".into()).retry_after(),
+            Some(BLOCKED_RETRY_AFTER_SECONDS)
+        );
+        assert_eq!(AppError::Network("down".into()).retry_after(), None);
+        assert_eq!(AppError::Blocked("403".into()).kind(), "blocked");
+    }
+}
@@ -0,0 +1,501 @@
+//
+// Flight builder: pick N taps for a tasting flight under constraints (alcohol
+// budget, distinct styles, one beer per brewery, minimum rating, price
+// ceiling) and order them light to heavy. Pure, so the worker's `/flight` page
+// and JSON API and the dev runner share it.
+//
+
+use crate::model::{Pour, RatedBeer};
+use crate::normalize;
+use crate::taxonomy::Taxonomy;
+use serde_json::{json, Value};
+
+/// Default flight length (most bars pour four tasters).
+pub const DEFAULT_FLIGHT_SIZE: usize = 4;
+/// Longest flight we'll build.
+pub const MAX_FLIGHT_SIZE: usize = 8;
+/// Best-rated candidates the search considers; keeps it to a few hundred
+/// thousand combinations at `MAX_FLIGHT_SIZE`.
+const MAX_CANDIDATES: usize = 20;
+/// Ounces of pure alcohol in one US standard drink.
+pub const STANDARD_DRINK_OZ: f64 = 0.6;
+/// Taster size assumed when the menu doesn't say.
+const DEFAULT_POUR_OZ: f64 = 5.0;
+/// Value an unrated beer adds to a flight: enough to fill a slot, never enough
+/// to beat a rated one.
+const UNRATED_VALUE: f64 = 3.0;
+
+/// Extra "intensity" per style family on top of ABV when ordering a flight:
+/// hops, acidity and roast linger and belong later than their ABV alone says.
+const FAMILY_INTENSITY: &[(&str, f64)] = &[
+    ("IPA", 1.0),
+    ("Sour", 1.5),
+    ("Strong Ale", 1.0),
+    ("Stout & Porter", 2.0),
+];
+
+/// What the flight must satisfy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlightRequest {
+    /// Number of taps (1..=`MAX_FLIGHT_SIZE`).
+    pub size: usize,
+    /// Total alcohol across the tasters, in US standard drinks.
+    pub max_drinks: Option<f64>,
+    /// Total price of the tasters, in dollars.
+    pub max_price: Option<f64>,
+    /// Lowest acceptable Untappd rating (unrated beers are excluded when set).
+    pub min_rating: Option<f64>,
+    /// At most one beer per style family.
+    pub distinct_styles: bool,
+    /// At most one beer per brewery.
+    pub distinct_breweries: bool,
+}
+
+impl Default for FlightRequest {
+    fn default() -> FlightRequest {
+        FlightRequest {
+            size: DEFAULT_FLIGHT_SIZE,
+            max_drinks: None,
+            max_price: None,
+            min_rating: None,
+            distinct_styles: true,
+            distinct_breweries: true,
+        }
+    }
+}
+
+impl FlightRequest {
+    /// From a query string: `n=5&drinks=2&price=20&min=3.8`, plus
+    /// `styles=any` / `breweries=any` to allow repeats. Malformed values are
+    /// ignored.
+    pub fn from_query(query: &str) -> FlightRequest {
+        let mut request = FlightRequest::default();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            let number = || value.trim().parse::<f64>().ok().filter(|v| *v > 0.0);
+            match key.as_ref() {
+                "n" => {
+                    if let Ok(n) = value.trim().parse::<usize>() {
+                        request.size = n.clamp(1, MAX_FLIGHT_SIZE);
+                    }
+                }
+                "drinks" => request.max_drinks = number(),
+                "price" => request.max_price = number(),
+                "min" => request.min_rating = number(),
+                "styles" => request.distinct_styles = value != "any",
+                "breweries" => request.distinct_breweries = value != "any",
+                _ => {}
+            }
+        }
+        request
+    }
+}
+
+/// One taster in a flight.
+#[derive(Debug, Clone)]
+pub struct FlightPour<'a> {
+    pub beer: &'a RatedBeer,
+    /// Style family label (`taxonomy::StyleFamily::label`).
+    pub style: String,
+    /// The taster pour (smallest size on the menu), if listed.
+    pub pour: Option<&'a Pour>,
+    /// Standard drinks in this pour; `None` with an unknown ABV.
+    pub drinks: Option<f64>,
+}
+
+impl FlightPour<'_> {
+    pub fn price(&self) -> Option<f64> {
+        self.pour.and_then(|p| p.price)
+    }
+}
+
+/// A built flight, lightest first.
+#[derive(Debug, Clone)]
+pub struct Flight<'a> {
+    pub pours: Vec<FlightPour<'a>>,
+}
+
+impl Flight<'_> {
+    /// Standard drinks across the flight (unknown ABVs count as zero).
+    pub fn total_drinks(&self) -> f64 {
+        self.pours.iter().filter_map(|p| p.drinks).sum()
+    }
+
+    /// Total price; `None` if any taster's price isn't listed.
+    pub fn total_price(&self) -> Option<f64> {
+        self.pours.iter().map(FlightPour::price).sum()
+    }
+
+    /// The `/flight.json` body.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "taps": self.pours.iter().map(|p| json!({
+                "tap": p.beer.entry.tap_number,
+                "brewery": p.beer.entry.brewery,
+                "name": p.beer.entry.name,
+                "style": p.style,
+                "abv": p.beer.entry.abv,
+                "rating": p.beer.rating.as_ref().and_then(|r| r.score()),
+                "pour": p.pour.map(|p| p.size.as_str()),
+                "price": p.price(),
+            })).collect::<Vec<Value>>(),
+            "total_drinks": (self.total_drinks() * 100.0).round() / 100.0,
+            "total_price": self.total_price(),
+        })
+    }
+}
+
+/// A candidate with everything the search checks precomputed.
+struct Candidate<'a> {
+    pour: FlightPour<'a>,
+    family: String,
+    brewery: String,
+    value: f64,
+    intensity: f64,
+}
+
+/// The highest-rated flight satisfying `request`, or `None` if none is found.
+/// Taps no flight could afford are dropped first. Among the `MAX_CANDIDATES`
+/// best-rated of the rest the search is exhaustive, so there a budget never
+/// crowds out a better combination the way a greedy pick would; if those taps
+/// admit no flight at all, a greedy pass over every tap looks for one.
+pub fn build_flight<'a>(
+    beers: &'a [RatedBeer],
+    taxonomy: &Taxonomy,
+    request: &FlightRequest,
+) -> Option<Flight<'a>> {
+    let mut candidates: Vec<Candidate<'a>> = beers
+        .iter()
+        .filter_map(|b| candidate(b, taxonomy, request))
+        .collect();
+    // A tap over budget even with the cheapest others filling out the flight
+    // mustn't take one of the search's places.
+    let (drinks, price) = cheapest(&candidates, request.size - 1);
+    candidates.retain(|c| !over_budget(request, c.drinks() + drinks, c.price() + price));
+    candidates.sort_by(|a, b| {
+        b.value
+            .partial_cmp(&a.value)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut search = Search {
+        candidates: &candidates[..candidates.len().min(MAX_CANDIDATES)],
+        request,
+        chosen: Vec::with_capacity(request.size),
+        best: None,
+    };
+    search.extend(0, 0.0, 0.0, 0.0);
+    if search.best.is_none() && candidates.len() > MAX_CANDIDATES {
+        search.candidates = &candidates;
+        search.greedy();
+    }
+    let (_, chosen) = search.best?;
+
+    let mut picked: Vec<&Candidate> = chosen.iter().map(|&i| &candidates[i]).collect();
+    picked.sort_by(|a, b| {
+        a.intensity
+            .partial_cmp(&b.intensity)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(
+                a.pour
+                    .beer
+                    .entry
+                    .tap_number
+                    .cmp(&b.pour.beer.entry.tap_number),
+            )
+    });
+    Some(Flight {
+        pours: picked.into_iter().map(|c| c.pour.clone()).collect(),
+    })
+}
+
+fn candidate<'a>(
+    beer: &'a RatedBeer,
+    taxonomy: &Taxonomy,
+    request: &FlightRequest,
+) -> Option<Candidate<'a>> {
+    let score = beer.rating.as_ref().and_then(|r| r.score());
+    if request
+        .min_rating
+        .is_some_and(|min| score.is_none_or(|s| s < min))
+    {
+        return None;
+    }
+    let pour = beer.entry.pours.first();
+    let price = pour.and_then(|p| p.price);
+    if request.max_price.is_some() && price.is_none() {
+        return None; // can't promise a price ceiling we can't see
+    }
+    let oz = pour.and_then(|p| p.oz).unwrap_or(DEFAULT_POUR_OZ);
+    let drinks = beer
+        .entry
+        .abv
+        .map(|abv| oz * abv / 100.0 / STANDARD_DRINK_OZ);
+    if request.max_drinks.is_some() && drinks.is_none() {
+        return None;
+    }
+
+    let family = taxonomy.classify(beer);
+    let intensity = beer.entry.abv.unwrap_or(0.0)
+        + FAMILY_INTENSITY
+            .iter()
+            .find(|(f, _)| *f == family.family)
+            .map_or(0.0, |(_, extra)| *extra);
+    Some(Candidate {
+        pour: FlightPour {
+            beer,
+            style: family.label(),
+            pour,
+            drinks,
+        },
+        family: family.family,
+        brewery: normalize::brewery_core(&beer.entry.brewery),
+        value: score.unwrap_or(UNRATED_VALUE),
+        intensity,
+    })
+}
+
+impl Candidate<'_> {
+    fn drinks(&self) -> f64 {
+        self.pour.drinks.unwrap_or(0.0)
+    }
+
+    fn price(&self) -> f64 {
+        self.pour.price().unwrap_or(0.0)
+    }
+}
+
+/// The fewest drinks and the lowest price any `n` of `candidates` could add up
+/// to (each minimized on its own, so a lower bound).
+fn cheapest(candidates: &[Candidate], n: usize) -> (f64, f64) {
+    let lowest = |mut values: Vec<f64>| {
+        values.sort_by(f64::total_cmp);
+        values.into_iter().take(n).sum::<f64>()
+    };
+    (
+        lowest(candidates.iter().map(Candidate::drinks).collect()),
+        lowest(candidates.iter().map(Candidate::price).collect()),
+    )
+}
+
+fn over_budget(request: &FlightRequest, drinks: f64, price: f64) -> bool {
+    request.max_drinks.is_some_and(|max| drinks > max + 1e-9)
+        || request.max_price.is_some_and(|max| price > max + 1e-9)
+}
+
+/// Depth-first search over candidate combinations, pruning on the budgets and
+/// the distinctness rules.
+struct Search<'s, 'a> {
+    candidates: &'s [Candidate<'a>],
+    request: &'s FlightRequest,
+    chosen: Vec<usize>,
+    best: Option<(f64, Vec<usize>)>,
+}
+
+impl Search<'_, '_> {
+    fn extend(&mut self, from: usize, value: f64, drinks: f64, price: f64) {
+        if self.chosen.len() == self.request.size {
+            if self.best.as_ref().is_none_or(|(best, _)| value > *best) {
+                self.best = Some((value, self.chosen.clone()));
+            }
+            return;
+        }
+        let needed = self.request.size - self.chosen.len();
+        for i in from..self.candidates.len() {
+            if self.candidates.len() - i < needed {
+                break;
+            }
+            let c = &self.candidates[i];
+            let drinks = drinks + c.drinks();
+            let price = price + c.price();
+            if over_budget(self.request, drinks, price) || self.clashes(c) {
+                continue;
+            }
+            self.chosen.push(i);
+            self.extend(i + 1, value + c.value, drinks, price);
+            self.chosen.pop();
+        }
+    }
+
+    /// Take candidates in rating order, each only if the budgets still leave
+    /// room to finish the flight with the cheapest of the ones after it.
+    fn greedy(&mut self) {
+        let candidates = self.candidates;
+        let (mut value, mut drinks, mut price) = (0.0, 0.0, 0.0);
+        for (i, c) in candidates.iter().enumerate() {
+            let needed = self.request.size - self.chosen.len();
+            if needed == 0 {
+                break;
+            }
+            let (rest_drinks, rest_price) = cheapest(&candidates[i + 1..], needed - 1);
+            if self.clashes(c)
+                || over_budget(
+                    self.request,
+                    drinks + c.drinks() + rest_drinks,
+                    price + c.price() + rest_price,
+                )
+            {
+                continue;
+            }
+            self.chosen.push(i);
+            value += c.value;
+            drinks += c.drinks();
+            price += c.price();
+        }
+        if self.chosen.len() == self.request.size {
+            self.best = Some((value, std::mem::take(&mut self.chosen)));
+        }
+    }
+
+    fn clashes(&self, c: &Candidate) -> bool {
+        self.chosen.iter().any(|&j| {
+            let other = &self.candidates[j];
+            (self.request.distinct_styles && other.family == c.family)
+                || (self.request.distinct_breweries && other.brewery == c.brewery)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BeerDetails, BeerEntry, LookupOutcome, RatingResult};
+
+    fn beer(tap: i32, brewery: &str, style: &str, abv: f64, score: &str, price: f64) -> RatedBeer {
+        RatedBeer {
+            entry: BeerEntry {
+                tap_number: tap,
+                brewery: brewery.into(),
+                brewery_full: brewery.into(),
+                name: format!("Beer {tap}"),
+                full_name: format!("{brewery} Beer {tap}"),
+                abv: Some(abv),
+                ibu: None,
+                category: String::new(),
+                category_order: None,
+                origin: String::new(),
+                style: style.into(),
+                date_added: None,
+                pours: vec![
+                    Pour::parse("5 oz", &format!("${price}")),
+                    Pour::parse("16 oz", "$9.00"),
+                ],
+            },
+            rating: Some(RatingResult {
+                rating: score.into(),
+                url: format!("https://untappd.com/b/beer/{tap}"),
+                rating_count: 1000,
+                matched_by: None,
+                details: BeerDetails::default(),
+                fetched_at: None,
+            }),
+            outcome: LookupOutcome::Cached,
+        }
+    }
+
+    fn menu() -> Vec<RatedBeer> {
+        vec![
+            beer(1, "Alpha", "Imperial Stout", 12.0, "4.40", 5.0),
+            beer(2, "Alpha", "Hazy IPA", 6.5, "4.30", 4.0),
+            beer(3, "Bravo", "West Coast IPA", 7.0, "4.20", 4.0),
+            beer(4, "Charlie", "Pilsner", 5.0, "3.80", 3.0),
+            beer(5, "Delta", "Gose", 4.2, "3.90", 3.5),
+            beer(6, "Echo", "Hefeweizen", 5.2, "3.60", 3.0),
+            beer(7, "Foxtrot", "Porter", 6.0, "3.70", 3.5),
+        ]
+    }
+
+    fn taps(flight: &Flight) -> Vec<i32> {
+        flight
+            .pours
+            .iter()
+            .map(|p| p.beer.entry.tap_number)
+            .collect()
+    }
+
+    #[test]
+    fn best_flight_respects_distinct_styles_and_breweries() {
+        let beers = menu();
+        let flight = build_flight(&beers, &Taxonomy::builtin(), &FlightRequest::default()).unwrap();
+        // Stout (Alpha) rules out the other Alpha; one IPA; then the best of
+        // the rest. Ordered light to heavy: the gose's acidity puts it after
+        // the stronger pilsner, the stout last.
+        assert_eq!(taps(&flight), [4, 5, 3, 1]);
+        assert_eq!(flight.total_price(), Some(15.5));
+    }
+
+    #[test]
+    fn budgets_and_minimum_rating_constrain_the_flight() {
+        let beers = menu();
+        let request = FlightRequest::from_query("n=3&drinks=1.5&min=3.75");
+        let flight = build_flight(&beers, &Taxonomy::builtin(), &request).unwrap();
+        // 12% stout alone is a standard drink; it can't fit with two more.
+        assert!(!taps(&flight).contains(&1));
+        assert!(flight.total_drinks() <= 1.5);
+        assert!(flight
+            .pours
+            .iter()
+            .all(|p| p.beer.rating.as_ref().unwrap().score().unwrap() >= 3.75));
+
+        let cheap = FlightRequest::from_query("n=3&price=10");
+        let flight = build_flight(&beers, &Taxonomy::builtin(), &cheap).unwrap();
+        assert!(flight.total_price().unwrap() <= 10.0);
+
+        let impossible = FlightRequest::from_query("n=3&price=5");
+        assert!(build_flight(&beers, &Taxonomy::builtin(), &impossible).is_none());
+    }
+
+    #[test]
+    fn budgets_reach_past_the_best_rated_taps() {
+        // The 20 best taps cost $4 and the rest $3: three $4 pours bust $10,
+        // so the flight has to reach past the top 20.
+        let beers: Vec<RatedBeer> = (1..=40)
+            .map(|tap| {
+                let (score, price) = if tap <= 20 {
+                    (format!("{:.2}", 4.5 - f64::from(tap) / 100.0), 4.0)
+                } else {
+                    (format!("{:.2}", 3.9 - f64::from(tap) / 100.0), 3.0)
+                };
+                beer(
+                    tap,
+                    &format!("Brewery {tap}"),
+                    "Pilsner",
+                    5.0,
+                    &score,
+                    price,
+                )
+            })
+            .collect();
+        let request = FlightRequest::from_query("n=3&price=10&styles=any");
+        let flight = build_flight(&beers, &Taxonomy::builtin(), &request).unwrap();
+        assert_eq!(taps(&flight), [1, 21, 22]);
+        assert_eq!(flight.total_price(), Some(10.0));
+
+        // Taps no flight can afford are dropped before the search, leaving
+        // the exhaustive pass to the ones that fit.
+        let request = FlightRequest::from_query("n=3&price=9&styles=any");
+        let flight = build_flight(&beers, &Taxonomy::builtin(), &request).unwrap();
+        assert_eq!(taps(&flight), [21, 22, 23]);
+    }
+
+    #[test]
+    fn repeats_can_be_allowed() {
+        let beers = menu();
+        let request = FlightRequest::from_query("n=2&styles=any&breweries=any");
+        let flight = build_flight(&beers, &Taxonomy::builtin(), &request).unwrap();
+        assert_eq!(taps(&flight), [2, 1]);
+        assert_eq!(FlightRequest::from_query("n=99").size, MAX_FLIGHT_SIZE);
+    }
+
+    #[test]
+    fn json_lists_taps_and_totals() {
+        let beers = menu();
+        let request = FlightRequest::from_query("n=1&min=4.35");
+        let flight = build_flight(&beers, &Taxonomy::builtin(), &request).unwrap();
+        let json = flight.to_json();
+        assert_eq!(json["taps"][0]["tap"], 1);
+        assert_eq!(json["taps"][0]["pour"], "5 oz");
+        assert_eq!(json["taps"][0]["style"], "Stout & Porter / Imperial Stout");
+        assert_eq!(json["total_drinks"], 1.0);
+        assert_eq!(json["total_price"], 5.0);
+    }
+}
@@ -0,0 +1,87 @@
+//
+// Rating freshness. Cached ratings carry when they were fetched. Their KV TTLs
+// are jittered, so a menu's worth of ratings fetched in one cold load doesn't
+// expire in one go a week later. Ratings past `REFRESH_AFTER_DAYS` are still
+// served, and refreshed in the background as the subrequest budget allows
+// (see `budget::RatingPlan::refreshes`). Most are replaced well before their
+// TTL runs out.
+//
+
+use crate::model::RatingResult;
+use crate::retry;
+use chrono::{DateTime, Duration, Utc};
+
+/// Cache successful ratings for about a week.
+pub const RATING_TTL_SECONDS: u64 = 7 * 24 * 60 * 60;
+/// Cache confirmed "no rating found" (and unrated beers) for about a day:
+/// cheaper to recheck, and a real rating appears sooner.
+pub const NOT_FOUND_TTL_SECONDS: u64 = 24 * 60 * 60;
+/// TTLs land anywhere within this share either side of nominal.
+pub const TTL_JITTER: f64 = 0.2;
+/// Age at which a cached rating is refreshed in the background. Below the
+/// shortest jittered TTL (5.6 days), so a menu seen daily rarely misses.
+pub const REFRESH_AFTER_DAYS: i64 = 5;
+
+/// `nominal` seconds, jittered by up to `TTL_JITTER` either way. `seed` picks
+/// the point; `seed_for` gives each write its own.
+pub fn jittered_ttl(nominal: u64, seed: u64) -> u64 {
+    let spread = 1.0 - TTL_JITTER + 2.0 * TTL_JITTER * retry::jitter(seed, 0);
+    (nominal as f64 * spread).round() as u64
+}
+
+/// A seed from a cache key and the time of the write (FNV-1a).
+pub fn seed_for(key: &str, now: DateTime<Utc>) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    }) ^ now.timestamp_millis() as u64
+}
+
+/// Whether a cached rating is old enough to refresh. Undated ones (cached
+/// before we kept track) are.
+pub fn needs_refresh(rating: &RatingResult, now: DateTime<Utc>) -> bool {
+    rating
+        .fetched_at
+        .is_none_or(|at| now - at >= Duration::days(REFRESH_AFTER_DAYS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::parse_utc;
+    use crate::model::BeerDetails;
+
+    #[test]
+    fn ttls_spread_around_nominal() {
+        let now = parse_utc("2026-06-28T07:30:00Z").unwrap();
+        let ttls: Vec<u64> = (0..200)
+            .map(|i| jittered_ttl(RATING_TTL_SECONDS, seed_for(&format!("rating:v9:{i}"), now)))
+            .collect();
+        let (min, max) = (*ttls.iter().min().unwrap(), *ttls.iter().max().unwrap());
+        assert!(min >= (RATING_TTL_SECONDS as f64 * 0.8) as u64, "{min}");
+        assert!(max <= (RATING_TTL_SECONDS as f64 * 1.2) as u64, "{max}");
+        // Spread out, not bunched: over a day apart end to end.
+        assert!(max - min > 24 * 60 * 60, "{min}..{max}");
+        assert!(min > Duration::days(REFRESH_AFTER_DAYS).num_seconds() as u64);
+        // Same key, same instant, same TTL.
+        assert_eq!(
+            jittered_ttl(RATING_TTL_SECONDS, seed_for("k", now)),
+            jittered_ttl(RATING_TTL_SECONDS, seed_for("k", now))
+        );
+    }
+
+    #[test]
+    fn old_or_undated_ratings_need_refresh() {
+        let now = parse_utc("2026-06-28T07:30:00Z").unwrap();
+        let fetched = |at: Option<&str>| RatingResult {
+            rating: "3.90".into(),
+            url: String::new(),
+            rating_count: 100,
+            matched_by: None,
+            details: BeerDetails::default(),
+            fetched_at: at.and_then(parse_utc),
+        };
+        assert!(!needs_refresh(&fetched(Some("2026-06-24T07:30:00Z")), now));
+        assert!(needs_refresh(&fetched(Some("2026-06-23T07:30:00Z")), now));
+        assert!(needs_refresh(&fetched(None), now));
+    }
+}
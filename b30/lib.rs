@@ -7,10 +7,26 @@
 // gated to the wasm32 target.
 //
 
+pub mod admin;
+pub mod budget;
+pub mod clock;
+pub mod diagnostics;
 pub mod error;
+pub mod flight;
+pub mod freshness;
+pub mod metrics;
 pub mod model;
+pub mod normalize;
+pub mod percentile;
+pub mod qr;
+pub mod recommend;
 pub mod render;
+pub mod retry;
+pub mod schema;
+pub mod snapshot;
 pub mod taphunter;
+pub mod taxonomy;
+pub mod tried;
 pub mod untappd;
 
 // ---------------------------------------------------------------------------
@@ -18,28 +34,89 @@ pub mod untappd;
 // ---------------------------------------------------------------------------
 #[cfg(target_arch = "wasm32")]
 mod worker_glue {
+    use crate::admin::{self, Access};
+    use crate::budget::{self, Budget, RatingPlan};
+    use crate::clock::{self, VenueClock, VenueTimezone};
+    use crate::diagnostics::{self, Report};
     use crate::error::{AppError, AppResult};
-    use crate::model::{sort_rated, BeerEntry, RatedBeer};
+    use crate::flight::{self, FlightRequest};
+    use crate::freshness;
+    use crate::metrics::{self, Counters, MetricsSink, PendingStats, Phase, RequestLog};
+    use crate::model::{
+        sort_rated_with, BeerEntry, CategoryOrder, LookupOutcome, LookupStats, RatedBeer,
+        RatingResult, SortOptions, WithinCategory,
+    };
+    use crate::recommend::{self, TasteProfile};
+    use crate::render::{Heatmap, PrintOptions, RenderOptions, ScreenOptions};
+    use crate::retry::{self, Attempt, Decision, RateLimiter, RetryPolicy};
+    use crate::schema;
+    use crate::snapshot::{self, MenuSnapshot};
+    use crate::taphunter::UrlStrategy;
+    use crate::taxonomy::{GroupBy, StyleView, Taxonomy};
+    use crate::tried::{self, Identity, TriedList};
     use crate::{render, taphunter, untappd};
+    use chrono::{DateTime, Utc};
     use futures::stream::{self, StreamExt};
+    use serde_json::json;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
     use worker::kv::KvStore;
     use worker::{
-        console_log, event, Context, Env, Fetch, Headers, Method, Request, RequestInit, Response,
-        Router,
+        console_log, event, Context, Date, Delay, Env, Fetch, Headers, Method, Request,
+        RequestInit, Response, RouteContext, Router, Url,
     };
 
     const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
     const CONCURRENT_REQUESTS: usize = 5;
-    /// Cache successful ratings for a week.
-    const CACHE_TTL_SECONDS: u64 = 7 * 24 * 60 * 60;
-    /// Cache confirmed "no rating found" for a day (cheaper to recheck).
-    const NOT_FOUND_TTL_SECONDS: u64 = 24 * 60 * 60;
     /// Bump this to invalidate all cached entries at once (e.g. after a parser
     /// change or to flush the poisoned "N/A" entries from the outage).
     /// v3: unrated-but-existing beers are now cached as a linked "N/A".
     /// v4: fuzzy Algolia matching (ignorePlurals + lastWords) changes results.
     /// v5: dropped lastWords (it force-matched beers not really on Untappd).
-    const CACHE_VERSION: &str = "v5";
+    /// v6: normalized search strings (brewery suffixes/aliases, diacritics).
+    /// v7: search variants with brewery-confirmed matching.
+    /// v8: entries are `RatingResult` JSON (with check-in counts), not HTML cells.
+    /// v9: cached JSON carries Untappd details (labels, style, IBU, awards).
+    const CACHE_VERSION: &str = "v9";
+    /// Cached value for a confirmed "not on Untappd".
+    const NOT_FOUND_MARKER: &str = "N/A";
+
+    /// Per-request state, shared with every route as the router's data.
+    #[derive(Default)]
+    struct RequestState {
+        log: RefCell<RequestLog>,
+        /// Stale cached ratings to refresh after responding (see `main`).
+        refresh: RefCell<Vec<BeerEntry>>,
+    }
+
+    type Shared = Rc<RequestState>;
+
+    thread_local! {
+        /// Where finished requests are counted: batched for KV, per isolate.
+        static STATS: RefCell<Box<dyn MetricsSink>> =
+            RefCell::new(Box::new(PendingStats::default()));
+        /// Upstream pacing, shared by every request this isolate serves.
+        static LIMITER: RefCell<RateLimiter> = RefCell::new(RateLimiter::default());
+    }
+
+    fn now_ms() -> u64 {
+        Date::now().as_millis()
+    }
+
+    /// Run `f`, adding its time to `phase`.
+    fn timed<T>(log: &RefCell<RequestLog>, phase: Phase, f: impl FnOnce() -> T) -> T {
+        let started = now_ms();
+        let result = f();
+        log.borrow_mut()
+            .phase(phase, now_ms().saturating_sub(started));
+        result
+    }
+
+    /// Log a structured event for the current request.
+    fn log_event(log: &RefCell<RequestLog>, name: &str, fields: serde_json::Value) {
+        console_log!("{}", log.borrow().event(name, fields));
+    }
 
     fn cache_key(brewery: &str, name: &str) -> String {
         format!(
@@ -50,28 +127,71 @@ mod worker_glue {
         )
     }
 
+    /// One try at a request, whatever status comes back.
+    async fn exchange(url: &str, init: &RequestInit) -> AppResult<Attempt> {
+        let req = Request::new_with_init(url, init)
+            .map_err(|e| AppError::Client(format!("Failed to create request: {e}")))?;
+        let mut resp = match Fetch::Request(req).send().await {
+            Ok(resp) => resp,
+            Err(e) => return Ok(Attempt::Transport(format!("Failed to get response: {e}"))),
+        };
+        let retry_after = resp.headers().get("Retry-After").ok().flatten();
+        Ok(match resp.text().await {
+            Ok(body) => Attempt::Response {
+                status: resp.status_code(),
+                retry_after,
+                body,
+            },
+            Err(e) => Attempt::Transport(format!("Failed to read response: {e}")),
+        })
+    }
+
+    /// Send a request under the default `RetryPolicy`, paced by the isolate's
+    /// per-host `RateLimiter`; the body of a 2xx response, else the classified
+    /// error (see `retry::classify`).
+    async fn send(url: &str, init: &RequestInit) -> AppResult<String> {
+        let host = retry::host_of(url);
+        let policy = RetryPolicy::default();
+        let seed = now_ms() ^ url.len() as u64;
+        let mut attempt = 1;
+        loop {
+            let wait = LIMITER.with(|l| l.borrow_mut().reserve(&host, now_ms()));
+            sleep_ms(wait).await;
+            let outcome = exchange(url, init).await?;
+            match policy.decide(
+                &host,
+                attempt,
+                outcome,
+                Utc::now(),
+                retry::jitter(seed, attempt),
+            ) {
+                Decision::Done(result) => return result,
+                Decision::Retry { wait_ms } => {
+                    sleep_ms(wait_ms).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    async fn sleep_ms(ms: u64) {
+        if ms > 0 {
+            Delay::from(Duration::from_millis(ms)).await;
+        }
+    }
+
     /// GET a URL with our browser-ish User-Agent and return the body text.
     async fn fetch_text(url: &str) -> AppResult<String> {
         let headers = Headers::new();
         headers
             .set("User-Agent", USER_AGENT)
             .map_err(|e| AppError::Client(format!("Failed to set headers: {e}")))?;
-        let req = Request::new_with_init(
-            url,
-            &RequestInit {
-                method: Method::Get,
-                headers,
-                ..Default::default()
-            },
-        )
-        .map_err(|e| AppError::Client(format!("Failed to create request: {e}")))?;
-        let mut resp = Fetch::Request(req)
-            .send()
-            .await
-            .map_err(|e| AppError::Network(format!("Failed to get response: {e}")))?;
-        resp.text()
-            .await
-            .map_err(|e| AppError::Network(format!("Failed to read response: {e}")))
+        let init = RequestInit {
+            method: Method::Get,
+            headers,
+            ..Default::default()
+        };
+        send(url, &init).await
     }
 
     /// POST an Algolia query and return the response body.
@@ -86,120 +206,948 @@ mod worker_glue {
         init.with_method(Method::Post)
             .with_headers(headers)
             .with_body(Some(q.body.clone().into()));
-        let req = Request::new_with_init(&q.url, &init)
-            .map_err(|e| AppError::Client(format!("Failed to create request: {e}")))?;
-        let mut resp = Fetch::Request(req)
-            .send()
-            .await
-            .map_err(|e| AppError::Network(format!("Failed to get response: {e}")))?;
-        resp.text()
-            .await
-            .map_err(|e| AppError::Network(format!("Failed to read response: {e}")))
+        send(&q.url, &init).await
     }
 
-    /// Resolve a single beer's rating cell ("N/A" when unresolved).
-    async fn resolve_rating(brewery: &str, name: &str) -> AppResult<crate::model::RatingResult> {
-        let search = format!("{brewery} {name}");
-        let q = untappd::build_query(&search);
-        let body = algolia_post(&q).await?;
-        untappd::parse_rating(&body)
+    /// Look up `misses` (indexes into `entries`) with batched Algolia queries,
+    /// a round of variants per request (see `untappd::BatchLookup`).
+    async fn resolve_ratings(
+        entries: &[BeerEntry],
+        misses: &[usize],
+        log: &RefCell<RequestLog>,
+    ) -> Vec<(usize, AppResult<RatingResult>)> {
+        let mut lookup = untappd::BatchLookup::new(misses.iter().map(|&idx| (idx, &entries[idx])));
+        while let Some(batch) = lookup.next_batch() {
+            let started = now_ms();
+            let body = algolia_post(&batch).await;
+            log.borrow_mut().algolia(now_ms().saturating_sub(started));
+            lookup.offer(body);
+        }
+        lookup.finish()
     }
 
-    /// Fetch ratings for all entries concurrently, using KV as a cache.
-    /// Only successful ratings and confirmed not-founds are cached; transient
-    /// failures (network/blocked) are never cached so they self-heal.
-    async fn fetch_ratings(entries: &[BeerEntry], kv: &KvStore) -> Vec<String> {
-        let results: Vec<(usize, String)> = stream::iter(entries.iter().enumerate())
-            .map(|(idx, entry)| async move {
-                let key = cache_key(&entry.brewery, &entry.name);
-
-                if let Ok(Some(cached)) = kv.get(&key).text().await {
-                    return (idx, cached);
-                }
-
-                let (cell, ttl) = match resolve_rating(&entry.brewery, &entry.name).await {
-                    Ok(rating) => {
-                        // Existing-but-unrated beers still link, but show "N/A";
-                        // recheck them daily so a real rating appears sooner.
-                        let ttl = if rating.rating == "N/A" {
-                            NOT_FOUND_TTL_SECONDS
-                        } else {
-                            CACHE_TTL_SECONDS
-                        };
-                        (rating.to_cell(), Some(ttl))
-                    }
-                    Err(AppError::NotFound) => ("N/A".to_string(), Some(NOT_FOUND_TTL_SECONDS)),
-                    Err(e @ AppError::Blocked(_)) => {
-                        console_log!(
-                            "Rating blocked for '{} {}': {}",
-                            entry.brewery,
-                            entry.name,
-                            e
-                        );
-                        ("N/A".to_string(), None) // never cache a block
-                    }
-                    Err(e) => {
-                        console_log!("Rating error for '{} {}': {}", entry.brewery, entry.name, e);
-                        ("N/A".to_string(), None) // never cache transient failures
-                    }
-                };
+    /// A KV write caching `value` under `key` for about `nominal_ttl` seconds
+    /// (jittered, see `freshness`).
+    fn cache_write(key: String, value: String, nominal_ttl: u64) -> (String, String, u64) {
+        let ttl = freshness::jittered_ttl(nominal_ttl, freshness::seed_for(&key, Utc::now()));
+        (key, value, ttl)
+    }
 
-                if let Some(ttl) = ttl {
-                    if let Ok(put) = kv.put(&key, cell.clone()) {
-                        if let Err(e) = put.expiration_ttl(ttl).execute().await {
-                            console_log!("Failed to cache rating for key '{}': {}", key, e);
-                        }
+    /// Cache a freshly fetched rating. Existing-but-unrated beers still link,
+    /// but show "N/A"; recheck them daily so a real rating appears sooner.
+    fn rating_write(entry: &BeerEntry, rating: &RatingResult) -> (String, String, u64) {
+        let ttl = if rating.score().is_none() {
+            freshness::NOT_FOUND_TTL_SECONDS
+        } else {
+            freshness::RATING_TTL_SECONDS
+        };
+        cache_write(
+            cache_key(&entry.brewery, &entry.name),
+            rating.to_cache_json(),
+            ttl,
+        )
+    }
+
+    async fn write_cache(
+        kv: &KvStore,
+        writes: Vec<(String, String, u64)>,
+        log: &RefCell<RequestLog>,
+    ) {
+        stream::iter(writes)
+            .map(|(key, value, ttl)| async move {
+                if let Ok(put) = kv.put(&key, value) {
+                    if let Err(e) = put.expiration_ttl(ttl).execute().await {
+                        log_event(
+                            log,
+                            "cache_write_failed",
+                            json!({"key": key, "error": e.to_string()}),
+                        );
                     }
                 }
-
-                (idx, cell)
             })
             .buffer_unordered(CONCURRENT_REQUESTS)
-            .collect()
+            .collect::<Vec<()>>()
             .await;
+    }
+
+    /// Fetch ratings for the entries `plan` can afford, using KV as a cache,
+    /// with how each lookup went; the rest are `Pending`. Cache reads and
+    /// writes run concurrently; the misses share batched Algolia requests.
+    /// Only successful ratings and confirmed not-founds are cached; transient
+    /// failures (network/blocked) are never cached so they self-heal.
+    ///
+    /// Also returns the cached ratings due a refresh that the budget leaves
+    /// room for (see `freshness`), for `refresh_ratings` after responding.
+    async fn fetch_ratings(
+        entries: &[BeerEntry],
+        kv: &KvStore,
+        mut plan: RatingPlan,
+        log: &RefCell<RequestLog>,
+    ) -> (Vec<(Option<RatingResult>, LookupOutcome)>, Vec<usize>) {
+        let now = Utc::now();
+        let mut ratings = vec![(None, LookupOutcome::Pending); entries.len()];
+        let mut stale = Vec::new();
+        loop {
+            let chunk = plan.next_reads();
+            if chunk.is_empty() {
+                break;
+            }
+            let cached: Vec<(usize, Option<String>)> = stream::iter(chunk)
+                .map(|idx| async move {
+                    let key = cache_key(&entries[idx].brewery, &entries[idx].name);
+                    (idx, kv.get(&key).text().await.ok().flatten())
+                })
+                .buffer_unordered(CONCURRENT_REQUESTS)
+                .collect()
+                .await;
+            for (idx, cached) in cached {
+                match cached.as_deref() {
+                    Some(NOT_FOUND_MARKER) => ratings[idx] = (None, LookupOutcome::Cached),
+                    Some(json) => match RatingResult::from_cache_json(json) {
+                        Some(rating) => {
+                            if freshness::needs_refresh(&rating, now) {
+                                stale.push(idx);
+                            }
+                            ratings[idx] = (Some(rating), LookupOutcome::Cached);
+                        }
+                        // Malformed entry: look it up again and overwrite it.
+                        None => plan.missed(idx),
+                    },
+                    None => plan.missed(idx),
+                }
+            }
+        }
+
+        let misses = plan.take_lookups();
+        let deferred = plan.deferred();
+        if !deferred.is_empty() {
+            log_event(
+                log,
+                "ratings_deferred",
+                json!({
+                    "pending": deferred.len(),
+                    "subrequests": plan.budget().spent(),
+                }),
+            );
+        }
+
+        let mut writes = Vec::new();
+        for (idx, result) in resolve_ratings(entries, &misses, log).await {
+            let entry = &entries[idx];
+            match result {
+                Ok(mut rating) => {
+                    log_event(
+                        log,
+                        "rating_matched",
+                        json!({
+                            "brewery": entry.brewery,
+                            "beer": entry.name,
+                            "matched_by": rating
+                                .matched_by
+                                .map_or("unconfirmed top hit", |v| v.label()),
+                        }),
+                    );
+                    rating.fetched_at = Some(now);
+                    writes.push(rating_write(entry, &rating));
+                    ratings[idx] = (Some(rating), LookupOutcome::Fresh);
+                }
+                Err(AppError::NotFound) => {
+                    writes.push(cache_write(
+                        cache_key(&entry.brewery, &entry.name),
+                        NOT_FOUND_MARKER.to_string(),
+                        freshness::NOT_FOUND_TTL_SECONDS,
+                    ));
+                    ratings[idx] = (None, LookupOutcome::NotFound);
+                }
+                Err(e) => {
+                    log.borrow_mut().error(&e);
+                    log_event(
+                        log,
+                        "rating_error",
+                        json!({
+                            "brewery": entry.brewery,
+                            "beer": entry.name,
+                            "kind": e.kind(),
+                            "error": e.to_string(),
+                        }),
+                    );
+                    // never cache a block or other transient failure
+                    ratings[idx] = (None, LookupOutcome::from_error(&e));
+                }
+            }
+        }
 
-        let mut cells = vec!["N/A".to_string(); entries.len()];
-        for (idx, cell) in results {
-            cells[idx] = cell;
+        write_cache(kv, writes, log).await;
+        let refresh = plan.refreshes(stale);
+        (ratings, refresh)
+    }
+
+    /// Look up `entries` again and re-cache what's found; run after the
+    /// response is sent. A failure or not-found leaves the cached rating to be
+    /// served until its TTL runs out.
+    async fn refresh_ratings(kv: KvStore, entries: Vec<BeerEntry>, log: RequestLog) {
+        let log = RefCell::new(log);
+        let ids: Vec<usize> = (0..entries.len()).collect();
+        let mut writes = Vec::new();
+        for (idx, result) in resolve_ratings(&entries, &ids, &log).await {
+            if let Ok(mut rating) = result {
+                rating.fetched_at = Some(Utc::now());
+                writes.push(rating_write(&entries[idx], &rating));
+            }
         }
-        cells
+        log_event(
+            &log,
+            "ratings_refreshed",
+            json!({"refreshed": writes.len(), "kept": entries.len() - writes.len()}),
+        );
+        write_cache(&kv, writes, &log).await;
     }
 
-    async fn build_menu_html(kv: &KvStore) -> AppResult<String> {
-        // 1. Resolve the TapHunter JSON endpoint, then the menu.
-        let bigscreen = fetch_text(&taphunter::bigscreen_url()).await?;
-        let json_url = taphunter::parse_json_url(&bigscreen)?;
-        let menu_json = fetch_text(&json_url).await?;
-        let entries = taphunter::parse_menu(&menu_json)?;
+    /// The venue's clock, in the timezone from the `VENUE_TZ` var (an IANA name
+    /// or "+HH:MM"; Pacific if unset or unrecognized).
+    fn venue_clock<D>(ctx: &RouteContext<D>) -> VenueClock {
+        let timezone = ctx
+            .var("VENUE_TZ")
+            .ok()
+            .and_then(|tz| VenueTimezone::parse(&tz.to_string()))
+            .unwrap_or(clock::PACIFIC);
+        VenueClock::system(timezone)
+    }
 
-        // 2. Cross-reference Untappd ratings (cached).
-        let ratings = fetch_ratings(&entries, kv).await;
+    /// What a menu page may spend on rating lookups: the `SUBREQUEST_LIMIT`
+    /// var (50, the free plan's, if unset) less the page's other subrequests.
+    fn subrequest_budget<D>(ctx: &RouteContext<D>) -> Budget {
+        let limit = ctx
+            .var("SUBREQUEST_LIMIT")
+            .ok()
+            .and_then(|v| v.to_string().trim().parse().ok())
+            .unwrap_or(budget::DEFAULT_SUBREQUEST_LIMIT);
+        let mut budget = Budget::new(limit);
+        budget.spend(budget::page_overhead());
+        budget
+    }
+
+    /// Menu ordering from the `CATEGORY_ORDER` ("alphabetical", "venue",
+    /// "progression", or a comma-separated category list) and
+    /// `WITHIN_CATEGORY_SORT` ("abv", "tap", "rating", "name") vars.
+    fn sort_options<D>(ctx: &RouteContext<D>) -> SortOptions {
+        let var = |name| ctx.var(name).ok().map(|v| v.to_string());
+        SortOptions {
+            categories: var("CATEGORY_ORDER")
+                .and_then(|v| CategoryOrder::parse(&v))
+                .unwrap_or_default(),
+            within: var("WITHIN_CATEGORY_SORT")
+                .and_then(|v| WithinCategory::parse(&v))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The built-in style taxonomy, extended by the `STYLE_TAXONOMY` var (same
+    /// JSON shape as `data/style_taxonomy.json`) when set. A malformed
+    /// extension is logged and ignored rather than taking the menu down.
+    fn style_taxonomy(ctx: &RouteContext<Shared>) -> Taxonomy {
+        let builtin = Taxonomy::builtin();
+        let Ok(extra) = ctx.var("STYLE_TAXONOMY") else {
+            return builtin;
+        };
+        match Taxonomy::from_json(&extra.to_string()) {
+            Ok(extra) => builtin.extended(extra),
+            Err(e) => {
+                log_event(
+                    &ctx.data.log,
+                    "config_ignored",
+                    json!({"var": "STYLE_TAXONOMY", "error": e.to_string()}),
+                );
+                builtin
+            }
+        }
+    }
+
+    /// Default grouping from the `GROUP_BY` var ("category", "family",
+    /// "subfamily").
+    fn style_view<D>(ctx: &RouteContext<D>) -> StyleView {
+        StyleView {
+            group_by: ctx
+                .var("GROUP_BY")
+                .ok()
+                .and_then(|v| GroupBy::parse(&v.to_string()))
+                .unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    /// How the fetched menu is shaped before rendering.
+    struct MenuLayout {
+        taxonomy: Taxonomy,
+        view: StyleView,
+        sort: SortOptions,
+        /// The viewer's taste; empty means no "For you" picks.
+        profile: TasteProfile,
+    }
+
+    /// Whose tried list a request acts on.
+    struct Viewer {
+        user: String,
+        /// The browser doesn't hold `user` in its cookie yet.
+        new: bool,
+        /// Another list a `?user=` link offered (see `tried::identify`).
+        offered: Option<String>,
+    }
+
+    /// The viewer: the cookie's token, else a valid `?user=` (a shared link),
+    /// else a freshly minted one.
+    fn user_identity(req: &Request) -> worker::Result<Viewer> {
+        let shared = req
+            .url()?
+            .query_pairs()
+            .find(|(k, _)| k == "user")
+            .map(|(_, v)| v.into_owned());
+        let cookie = req.headers().get("Cookie")?;
+        let viewer = match tried::identify(cookie.as_deref(), shared.as_deref()) {
+            Identity::Cookie { user, offered } => Viewer {
+                user,
+                new: false,
+                offered,
+            },
+            Identity::Shared(user) => Viewer {
+                user,
+                new: true,
+                offered: None,
+            },
+            Identity::New => {
+                let mut random = [0; 16];
+                getrandom::getrandom(&mut random)
+                    .map_err(|e| worker::Error::from(e.to_string()))?;
+                Viewer {
+                    user: tried::new_user_id(random),
+                    new: true,
+                    offered: None,
+                }
+            }
+        };
+        Ok(viewer)
+    }
+
+    /// Set the user cookie on a response if the browser doesn't hold it yet;
+    /// `refresh` re-issues it anyway to push back its expiry.
+    fn with_user_cookie(
+        mut resp: Response,
+        viewer: &Viewer,
+        refresh: bool,
+    ) -> worker::Result<Response> {
+        if viewer.new || refresh {
+            resp.headers_mut()
+                .set("Set-Cookie", &tried::set_cookie(&viewer.user))?;
+        }
+        Ok(resp)
+    }
+
+    async fn load_tried(kv: &KvStore, user: &str) -> TriedList {
+        match kv.get(&tried::kv_key(user)).text().await {
+            Ok(Some(json)) => TriedList::from_json(&json),
+            _ => TriedList::default(),
+        }
+    }
+
+    async fn load_profile(kv: &KvStore, user: &str) -> TasteProfile {
+        match kv.get(&recommend::kv_key(user)).text().await {
+            Ok(Some(json)) => TasteProfile::from_json(&json),
+            _ => TasteProfile::default(),
+        }
+    }
+
+    /// Store state that never expires (per-user lists and profiles, the menu
+    /// snapshot).
+    async fn save_value(kv: &KvStore, key: &str, value: String) -> AppResult<()> {
+        kv.put(key, value)
+            .map_err(|e| AppError::Internal(format!("Failed to store {key}: {e}")))?
+            .execute()
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to store {key}: {e}")))
+    }
+
+    fn menu_kv<D>(ctx: &RouteContext<D>) -> AppResult<KvStore> {
+        ctx.kv("b30")
+            .map_err(|e| AppError::Internal(format!("Failed to get KV store: {e}")))
+    }
+
+    /// Friendly error page with `AppError::status`, plus `Retry-After` where it
+    /// applies. The error is logged under the request id (its `cf-ray`), which
+    /// the page shows as its reference.
+    fn error_page(log: &RefCell<RequestLog>, error: &AppError) -> worker::Result<Response> {
+        let reference = log_error(log, error);
+        let resp = Response::from_html(render::render_error(error, &reference))?;
+        with_error_status(resp, error)
+    }
 
-        // 3. Pair, sort, render.
-        let mut rated: Vec<RatedBeer> = entries
+    /// `error_page` for JSON clients: `{"error": <headline>, "reference": <id>}`.
+    fn error_json(log: &RefCell<RequestLog>, error: &AppError) -> worker::Result<Response> {
+        let reference = log_error(log, error);
+        let (headline, _) = render::error_message(error);
+        let resp = Response::from_json(&json!({"error": headline, "reference": reference}))?;
+        with_error_status(resp, error)
+    }
+
+    /// Log `error`; returns the request id it was logged under.
+    fn log_error(log: &RefCell<RequestLog>, error: &AppError) -> String {
+        log.borrow_mut().error(error);
+        log_event(
+            log,
+            "error",
+            json!({"kind": error.kind(), "error": error.to_string()}),
+        );
+        let reference = log.borrow().request_id.clone();
+        reference
+    }
+
+    fn with_error_status(resp: Response, error: &AppError) -> worker::Result<Response> {
+        let mut resp = resp.with_status(error.status());
+        if let Some(seconds) = error.retry_after() {
+            resp.headers_mut()
+                .set("Retry-After", &seconds.to_string())?;
+        }
+        Ok(resp)
+    }
+
+    /// The rated menu, and when it's the last good copy rather than live, when
+    /// that copy was fetched.
+    struct Menu {
+        beers: Vec<RatedBeer>,
+        stale_since: Option<DateTime<Utc>>,
+    }
+
+    /// The menu JSON URL: found on the bigscreen page when possible (and then
+    /// remembered in KV), else the last one found there, else the one derived
+    /// from the venue id.
+    async fn find_json_url(kv: &KvStore, log: &RefCell<RequestLog>) -> String {
+        let started = now_ms();
+        let bigscreen = fetch_text(&taphunter::bigscreen_url()).await;
+        log.borrow_mut()
+            .phase(Phase::Bigscreen, now_ms().saturating_sub(started));
+        let cached = kv
+            .get(taphunter::JSON_URL_KV_KEY)
+            .cache_ttl(300)
+            .text()
+            .await
+            .ok()
+            .flatten()
+            .filter(|url| taphunter::is_taphunter_url(url));
+        let (url, strategy) = match bigscreen.and_then(|html| taphunter::discover_json_url(&html)) {
+            Ok((url, strategy)) => {
+                if cached.as_deref() != Some(url.as_str()) {
+                    if let Err(e) = save_value(kv, taphunter::JSON_URL_KV_KEY, url.clone()).await {
+                        log_event(log, "json_url_not_saved", json!({"error": e.to_string()}));
+                    }
+                }
+                (url, strategy)
+            }
+            Err(error) => {
+                log.borrow_mut().error(&error);
+                log_event(
+                    log,
+                    "json_url_not_found",
+                    json!({"kind": error.kind(), "error": error.to_string()}),
+                );
+                match cached {
+                    Some(url) => (url, UrlStrategy::CachedUrl),
+                    None => (
+                        taphunter::venue_json_url(taphunter::BEER_THIRTY_VENUE_ID),
+                        UrlStrategy::VenueKey,
+                    ),
+                }
+            }
+        };
+        log_event(
+            log,
+            "json_url",
+            json!({"strategy": strategy.label(), "url": url}),
+        );
+        url
+    }
+
+    /// Resolve the TapHunter JSON endpoint, then fetch and parse the menu.
+    async fn fetch_taphunter(
+        kv: &KvStore,
+        clock: &VenueClock,
+        log: &RefCell<RequestLog>,
+    ) -> AppResult<(String, Vec<BeerEntry>)> {
+        let json_url = find_json_url(kv, log).await;
+        let started = now_ms();
+        let menu_json = fetch_text(&json_url).await;
+        log.borrow_mut()
+            .phase(Phase::Menu, now_ms().saturating_sub(started));
+        let menu_json = menu_json?;
+        let entries = taphunter::parse_menu(&menu_json, clock)?;
+        Ok((menu_json, entries))
+    }
+
+    /// The current menu, each tap paired with its (cached) Untappd rating.
+    /// Falls back to the last good TapHunter menu (see `snapshot`) when the
+    /// live one can't be fetched or parsed.
+    async fn load_menu(
+        kv: &KvStore,
+        clock: &VenueClock,
+        budget: Budget,
+        sort: &SortOptions,
+        state: &RequestState,
+    ) -> AppResult<Menu> {
+        let log = &state.log;
+        // 1. The menu, live or last known good.
+        let stored = match kv.get(snapshot::KV_KEY).cache_ttl(60).text().await {
+            Ok(Some(json)) => MenuSnapshot::from_json(&json),
+            _ => None,
+        };
+        let (entries, stale_since) = match fetch_taphunter(kv, clock, log).await {
+            Ok((menu_json, entries)) => {
+                // A drifted menu still renders, but isn't worth keeping as the
+                // last good copy.
+                let drift = schema::validate_menu(&menu_json)
+                    .map(|report| report.warnings)
+                    .unwrap_or_default();
+                if !drift.is_empty() {
+                    log_event(log, "schema_drift", json!({"warnings": drift}));
+                } else if snapshot::needs_save(stored.as_ref(), &menu_json, clock.now) {
+                    let fresh = MenuSnapshot {
+                        saved_at: clock.now,
+                        menu_json,
+                    };
+                    if let Err(e) = save_value(kv, snapshot::KV_KEY, fresh.to_json()).await {
+                        log_event(log, "snapshot_not_saved", json!({"error": e.to_string()}));
+                    }
+                }
+                (entries, None)
+            }
+            Err(error) => {
+                let Some(last) = stored else {
+                    return Err(error);
+                };
+                let entries =
+                    taphunter::parse_menu(&last.menu_json, clock).map_err(|_| error.clone())?;
+                log.borrow_mut().error(&error);
+                log_event(
+                    log,
+                    "snapshot_served",
+                    json!({
+                        "saved_at": last.saved_at.to_rfc3339(),
+                        "kind": error.kind(),
+                        "error": error.to_string(),
+                    }),
+                );
+                (entries, Some(last.saved_at))
+            }
+        };
+
+        // 2. Cross-reference Untappd ratings (cached).
+        let started = now_ms();
+        let plan = RatingPlan::new(budget::by_visibility(&entries, sort), budget);
+        let (ratings, refresh) = fetch_ratings(&entries, kv, plan, log).await;
+        state
+            .refresh
+            .borrow_mut()
+            .extend(refresh.into_iter().map(|idx| entries[idx].clone()));
+        let beers: Vec<RatedBeer> = entries
             .into_iter()
             .zip(ratings)
-            .map(|(entry, rating_html)| RatedBeer { entry, rating_html })
+            .map(|(entry, (rating, outcome))| RatedBeer {
+                entry,
+                rating,
+                outcome,
+            })
             .collect();
-        sort_rated(&mut rated);
-        Ok(render::render(&rated))
+        let mut log = log.borrow_mut();
+        log.phase(Phase::Ratings, now_ms().saturating_sub(started));
+        log.lookups(&LookupStats::tally(&beers));
+        Ok(Menu { beers, stale_since })
+    }
+
+    async fn current_menu(ctx: &RouteContext<Shared>) -> AppResult<Menu> {
+        load_menu(
+            &menu_kv(ctx)?,
+            &venue_clock(ctx),
+            subrequest_budget(ctx),
+            &sort_options(ctx),
+            &ctx.data,
+        )
+        .await
+    }
+
+    async fn build_menu_html(
+        kv: &KvStore,
+        clock: &VenueClock,
+        layout: &MenuLayout,
+        mut options: RenderOptions,
+        budget: Budget,
+        state: &RequestState,
+    ) -> AppResult<String> {
+        let log = &state.log;
+        // 3. Group/filter by style, sort, render.
+        let menu = load_menu(kv, clock, budget, &layout.sort, state).await?;
+        let started = now_ms();
+        options.stale_since = menu.stale_since.map(|at| clock.local(at));
+        let mut rated = layout.view.apply(menu.beers, &layout.taxonomy);
+        sort_rated_with(&mut rated, &layout.sort);
+        // 4. "For you": feature the best fits, and order by them if asked.
+        if !layout.profile.is_empty() {
+            let fits = recommend::fits(
+                &rated,
+                &layout.profile,
+                &layout.taxonomy,
+                options.tried.as_ref(),
+            );
+            options.picks = recommend::top_picks(&rated, &fits);
+            if layout.sort.within == WithinCategory::ForYou {
+                recommend::sort_for_you(&mut rated, fits);
+            }
+        }
+        let html = render::render_with(&rated, &options);
+        log.borrow_mut()
+            .phase(Phase::Render, now_ms().saturating_sub(started));
+        Ok(html)
+    }
+
+    /// Gate an `/admin/*` route on the `ADMIN_TOKEN` secret: `Some` refusal
+    /// response, or `None` to go ahead.
+    fn admin_refusal(
+        req: &Request,
+        ctx: &RouteContext<Shared>,
+    ) -> worker::Result<Option<Response>> {
+        let token = ctx.secret("ADMIN_TOKEN").ok().map(|t| t.to_string());
+        let access = admin::authorize(
+            req.headers().get("Authorization")?.as_deref(),
+            token.as_deref(),
+        );
+        let Some(status) = access.status() else {
+            return Ok(None);
+        };
+        let mut resp = Response::error(
+            if access == Access::Denied {
+                "Unauthorized"
+            } else {
+                "Not Found"
+            },
+            status,
+        )?;
+        if access == Access::Denied {
+            resp.headers_mut().set("WWW-Authenticate", "Bearer")?;
+        }
+        Ok(Some(resp))
+    }
+
+    /// Run the upstream checks (see `diagnostics`), timing each fetch.
+    async fn run_diagnostics(clock: &VenueClock) -> Report {
+        let started = now_ms();
+        let bigscreen = fetch_text(&taphunter::bigscreen_url()).await;
+        let (url_check, json_url) = diagnostics::check_json_url(bigscreen);
+        let url_check = url_check.timed(now_ms().saturating_sub(started));
+
+        let started = now_ms();
+        let menu_json = match json_url {
+            Some(url) => Some(fetch_text(&url).await),
+            None => None,
+        };
+        let fetched = menu_json.is_some();
+        let mut menu_check = diagnostics::check_menu(menu_json, clock);
+        if fetched {
+            menu_check = menu_check.timed(now_ms().saturating_sub(started));
+        }
+
+        let started = now_ms();
+        let canary = algolia_post(&diagnostics::canary_query()).await;
+        let canary_check =
+            diagnostics::check_canary(canary).timed(now_ms().saturating_sub(started));
+
+        Report {
+            checks: vec![url_check, menu_check, canary_check],
+        }
+    }
+
+    async fn load_counters(kv: &KvStore) -> Counters {
+        match kv.get(metrics::KV_KEY).text().await {
+            Ok(Some(json)) => Counters::from_json(&json),
+            _ => Counters::default(),
+        }
+    }
+
+    /// Count a finished request in this isolate, and merge the isolate's
+    /// counts into KV when `FLUSH_INTERVAL_MS` has passed since it last did.
+    async fn record_stats(kv: KvStore, log: RequestLog) {
+        let batch = STATS.with(|sink| {
+            let mut sink = sink.borrow_mut();
+            sink.record(&log);
+            sink.flush(now_ms())
+        });
+        let Some(batch) = batch else {
+            return;
+        };
+        let mut counters = load_counters(&kv).await;
+        counters.merge(&batch);
+        if let Err(e) = save_value(&kv, metrics::KV_KEY, counters.to_json().to_string()).await {
+            console_log!(
+                "{}",
+                log.event("stats_not_saved", json!({"error": e.to_string()}))
+            );
+        }
     }
 
     #[event(fetch)]
-    async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response, worker::Error> {
-        Router::new()
-            .get_async("/", |_req, ctx| async move {
+    async fn main(req: Request, env: Env, ctx: Context) -> Result<Response, worker::Error> {
+        let started = now_ms();
+        let request_id = req
+            .headers()
+            .get("cf-ray")?
+            .unwrap_or_else(|| format!("{started:x}"));
+        let state: Shared = Rc::new(RequestState {
+            log: RefCell::new(RequestLog::new(&request_id, &req.path(), Utc::now())),
+            ..Default::default()
+        });
+        let kv = env.kv("b30").ok();
+
+        let resp = Router::with_data(state.clone())
+            .get_async("/", |req, ctx| async move {
+                let clock = venue_clock(&ctx);
+                let viewer = user_identity(&req)?;
+                let user = &viewer.user;
+                let url = req.url()?;
+                let query = url.query().unwrap_or("");
+                let mut layout = MenuLayout {
+                    taxonomy: style_taxonomy(&ctx),
+                    view: style_view(&ctx).with_query(query),
+                    sort: sort_options(&ctx).with_query(query),
+                    profile: TasteProfile::from_query(query),
+                };
+                let mut options = RenderOptions::from_query(query);
+                options.today = Some(clock.today());
+                options.timezone = Some(clock.timezone);
+                options.offered_user = viewer.offered.clone();
                 let html = async {
-                    let kv = ctx
-                        .kv("b30")
-                        .map_err(|e| AppError::Client(format!("Failed to get KV store: {e}")))?;
-                    build_menu_html(&kv).await
+                    let kv = menu_kv(&ctx)?;
+                    options.tried = Some(load_tried(&kv, user).await);
+                    layout.profile = layout.profile.or(load_profile(&kv, user).await);
+                    let budget = subrequest_budget(&ctx);
+                    build_menu_html(&kv, &clock, &layout, options, budget, &ctx.data).await
+                }
+                .await;
+                match html {
+                    Ok(html) => with_user_cookie(Response::from_html(html)?, &viewer, true),
+                    Err(e) => error_page(&ctx.data.log, &e),
                 }
-                .await
-                .map_err(worker::Error::from)?;
+            })
+            // Kiosk display for a taproom TV: tap order, paged, self-refreshing
+            // via `If-None-Match` polls that cost a 304 until the menu changes.
+            .get_async("/screen", |req, ctx| async move {
+                let url = req.url()?;
+                let query = url.query().unwrap_or("");
+                let taxonomy = style_taxonomy(&ctx);
+                let view = style_view(&ctx).with_query(query);
+                let menu = match current_menu(&ctx).await {
+                    Ok(menu) => menu,
+                    Err(e) => return error_page(&ctx.data.log, &e),
+                };
+                let page = timed(&ctx.data.log, Phase::Render, || {
+                    let mut beers = view.apply(menu.beers, &taxonomy);
+                    beers.sort_by_key(|b| b.entry.tap_number);
+                    render::render_screen(&beers, &ScreenOptions::from_query(query))
+                });
+
+                let unchanged = req
+                    .headers()
+                    .get("If-None-Match")?
+                    .is_some_and(|tags| render::etag_matches(&tags, &page.etag));
+                let mut resp = if unchanged {
+                    Response::empty()?.with_status(304)
+                } else {
+                    Response::from_html(page.html)?
+                };
+                resp.headers_mut().set("ETag", &page.etag)?;
+                resp.headers_mut().set("Cache-Control", "no-cache")?;
+                Ok(resp)
+            })
+            // Paper menu: letter-size print layout, `?qr=1` for Untappd QR codes.
+            .get_async("/print", |req, ctx| async move {
+                let clock = venue_clock(&ctx);
+                let url = req.url()?;
+                let query = url.query().unwrap_or("");
+                let taxonomy = style_taxonomy(&ctx);
+                let view = style_view(&ctx).with_query(query);
+                let sort = sort_options(&ctx).with_query(query);
+                let mut options = PrintOptions::from_query(query);
+                options.today = Some(clock.today());
+                let menu = match current_menu(&ctx).await {
+                    Ok(menu) => menu,
+                    Err(e) => return error_page(&ctx.data.log, &e),
+                };
+                let html = timed(&ctx.data.log, Phase::Render, || {
+                    let mut beers = view.apply(menu.beers, &taxonomy);
+                    sort_rated_with(&mut beers, &sort);
+                    render::render_print(&beers, &options)
+                });
                 Response::from_html(html)
             })
+            // A tasting flight under the query's constraints (see `flight`).
+            .get_async("/flight", |req, ctx| async move {
+                let url = req.url()?;
+                let query = url.query().unwrap_or("");
+                let request = FlightRequest::from_query(query);
+                let heatmap = Heatmap::from_query(query);
+                match current_menu(&ctx).await {
+                    Ok(menu) => Response::from_html(timed(&ctx.data.log, Phase::Render, || {
+                        let flight =
+                            flight::build_flight(&menu.beers, &style_taxonomy(&ctx), &request);
+                        render::render_flight(flight.as_ref(), &request, &menu.beers, &heatmap)
+                    })),
+                    Err(e) => error_page(&ctx.data.log, &e),
+                }
+            })
+            .get_async("/flight.json", |req, ctx| async move {
+                let request = FlightRequest::from_query(req.url()?.query().unwrap_or(""));
+                let menu = match current_menu(&ctx).await {
+                    Ok(menu) => menu,
+                    Err(e) => return error_json(&ctx.data.log, &e),
+                };
+                match flight::build_flight(&menu.beers, &style_taxonomy(&ctx), &request) {
+                    Some(flight) => Response::from_json(&flight.to_json()),
+                    None => Response::error("No flight fits those constraints", 404),
+                }
+            })
+            // The viewer's token and list (to back up, or to find the token for
+            // a `?user=` link on another device).
+            .get_async("/tried", |req, ctx| async move {
+                let viewer = user_identity(&req)?;
+                let user = &viewer.user;
+                let list = load_tried(&menu_kv(&ctx)?, user).await;
+                let body = format!("{{\"user\":\"{user}\",\"tried\":{}}}", list.to_json());
+                let mut resp = Response::ok(body)?;
+                resp.headers_mut().set("Content-Type", "application/json")?;
+                with_user_cookie(resp, &viewer, false)
+            })
+            // Seed the list, and learn a taste profile, from an Untappd check-in
+            // export (CSV or JSON body):
+            // `curl --data-binary @export.csv '.../tried/import?user=<token>'`.
+            .post_async("/tried/import", |mut req, ctx| async move {
+                let viewer = user_identity(&req)?;
+                let user = &viewer.user;
+                let checkins = match tried::parse_export(&req.text().await?) {
+                    Ok(checkins) => checkins,
+                    Err(e) => return Response::error(e.to_string(), 400),
+                };
+                let kv = menu_kv(&ctx)?;
+                let mut list = load_tried(&kv, user).await;
+                let added = list.import(&checkins);
+                save_value(&kv, &tried::kv_key(user), list.to_json()).await?;
+                let profile = TasteProfile::learn(&checkins, &style_taxonomy(&ctx));
+                save_value(&kv, &recommend::kv_key(user), profile.to_json()).await?;
+                let summary = format!(
+                    "Imported {} check-ins: {added} new beers, {} tried in total. Styles you like: {}.",
+                    checkins.len(),
+                    list.len(),
+                    if profile.styles.is_empty() {
+                        "not enough history to tell".to_string()
+                    } else {
+                        profile.styles.join(", ")
+                    }
+                );
+                with_user_cookie(Response::ok(summary)?, &viewer, false)
+            })
+            // The menu's "switch" button for a `?user=` link opened in a browser
+            // that already has a list: adopt the link's token.
+            .post_async("/tried/switch", |mut req, _ctx| async move {
+                let menu = req
+                    .url()?
+                    .join("/")
+                    .map_err(|e| worker::Error::from(e.to_string()))?;
+                // Only from our own page: another site mustn't be able to
+                // attach a visitor to a list of its choosing.
+                let same_origin = req
+                    .headers()
+                    .get("Origin")?
+                    .is_some_and(|o| o == menu.origin().ascii_serialization());
+                if !same_origin {
+                    return Response::error("Switch lists from the menu page", 403);
+                }
+                let form = req.text().await?;
+                let Some(user) = url::form_urlencoded::parse(form.as_bytes())
+                    .find(|(k, _)| k == "user")
+                    .map(|(_, v)| v.into_owned())
+                    .filter(|u| tried::valid_user_id(u))
+                else {
+                    return Response::error("Bad user token", 400);
+                };
+                let mut resp = Response::redirect_with_status(menu, 303)?;
+                resp.headers_mut()
+                    .set("Set-Cookie", &tried::set_cookie(&user))?;
+                Ok(resp)
+            })
+            // The menu's per-row toggle: flip and go back to the menu.
+            .post_async("/tried/:bid", |req, ctx| async move {
+                let Some(bid) = ctx.param("bid").and_then(|b| b.parse::<u64>().ok()) else {
+                    return Response::error("Bad beer id", 400);
+                };
+                let viewer = user_identity(&req)?;
+                let kv = menu_kv(&ctx)?;
+                let mut list = load_tried(&kv, &viewer.user).await;
+                list.toggle(bid);
+                save_value(&kv, &tried::kv_key(&viewer.user), list.to_json()).await?;
+                let menu = req
+                    .url()?
+                    .join("/")
+                    .map_err(|e| worker::Error::from(e.to_string()))?;
+                let back = req
+                    .headers()
+                    .get("Referer")?
+                    .and_then(|r| Url::parse(&r).ok())
+                    .filter(|r| r.origin() == menu.origin())
+                    .unwrap_or(menu);
+                with_user_cookie(Response::redirect_with_status(back, 303)?, &viewer, false)
+            })
+            // Liveness only: no upstream calls, so it's cheap to poll.
+            .get_async("/health", |_req, _ctx| async move {
+                Response::from_json(&json!({
+                    "status": "ok",
+                    "version": env!("CARGO_PKG_VERSION"),
+                }))
+            })
+            // Live upstream checks for an uptime monitor: 200 if all pass, 503
+            // with the failing checks' reasons otherwise.
+            .get_async("/admin/diagnostics", |req, ctx| async move {
+                if let Some(refusal) = admin_refusal(&req, &ctx)? {
+                    return Ok(refusal);
+                }
+                let report = run_diagnostics(&venue_clock(&ctx)).await;
+                for check in report.checks.iter().filter(|c| !c.passed) {
+                    log_event(
+                        &ctx.data.log,
+                        "diagnostic_failed",
+                        json!({"check": check.name, "detail": check.detail}),
+                    );
+                }
+                let mut resp = Response::from_json(&report.to_json())?.with_status(report.status());
+                resp.headers_mut().set("Cache-Control", "no-store")?;
+                Ok(resp)
+            })
+            // Aggregate request counters (see `metrics::Counters`).
+            .get_async("/admin/stats", |req, ctx| async move {
+                if let Some(refusal) = admin_refusal(&req, &ctx)? {
+                    return Ok(refusal);
+                }
+                let counters = load_counters(&menu_kv(&ctx)?).await;
+                Response::from_json(&counters.to_json())
+            })
             .run(req, env)
-            .await
+            .await;
+
+        // One summary line per request, then (after responding) any rating
+        // refreshes and the counters.
+        let mut log = state.log.take();
+        let status = resp.as_ref().map_or(500, |r| r.status_code());
+        log.finish(status, now_ms().saturating_sub(started));
+        console_log!("{}", log.to_json());
+        let refresh = state.refresh.take();
+        if let Some(kv) = kv.as_ref().filter(|_| !refresh.is_empty()) {
+            ctx.wait_until(refresh_ratings(kv.clone(), refresh, log.clone()));
+        }
+        if let Some(kv) = kv.filter(|_| metrics::counted(&log.path)) {
+            ctx.wait_until(record_stats(kv, log));
+        }
+        resp
     }
 }
@@ -0,0 +1,451 @@
+//
+// Request metrics and structured logs. Each request fills in a `RequestLog`
+// (time per phase, rating cache hits/misses, Algolia calls and latency, error
+// categories); the worker logs it as one JSON line and folds it into the
+// aggregate `Counters` behind `/admin/stats`. The worker hands finished logs
+// to a `MetricsSink`: `PendingStats`, which batches them for KV, or in host
+// tests `NoopSink`, which drops them.
+//
+
+use crate::clock;
+use crate::error::AppError;
+use crate::model::{LookupOutcome, LookupStats};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// KV key holding the aggregate counters.
+pub const KV_KEY: &str = "stats:counters";
+/// How often an isolate merges the counts it has gathered into KV. Batching
+/// keeps stats to about one KV write a minute per isolate rather than one per
+/// request.
+pub const FLUSH_INTERVAL_MS: u64 = 60 * 1000;
+
+/// A timed stage of building a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    /// Fetching the TapHunter bigscreen page (to find the menu JSON URL).
+    Bigscreen,
+    /// Fetching and parsing the menu JSON.
+    Menu,
+    /// Rating lookups, cached or not.
+    Ratings,
+    /// Sorting, grouping and rendering the page.
+    Render,
+}
+
+impl Phase {
+    pub fn label(self) -> &'static str {
+        match self {
+            Phase::Bigscreen => "bigscreen",
+            Phase::Menu => "menu",
+            Phase::Ratings => "ratings",
+            Phase::Render => "render",
+        }
+    }
+}
+
+/// What one request did, for its log line and the counters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestLog {
+    /// The `cf-ray` id (also the reference shown on error pages).
+    pub request_id: String,
+    pub path: String,
+    /// When the request started.
+    pub at: DateTime<Utc>,
+    pub status: u16,
+    pub total_ms: u64,
+    phases: BTreeMap<Phase, u64>,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub algolia_calls: u64,
+    pub algolia_ms: u64,
+    /// Errors by `AppError::kind`.
+    errors: BTreeMap<&'static str, u64>,
+}
+
+impl RequestLog {
+    pub fn new(request_id: &str, path: &str, at: DateTime<Utc>) -> RequestLog {
+        RequestLog {
+            request_id: request_id.to_string(),
+            path: path.to_string(),
+            at,
+            ..Default::default()
+        }
+    }
+
+    /// Add `ms` to a phase (phases can run more than once, e.g. a retry).
+    pub fn phase(&mut self, phase: Phase, ms: u64) {
+        *self.phases.entry(phase).or_default() += ms;
+    }
+
+    /// One Algolia request that took `ms`.
+    pub fn algolia(&mut self, ms: u64) {
+        self.algolia_calls += 1;
+        self.algolia_ms += ms;
+    }
+
+    pub fn error(&mut self, error: &AppError) {
+        *self.errors.entry(error.kind()).or_default() += 1;
+    }
+
+    /// Cache hits and misses from the menu's lookup outcomes. Pending beers
+    /// weren't asked about, so are neither.
+    pub fn lookups(&mut self, stats: &LookupStats) {
+        let hits = stats.count(LookupOutcome::Cached) as u64;
+        let pending = stats.count(LookupOutcome::Pending) as u64;
+        self.cache_hits += hits;
+        self.cache_misses += stats.total() as u64 - hits - pending;
+    }
+
+    pub fn finish(&mut self, status: u16, total_ms: u64) {
+        self.status = status;
+        self.total_ms = total_ms;
+    }
+
+    /// The request's summary log line.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "event": "request",
+            "request_id": self.request_id,
+            "path": self.path,
+            "status": self.status,
+            "total_ms": self.total_ms,
+            "phases": self
+                .phases
+                .iter()
+                .map(|(p, ms)| (p.label().to_string(), json!(ms)))
+                .collect::<Map<_, _>>(),
+            "cache": {"hits": self.cache_hits, "misses": self.cache_misses},
+            "algolia": {"calls": self.algolia_calls, "ms": self.algolia_ms},
+            "errors": self.errors,
+        })
+    }
+
+    /// A structured log line for something that happened during this request:
+    /// `{"event": <name>, "request_id": ..., <fields>}`.
+    pub fn event(&self, name: &str, fields: Value) -> String {
+        let mut line = Map::new();
+        line.insert("event".into(), json!(name));
+        line.insert("request_id".into(), json!(self.request_id));
+        if let Value::Object(fields) = fields {
+            line.extend(fields);
+        }
+        Value::Object(line).to_string()
+    }
+}
+
+/// Whether a path's requests go into the counters. Admin pages and health
+/// checks would only measure the people and monitors watching the stats.
+pub fn counted(path: &str) -> bool {
+    !(path.starts_with("/admin") || path == "/health")
+}
+
+/// Takes finished request logs.
+pub trait MetricsSink {
+    fn record(&mut self, log: &RequestLog);
+
+    /// Counts due to be merged into KV at `now_ms`, if any.
+    fn flush(&mut self, now_ms: u64) -> Option<Counters>;
+}
+
+/// Drops everything.
+pub struct NoopSink;
+
+impl MetricsSink for NoopSink {
+    fn record(&mut self, _log: &RequestLog) {}
+
+    fn flush(&mut self, _now_ms: u64) -> Option<Counters> {
+        None
+    }
+}
+
+/// An isolate's counts since it last merged them into KV, handed over at most
+/// every `FLUSH_INTERVAL_MS` (the first request flushes straight away).
+#[derive(Debug, Clone, Default)]
+pub struct PendingStats {
+    counters: Counters,
+    flushed_ms: u64,
+}
+
+impl MetricsSink for PendingStats {
+    fn record(&mut self, log: &RequestLog) {
+        self.counters.record(log);
+    }
+
+    fn flush(&mut self, now_ms: u64) -> Option<Counters> {
+        if now_ms.saturating_sub(self.flushed_ms) < FLUSH_INTERVAL_MS {
+            return None;
+        }
+        self.flushed_ms = now_ms;
+        Some(std::mem::take(&mut self.counters))
+    }
+}
+
+/// Total and count, for an average.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+    pub count: u64,
+    pub total_ms: u64,
+}
+
+impl Timing {
+    fn add(&mut self, other: Timing) {
+        self.count += other.count;
+        self.total_ms += other.total_ms;
+    }
+
+    fn to_json(self) -> Value {
+        let avg = (self.count > 0).then(|| self.total_ms as f64 / self.count as f64);
+        json!({"count": self.count, "total_ms": self.total_ms, "avg_ms": avg})
+    }
+
+    fn from_json(value: &Value) -> Timing {
+        Timing {
+            count: value["count"].as_u64().unwrap_or(0),
+            total_ms: value["total_ms"].as_u64().unwrap_or(0),
+        }
+    }
+}
+
+/// Aggregate counts over many requests. Approximate by design: isolates flush
+/// with a KV read-modify-write, so concurrent flushes can drop a batch, and an
+/// isolate evicted between flushes loses its unflushed counts.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Counters {
+    /// The earliest request counted.
+    pub since: Option<DateTime<Utc>>,
+    pub requests: u64,
+    /// Responses by status class ("2xx", "5xx", ...).
+    pub statuses: BTreeMap<String, u64>,
+    pub phases: BTreeMap<String, Timing>,
+    /// Whole requests.
+    pub total: Timing,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    pub algolia: Timing,
+    pub errors: BTreeMap<String, u64>,
+}
+
+impl Counters {
+    /// Count one finished request.
+    pub fn record(&mut self, log: &RequestLog) {
+        self.since = Some(self.since.map_or(log.at, |since| since.min(log.at)));
+        self.requests += 1;
+        *self
+            .statuses
+            .entry(format!("{}xx", log.status / 100))
+            .or_default() += 1;
+        for (phase, &ms) in &log.phases {
+            self.phases
+                .entry(phase.label().to_string())
+                .or_default()
+                .add(Timing {
+                    count: 1,
+                    total_ms: ms,
+                });
+        }
+        self.total.add(Timing {
+            count: 1,
+            total_ms: log.total_ms,
+        });
+        self.cache_hits += log.cache_hits;
+        self.cache_misses += log.cache_misses;
+        self.algolia.add(Timing {
+            count: log.algolia_calls,
+            total_ms: log.algolia_ms,
+        });
+        for (kind, n) in &log.errors {
+            *self.errors.entry(kind.to_string()).or_default() += n;
+        }
+    }
+
+    /// Add another batch of counts (an isolate's, on flush).
+    pub fn merge(&mut self, other: &Counters) {
+        self.since = match (self.since, other.since) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.requests += other.requests;
+        for (class, n) in &other.statuses {
+            *self.statuses.entry(class.clone()).or_default() += n;
+        }
+        for (phase, timing) in &other.phases {
+            self.phases.entry(phase.clone()).or_default().add(*timing);
+        }
+        self.total.add(other.total);
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        self.algolia.add(other.algolia);
+        for (kind, n) in &other.errors {
+            *self.errors.entry(kind.clone()).or_default() += n;
+        }
+    }
+
+    /// Share of rating lookups served from the cache, if there were any.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    /// The stored form, which is also what `/admin/stats` shows (averages and
+    /// the hit rate are derived, and ignored when read back).
+    pub fn to_json(&self) -> Value {
+        json!({
+            "since": self.since.map(|s| s.to_rfc3339()),
+            "requests": self.requests,
+            "statuses": self.statuses,
+            "total": self.total.to_json(),
+            "phases": self
+                .phases
+                .iter()
+                .map(|(p, t)| (p.clone(), t.to_json()))
+                .collect::<Map<_, _>>(),
+            "cache": {
+                "hits": self.cache_hits,
+                "misses": self.cache_misses,
+                "hit_rate": self.cache_hit_rate(),
+            },
+            "algolia": self.algolia.to_json(),
+            "errors": self.errors,
+        })
+    }
+
+    /// Parse the stored form; anything missing or unreadable counts as zero.
+    pub fn from_json(json: &str) -> Counters {
+        let value: Value = serde_json::from_str(json).unwrap_or_default();
+        let counts = |value: &Value| -> BTreeMap<String, u64> {
+            value
+                .as_object()
+                .map(|o| {
+                    o.iter()
+                        .filter_map(|(k, v)| Some((k.clone(), v.as_u64()?)))
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Counters {
+            since: value["since"].as_str().and_then(clock::parse_utc),
+            requests: value["requests"].as_u64().unwrap_or(0),
+            statuses: counts(&value["statuses"]),
+            phases: value["phases"]
+                .as_object()
+                .map(|o| {
+                    o.iter()
+                        .map(|(k, v)| (k.clone(), Timing::from_json(v)))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            total: Timing::from_json(&value["total"]),
+            cache_hits: value["cache"]["hits"].as_u64().unwrap_or(0),
+            cache_misses: value["cache"]["misses"].as_u64().unwrap_or(0),
+            algolia: Timing::from_json(&value["algolia"]),
+            errors: counts(&value["errors"]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn menu_request(at: &str, status: u16) -> RequestLog {
+        let mut log = RequestLog::new("8f1c2a", "/", clock::parse_utc(at).unwrap());
+        log.phase(Phase::Bigscreen, 120);
+        log.phase(Phase::Menu, 80);
+        log.phase(Phase::Ratings, 300);
+        log.phase(Phase::Render, 4);
+        log.algolia(90);
+        log.algolia(110);
+        log.error(&AppError::Network("timed out".into()));
+        log.cache_hits = 18;
+        log.cache_misses = 2;
+        log.finish(status, 510);
+        log
+    }
+
+    #[test]
+    fn request_log_is_one_json_line() {
+        let log = menu_request("2026-05-20T22:00:00Z", 200);
+        let line = log.to_json();
+        assert_eq!(line["event"], "request");
+        assert_eq!(line["request_id"], "8f1c2a");
+        assert_eq!(line["phases"]["ratings"], 300);
+        assert_eq!(line["cache"]["hits"], 18);
+        assert_eq!(line["algolia"], json!({"calls": 2, "ms": 200}));
+        assert_eq!(line["errors"], json!({"network": 1}));
+        assert!(!line.to_string().contains('\n'));
+
+        let event: Value = serde_json::from_str(&log.event(
+            "snapshot_served",
+            json!({"saved_at": "2026-05-20T21:00:00Z"}),
+        ))
+        .unwrap();
+        assert_eq!(event["event"], "snapshot_served");
+        assert_eq!(event["request_id"], "8f1c2a");
+        assert_eq!(event["saved_at"], "2026-05-20T21:00:00Z");
+    }
+
+    #[test]
+    fn counters_aggregate_merge_and_round_trip() {
+        let mut a = Counters::default();
+        a.record(&menu_request("2026-05-20T22:00:00Z", 200));
+        a.record(&menu_request("2026-05-20T21:00:00Z", 502));
+        let mut b = Counters::default();
+        b.record(&menu_request("2026-05-20T23:00:00Z", 200));
+
+        a.merge(&b);
+        assert_eq!(a.requests, 3);
+        assert_eq!(a.since, clock::parse_utc("2026-05-20T21:00:00Z"));
+        assert_eq!(a.statuses["2xx"], 2);
+        assert_eq!(a.statuses["5xx"], 1);
+        assert_eq!(
+            a.phases["bigscreen"],
+            Timing {
+                count: 3,
+                total_ms: 360
+            }
+        );
+        assert_eq!(a.algolia.count, 6);
+        assert_eq!(a.errors["network"], 3);
+        assert_eq!(a.cache_hit_rate(), Some(0.9));
+
+        let shown = a.to_json();
+        assert_eq!(shown["algolia"]["avg_ms"], 100.0);
+        assert_eq!(Counters::from_json(&shown.to_string()), a);
+        assert_eq!(Counters::from_json("not json"), Counters::default());
+    }
+
+    #[test]
+    fn sinks_batch_or_drop_finished_requests() {
+        let requests = [
+            menu_request("2026-05-20T21:00:00Z", 200),
+            menu_request("2026-05-20T21:00:30Z", 200),
+            menu_request("2026-05-20T21:01:30Z", 502),
+        ];
+        let start = 1_779_310_800_000;
+        let times = [start, start + 30_000, start + 90_000];
+        let run = |sink: &mut dyn MetricsSink| -> Vec<Option<u64>> {
+            requests
+                .iter()
+                .zip(times)
+                .map(|(log, now)| {
+                    sink.record(log);
+                    sink.flush(now).map(|batch| batch.requests)
+                })
+                .collect()
+        };
+        // The first request flushes at once, the next waits out the interval,
+        // and the third takes both.
+        assert_eq!(run(&mut PendingStats::default()), [Some(1), None, Some(2)]);
+        assert_eq!(run(&mut NoopSink), [None, None, None]);
+    }
+
+    #[test]
+    fn admin_and_health_are_not_counted() {
+        assert!(counted("/"));
+        assert!(counted("/screen"));
+        assert!(!counted("/admin/stats"));
+        assert!(!counted("/health"));
+    }
+}
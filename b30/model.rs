@@ -2,17 +2,78 @@
 // Plain data models shared by the parsing, rendering, and worker layers.
 //
 
+use crate::error::AppError;
+use crate::taxonomy;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
 /// One beer on tap, as parsed from the TapHunter menu JSON.
 #[derive(Debug, Clone, PartialEq)]
 pub struct BeerEntry {
     pub tap_number: i32,
+    /// Short brewery name (`brewery.common_name`, e.g. "Allagash").
     pub brewery: String,
+    /// Full brewery name (`brewery.name`, e.g. "Allagash Brewing Company").
+    pub brewery_full: String,
+    /// Beer name with any leading brewery mention stripped (e.g. "Curieux").
     pub name: String,
-    pub abv: String,
+    /// TapHunter's combined display name (`beer.name`, e.g. "Allagash Curieux").
+    pub full_name: String,
+    /// Percent alcohol; `None` when the menu leaves it blank or it fails
+    /// validation (see `parse_abv`).
+    pub abv: Option<f64>,
+    /// Bitterness per the menu; `None` when blank or implausible.
+    pub ibu: Option<u32>,
     pub category: String,
+    /// The venue's own position for this item's menu category
+    /// (`category.order`), if it set one.
+    pub category_order: Option<i64>,
     pub origin: String,
     pub style: String,
-    pub days_old: i32,
+    /// Day the keg went on (the venue's local date); age is computed from it at
+    /// render time.
+    pub date_added: Option<NaiveDate>,
+    /// Pour sizes and prices (`serving_info.sized_pricing`), smallest first.
+    pub pours: Vec<Pour>,
+}
+
+/// One pour size on the menu ("5 oz" for $3.50).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pour {
+    /// The menu's label, e.g. "5 oz".
+    pub size: String,
+    /// Volume in fluid ounces, when the label says ("5 oz", "Pint").
+    pub oz: Option<f64>,
+    /// Price in dollars, when listed.
+    pub price: Option<f64>,
+}
+
+impl Pour {
+    /// Parse a `sized_pricing` entry's size and price strings.
+    pub fn parse(size: &str, price: &str) -> Pour {
+        let size = size.trim();
+        let lower = size.to_lowercase();
+        let oz = if let Some(amount) = lower.strip_suffix("oz") {
+            amount.trim().parse().ok()
+        } else if lower.contains("half pint") {
+            Some(8.0)
+        } else if lower.contains("pint") {
+            Some(16.0)
+        } else {
+            None
+        };
+        Pour {
+            size: size.to_string(),
+            oz: oz.filter(|oz: &f64| *oz > 0.0),
+            price: price
+                .trim()
+                .trim_start_matches('$')
+                .parse()
+                .ok()
+                .filter(|p: &f64| *p >= 0.0),
+        }
+    }
 }
 
 /// A rating + review link resolved from Untappd (via Algolia).
@@ -22,43 +83,865 @@ pub struct RatingResult {
     pub rating: String,
     /// Absolute URL to the beer's Untappd page.
     pub url: String,
+    /// Number of Untappd check-ins behind the score (0 if unknown).
+    pub rating_count: u64,
+    /// Which search variant produced a brewery-confirmed match. `None` for a
+    /// raw top hit whose brewery could not be confirmed.
+    pub matched_by: Option<SearchVariant>,
+    /// Label art, style, IBU, etc. from the same Algolia hit.
+    pub details: BeerDetails,
+    /// When the rating was fetched from Untappd; `None` if unknown (cached
+    /// before we kept track).
+    pub fetched_at: Option<DateTime<Utc>>,
+}
+
+/// Untappd metadata carried alongside a rating. Everything is optional: the
+/// hand-made fixtures and sparse hits omit most of it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BeerDetails {
+    /// Small label image URL (`beer_label`).
+    pub beer_label: Option<String>,
+    /// High-resolution label image URL (`beer_label_hd`).
+    pub beer_label_hd: Option<String>,
+    /// Brewery logo URL (`brewery_label`).
+    pub brewery_label: Option<String>,
+    /// Bitterness (`beer_ibu`); Untappd reports 0 for "unknown".
+    pub ibu: Option<u32>,
+    /// Untappd's fine-grained style (`type_name`, e.g. "Pale Ale - American").
+    pub style: Option<String>,
+    /// Whether the brewery still makes it (`in_production`).
+    pub in_production: Option<bool>,
+    /// Untappd community award winner (`has_community_award`).
+    pub has_community_award: bool,
+    /// Brewery location as (lat, lng) (`_geoloc`).
+    pub geoloc: Option<(f64, f64)>,
+}
+
+/// Untappd's placeholder label, shown for beers without their own art.
+const DEFAULT_LABEL_MARKER: &str = "badge-beer-default";
+
+impl BeerDetails {
+    /// Read the details from an Algolia hit. The KV cache stores the same field
+    /// names (see `to_json`), so this also decodes cached entries.
+    pub fn from_json(hit: &serde_json::Value) -> BeerDetails {
+        let text = |key: &str| {
+            hit.get(key)
+                .and_then(|v| v.as_str())
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+        BeerDetails {
+            beer_label: text("beer_label"),
+            beer_label_hd: text("beer_label_hd"),
+            brewery_label: text("brewery_label"),
+            ibu: hit
+                .get("beer_ibu")
+                .and_then(|v| v.as_u64())
+                .filter(|&ibu| ibu > 0)
+                .map(|ibu| ibu as u32),
+            style: text("type_name"),
+            // Algolia sends 0/1; accept a real bool too.
+            in_production: hit
+                .get("in_production")
+                .and_then(|v| v.as_bool().or_else(|| v.as_u64().map(|n| n != 0))),
+            has_community_award: hit
+                .get("has_community_award")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false),
+            geoloc: hit
+                .get("_geoloc")
+                .and_then(|g| Some((g.get("lat")?.as_f64()?, g.get("lng")?.as_f64()?))),
+        }
+    }
+
+    /// Inverse of `from_json`, using the Algolia field names.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "beer_label": self.beer_label,
+            "beer_label_hd": self.beer_label_hd,
+            "brewery_label": self.brewery_label,
+            "beer_ibu": self.ibu,
+            "type_name": self.style,
+            "in_production": self.in_production,
+            "has_community_award": self.has_community_award,
+            "_geoloc": self.geoloc.map(|(lat, lng)| serde_json::json!({"lat": lat, "lng": lng})),
+        })
+    }
+
+    /// Thumbnail to show: the beer's own label, else the brewery logo. Untappd's
+    /// generic placeholder label doesn't count, and neither does anything but
+    /// an `https://` URL (these come from a third party and go into a `src`).
+    pub fn thumbnail(&self) -> Option<&str> {
+        let https = |url: &&str| url.starts_with("https://");
+        self.beer_label
+            .as_deref()
+            .filter(|url| https(url) && !url.contains(DEFAULT_LABEL_MARKER))
+            .or(self.brewery_label.as_deref().filter(https))
+    }
+}
+
+/// The Algolia query variants tried for a menu entry, in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchVariant {
+    /// TapHunter's full display name (`beer.name`), e.g. "Allagash Curieux".
+    FullName,
+    /// Normalized short brewery name plus the stripped beer name.
+    CommonName,
+    /// Beer name alone, keeping only hits from the entry's brewery.
+    BreweryFiltered,
+}
+
+impl SearchVariant {
+    /// Short label for logs and the dev runner's audit output.
+    pub fn label(&self) -> &'static str {
+        match self {
+            SearchVariant::FullName => "full-name",
+            SearchVariant::CommonName => "common-name",
+            SearchVariant::BreweryFiltered => "brewery-filtered",
+        }
+    }
 }
 
 impl RatingResult {
-    /// Render the rating as the anchor cell used in the output table.
+    /// The score as a number; `None` for unrated ("N/A") beers.
+    pub fn score(&self) -> Option<f64> {
+        self.rating.parse::<f64>().ok()
+    }
+
+    /// Untappd beer id, from the trailing segment of the beer URL
+    /// (`https://untappd.com/b/<slug>/<bid>`).
+    pub fn bid(&self) -> Option<u64> {
+        self.url.rsplit('/').next()?.parse().ok()
+    }
+
+    /// Render the rating as the anchor cell used in the output table, with the
+    /// check-in count alongside a real score, e.g. "4.12 (3.2k)".
     pub fn to_cell(&self) -> String {
-        format!("<a href=\"{}\">{}</a>", self.url, self.rating)
+        if self.score().is_some() && self.rating_count > 0 {
+            format!(
+                "<a href=\"{}\">{} <span class=\"rating-count\">({})</span></a>",
+                self.url,
+                self.rating,
+                format_count(self.rating_count)
+            )
+        } else {
+            format!("<a href=\"{}\">{}</a>", self.url, self.rating)
+        }
+    }
+
+    /// Serialize for the KV cache. `matched_by` is diagnostic only and is not
+    /// persisted.
+    pub fn to_cache_json(&self) -> String {
+        serde_json::json!({
+            "rating": self.rating,
+            "url": self.url,
+            "rating_count": self.rating_count,
+            "details": self.details.to_json(),
+            "fetched_at": self.fetched_at.map(|at| at.to_rfc3339()),
+        })
+        .to_string()
+    }
+
+    /// Inverse of `to_cache_json`; `None` if the cached value is malformed.
+    pub fn from_cache_json(json: &str) -> Option<RatingResult> {
+        let value: serde_json::Value = serde_json::from_str(json).ok()?;
+        Some(RatingResult {
+            rating: value.get("rating")?.as_str()?.to_string(),
+            url: value.get("url")?.as_str()?.to_string(),
+            rating_count: value
+                .get("rating_count")
+                .and_then(|c| c.as_u64())
+                .unwrap_or(0),
+            matched_by: None,
+            details: value
+                .get("details")
+                .map(BeerDetails::from_json)
+                .unwrap_or_default(),
+            fetched_at: value
+                .get("fetched_at")
+                .and_then(|at| at.as_str())
+                .and_then(crate::clock::parse_utc),
+        })
+    }
+}
+
+/// Compact check-in count: 12, 3.2k, 548k, 1.2M.
+pub fn format_count(count: u64) -> String {
+    match count {
+        0..=999 => count.to_string(),
+        1_000..=9_999 => format!("{:.1}k", count as f64 / 1_000.0),
+        10_000..=999_999 => format!("{}k", count / 1_000),
+        _ => format!("{:.1}M", count as f64 / 1_000_000.0),
     }
 }
 
-/// A beer entry paired with its pre-rendered rating cell ("N/A" if unresolved).
+/// A beer entry paired with its resolved rating (`None` if unresolved).
 #[derive(Debug, Clone)]
 pub struct RatedBeer {
     pub entry: BeerEntry,
-    pub rating_html: String,
+    pub rating: Option<RatingResult>,
+    /// How the rating lookup went; tells "not on Untappd" from "couldn't ask".
+    pub outcome: LookupOutcome,
+}
+
+/// Result of one beer's rating lookup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LookupOutcome {
+    /// Served from the KV cache (a rating or a cached "not found").
+    Cached,
+    /// Looked up just now.
+    Fresh,
+    /// Untappd has no such beer (also cached, for a day).
+    NotFound,
+    /// Untappd/Algolia refused us.
+    Blocked,
+    /// The search request failed in transit (or couldn't be built).
+    NetworkError,
+    /// Untappd answered with something we couldn't read.
+    ParseError,
+    /// Not looked up: the page's subrequest budget ran out first (see
+    /// `budget::RatingPlan`). A later load gets to it.
+    Pending,
+}
+
+impl LookupOutcome {
+    /// All outcomes, in display order.
+    pub const ALL: [LookupOutcome; 7] = [
+        LookupOutcome::Cached,
+        LookupOutcome::Fresh,
+        LookupOutcome::NotFound,
+        LookupOutcome::Blocked,
+        LookupOutcome::NetworkError,
+        LookupOutcome::ParseError,
+        LookupOutcome::Pending,
+    ];
+
+    /// The outcome of a lookup that failed with `error`.
+    pub fn from_error(error: &AppError) -> LookupOutcome {
+        match error {
+            AppError::NotFound => LookupOutcome::NotFound,
+            AppError::Blocked(_) => LookupOutcome::Blocked,
+            AppError::Parse(_) => LookupOutcome::ParseError,
+            AppError::Network(_) | AppError::Client(_) | AppError::Internal(_) => {
+                LookupOutcome::NetworkError
+            }
+        }
+    }
+
+    /// True when we couldn't find out (as opposed to finding no rating). These
+    /// are never cached, so the next page load retries them.
+    pub fn is_failure(self) -> bool {
+        matches!(
+            self,
+            LookupOutcome::Blocked | LookupOutcome::NetworkError | LookupOutcome::ParseError
+        )
+    }
+
+    /// Short name for logs, stats and tooltips.
+    pub fn label(self) -> &'static str {
+        match self {
+            LookupOutcome::Cached => "cached",
+            LookupOutcome::Fresh => "fresh",
+            LookupOutcome::NotFound => "not found",
+            LookupOutcome::Blocked => "blocked",
+            LookupOutcome::NetworkError => "network error",
+            LookupOutcome::ParseError => "parse error",
+            LookupOutcome::Pending => "pending",
+        }
+    }
+}
+
+/// Lookup outcomes tallied over a menu.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LookupStats {
+    counts: HashMap<LookupOutcome, usize>,
+    total: usize,
+}
+
+impl LookupStats {
+    pub fn tally(beers: &[RatedBeer]) -> LookupStats {
+        let mut stats = LookupStats::default();
+        for b in beers {
+            *stats.counts.entry(b.outcome).or_default() += 1;
+            stats.total += 1;
+        }
+        stats
+    }
+
+    pub fn count(&self, outcome: LookupOutcome) -> usize {
+        self.counts.get(&outcome).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Lookups that failed (see `LookupOutcome::is_failure`).
+    pub fn failed(&self) -> usize {
+        LookupOutcome::ALL
+            .iter()
+            .filter(|o| o.is_failure())
+            .map(|&o| self.count(o))
+            .sum()
+    }
+
+    /// Share of lookups that failed (0 for an empty menu).
+    pub fn failure_rate(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.failed() as f64 / self.total as f64
+        }
+    }
+
+    /// The most common failure, if any failed.
+    pub fn main_failure(&self) -> Option<LookupOutcome> {
+        LookupOutcome::ALL
+            .into_iter()
+            .filter(|o| o.is_failure() && self.count(*o) > 0)
+            .max_by_key(|&o| self.count(o))
+    }
+}
+
+/// Highest ABV we believe (eisbocks top out in the 60s); above is a typo.
+const MAX_ABV: f64 = 70.0;
+/// Highest IBU we believe; beyond ~120 is marketing, beyond this a typo.
+const MAX_IBU: u32 = 200;
+
+/// Parse a menu ABV ("6.5", "6.5%", " 10 "). Blank, non-numeric or out-of-range
+/// values are `None` rather than a silent 0.0.
+pub fn parse_abv(raw: &str) -> Option<f64> {
+    raw.trim()
+        .trim_end_matches('%')
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|abv| (0.0..=MAX_ABV).contains(abv))
+}
+
+/// Parse a menu IBU ("30"). Blank, zero (TapHunter's "unknown"), non-numeric
+/// or implausible values are `None`.
+pub fn parse_ibu(raw: &str) -> Option<u32> {
+    raw.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|ibu| *ibu >= 1.0 && *ibu <= f64::from(MAX_IBU))
+        .map(|ibu| ibu.round() as u32)
 }
 
 impl BeerEntry {
-    /// ABV parsed as a number for sorting/heatmapping; 0.0 if unparseable.
-    pub fn abv_value(&self) -> f64 {
-        self.abv
-            .replace('%', "")
-            .trim()
-            .parse::<f64>()
-            .unwrap_or(0.0)
+    /// Whole days the keg has been on as of `today` (never negative); `None` if
+    /// the menu had no usable date.
+    pub fn days_old(&self, today: NaiveDate) -> Option<i64> {
+        self.date_added
+            .map(|added| (today - added).num_days().max(0))
+    }
+}
+
+/// How categories are ordered on the menu.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CategoryOrder {
+    /// A-Z by category name.
+    #[default]
+    Alphabetical,
+    /// The venue's order: TapHunter's `category.order` where set, otherwise by
+    /// each category's lowest tap number (how the bigscreen page lays out).
+    Venue,
+    /// Light to heavy (`PROGRESSION`), the way people drink through a menu.
+    Progression,
+    /// An explicit list of category names (case-insensitive); unlisted
+    /// categories follow alphabetically.
+    Custom(Vec<String>),
+}
+
+/// Light-to-heavy category order for `CategoryOrder::Progression`, covering
+/// TapHunter's `style_category` names and the style taxonomy's families.
+const PROGRESSION: &[&str] = &[
+    "Hard Seltzers",
+    "Seltzer",
+    "Cider",
+    "Pilsners and Pale Lagers",
+    "Lager",
+    "Wheat Beers",
+    "Wheat",
+    "Blonde and Pale Ales",
+    "Pale Ale",
+    "Sour and Fruit Beers",
+    "Sour",
+    "Belgian & Farmhouse",
+    "IPAs",
+    "IPA",
+    "Amber, Red, and Brown Ales",
+    "Amber & Brown",
+    "Specialty Beers",
+    "Specialty",
+    "Strong Ales",
+    "Strong Ale",
+    "Porters and Stouts",
+    "Stout & Porter",
+];
+
+impl CategoryOrder {
+    /// Parse a config/query value: "alphabetical", "venue", "progression", or
+    /// a comma-separated list of category names.
+    pub fn parse(value: &str) -> Option<CategoryOrder> {
+        match value.trim() {
+            "" => None,
+            "alphabetical" | "alpha" => Some(CategoryOrder::Alphabetical),
+            "venue" => Some(CategoryOrder::Venue),
+            "progression" => Some(CategoryOrder::Progression),
+            list => Some(CategoryOrder::Custom(
+                list.split(',')
+                    .map(|c| c.trim().to_string())
+                    .filter(|c| !c.is_empty())
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// How beers are ordered within a category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WithinCategory {
+    /// Ascending ABV, unknowns last.
+    #[default]
+    Abv,
+    /// Ascending tap number.
+    Tap,
+    /// Best Untappd score first, unrated last.
+    Rating,
+    /// A-Z by beer name.
+    Name,
+    /// Best fit to the viewer's taste profile first (`recommend::sort_for_you`,
+    /// applied after this sort); ABV order until then or without a profile.
+    ForYou,
+}
+
+impl WithinCategory {
+    /// Parse a config/query value: "abv", "tap", "rating", "name" or "foryou".
+    pub fn parse(value: &str) -> Option<WithinCategory> {
+        match value.trim() {
+            "abv" => Some(WithinCategory::Abv),
+            "tap" => Some(WithinCategory::Tap),
+            "rating" => Some(WithinCategory::Rating),
+            "name" => Some(WithinCategory::Name),
+            "foryou" => Some(WithinCategory::ForYou),
+            _ => None,
+        }
+    }
+}
+
+/// Menu ordering: categories, then beers within each.
+#[derive(Debug, Clone, Default)]
+pub struct SortOptions {
+    pub categories: CategoryOrder,
+    pub within: WithinCategory,
+}
+
+impl SortOptions {
+    /// Override from a request query string (`order=venue&sort=rating`).
+    /// Unknown keys and values are ignored.
+    pub fn with_query(mut self, query: &str) -> SortOptions {
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "order" => {
+                    if let Some(order) = CategoryOrder::parse(&value) {
+                        self.categories = order;
+                    }
+                }
+                "sort" => {
+                    if let Some(within) = WithinCategory::parse(&value) {
+                        self.within = within;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self
     }
 }
 
 /// Sort rated beers by category (ascending), then by ABV ascending within a
-/// category. (The previous implementation sorted ABV lexicographically via a
-/// string column — this sorts numerically, which matches the stated intent.)
+/// category, with unknown ABVs last. (The previous implementation sorted ABV
+/// lexicographically via a string column — this sorts numerically, which
+/// matches the stated intent.)
 pub fn sort_rated(beers: &mut [RatedBeer]) {
+    sort_rated_with(beers, &SortOptions::default());
+}
+
+/// `sort_rated` with an explicit category order and within-category sort.
+pub fn sort_rated_with(beers: &mut [RatedBeer], options: &SortOptions) {
+    let ranks = category_ranks(beers, &options.categories);
     beers.sort_by(|a, b| {
-        a.entry.category.cmp(&b.entry.category).then(
-            a.entry
-                .abv_value()
-                .partial_cmp(&b.entry.abv_value())
-                .unwrap_or(std::cmp::Ordering::Equal),
-        )
+        ranks[&a.entry.category]
+            .cmp(&ranks[&b.entry.category])
+            .then_with(|| cmp_within(a, b, options.within))
+    });
+}
+
+/// Indexes of `beers` in `sort_rated_with` order, leaving `beers` as they are.
+pub fn sorted_order(beers: &[RatedBeer], options: &SortOptions) -> Vec<usize> {
+    let ranks = category_ranks(beers, &options.categories);
+    let mut order: Vec<usize> = (0..beers.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&beers[a], &beers[b]);
+        ranks[&a.entry.category]
+            .cmp(&ranks[&b.entry.category])
+            .then_with(|| cmp_within(a, b, options.within))
     });
+    order
+}
+
+/// Position of each distinct category under `order`.
+fn category_ranks(beers: &[RatedBeer], order: &CategoryOrder) -> HashMap<String, usize> {
+    // Per category: lowest venue `category.order`, lowest tap number.
+    let mut seen: HashMap<&str, (Option<i64>, i32)> = HashMap::new();
+    for b in beers {
+        let e = &b.entry;
+        let slot = seen
+            .entry(e.category.as_str())
+            .or_insert((e.category_order, e.tap_number));
+        slot.0 = match (slot.0, e.category_order) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        slot.1 = slot.1.min(e.tap_number);
+    }
+
+    // A subfamily group ("IPA / Hazy") ranks with its family.
+    let listed = |names: &[&str], category: &str| {
+        let position = |c: &str| names.iter().position(|n| n.eq_ignore_ascii_case(c));
+        position(category)
+            .or_else(|| position(taxonomy::family_part(category)))
+            .unwrap_or(names.len())
+    };
+    let mut categories: Vec<(&str, (Option<i64>, i32))> = seen.into_iter().collect();
+    match order {
+        CategoryOrder::Alphabetical => categories.sort_by(|a, b| a.0.cmp(b.0)),
+        CategoryOrder::Venue => categories.sort_by(|a, b| {
+            // Explicit venue order first (unset last), then tap layout.
+            (a.1 .0.is_none(), a.1 .0, a.1 .1, a.0).cmp(&(b.1 .0.is_none(), b.1 .0, b.1 .1, b.0))
+        }),
+        CategoryOrder::Progression => {
+            categories.sort_by_key(|c| (listed(PROGRESSION, c.0), c.0));
+        }
+        CategoryOrder::Custom(names) => {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            categories.sort_by_key(|c| (listed(&names, c.0), c.0));
+        }
+    }
+    categories
+        .into_iter()
+        .enumerate()
+        .map(|(rank, (category, _))| (category.to_string(), rank))
+        .collect()
+}
+
+fn cmp_within(a: &RatedBeer, b: &RatedBeer, within: WithinCategory) -> Ordering {
+    match within {
+        WithinCategory::Abv | WithinCategory::ForYou => cmp_abv(a.entry.abv, b.entry.abv),
+        WithinCategory::Tap => a.entry.tap_number.cmp(&b.entry.tap_number),
+        WithinCategory::Rating => {
+            let score = |x: &RatedBeer| x.rating.as_ref().and_then(RatingResult::score);
+            // Best first, unrated last.
+            match (score(a), score(b)) {
+                (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(Ordering::Equal),
+                (a, b) => cmp_abv(a, b),
+            }
+        }
+        WithinCategory::Name => a.entry.name.cmp(&b.entry.name),
+    }
+}
+
+/// Ascending ABV, unknowns after every known value.
+fn cmp_abv(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(category: &str, abv: Option<f64>) -> RatedBeer {
+        RatedBeer {
+            entry: BeerEntry {
+                tap_number: 1,
+                brewery: "B".into(),
+                brewery_full: "B".into(),
+                name: format!("{abv:?}"),
+                full_name: String::new(),
+                abv,
+                ibu: None,
+                category: category.into(),
+                category_order: None,
+                origin: String::new(),
+                style: String::new(),
+                date_added: None,
+                pours: Vec::new(),
+            },
+            rating: None,
+            outcome: LookupOutcome::NotFound,
+        }
+    }
+
+    #[test]
+    fn tallies_lookup_outcomes() {
+        let with = |outcome| RatedBeer {
+            outcome,
+            ..entry("IPA", None)
+        };
+        let beers = [
+            with(LookupOutcome::Cached),
+            with(LookupOutcome::Fresh),
+            with(LookupOutcome::NotFound),
+            with(LookupOutcome::from_error(&AppError::Blocked("403".into()))),
+            with(LookupOutcome::from_error(&AppError::Parse("shape".into()))),
+            with(LookupOutcome::from_error(&AppError::Parse("shape".into()))),
+        ];
+        let stats = LookupStats::tally(&beers);
+        assert_eq!(stats.total(), 6);
+        assert_eq!(stats.count(LookupOutcome::ParseError), 2);
+        assert_eq!(stats.failed(), 3);
+        assert_eq!(stats.failure_rate(), 0.5);
+        assert_eq!(stats.main_failure(), Some(LookupOutcome::ParseError));
+        assert_eq!(LookupStats::tally(&beers[..3]).main_failure(), None);
+        assert_eq!(LookupStats::default().failure_rate(), 0.0);
+    }
+
+    #[test]
+    fn parses_and_validates_abv_and_ibu() {
+        assert_eq!(parse_abv("6.5"), Some(6.5));
+        assert_eq!(parse_abv(" 6.5% "), Some(6.5));
+        assert_eq!(parse_abv("0.1"), Some(0.1));
+        assert_eq!(parse_abv(""), None);
+        assert_eq!(parse_abv("n/a"), None);
+        assert_eq!(parse_abv("650"), None);
+        assert_eq!(parse_ibu("30"), Some(30));
+        assert_eq!(parse_ibu("0"), None);
+        assert_eq!(parse_ibu("9000"), None);
+        assert_eq!(parse_ibu(""), None);
+    }
+
+    #[test]
+    fn unknown_abv_sorts_last_in_its_category() {
+        let mut beers = vec![
+            entry("IPA", None),
+            entry("IPA", Some(7.0)),
+            entry("Amber", None),
+            entry("IPA", Some(5.5)),
+        ];
+        sort_rated(&mut beers);
+        let order: Vec<(&str, Option<f64>)> = beers
+            .iter()
+            .map(|b| (b.entry.category.as_str(), b.entry.abv))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("Amber", None),
+                ("IPA", Some(5.5)),
+                ("IPA", Some(7.0)),
+                ("IPA", None)
+            ]
+        );
+    }
+
+    fn tap(category: &str, tap_number: i32, order: Option<i64>) -> RatedBeer {
+        let mut b = entry(category, Some(5.0));
+        b.entry.tap_number = tap_number;
+        b.entry.category_order = order;
+        b
+    }
+
+    fn scored(rating: &str) -> RatingResult {
+        RatingResult {
+            rating: rating.into(),
+            url: String::new(),
+            rating_count: 100,
+            matched_by: None,
+            details: BeerDetails::default(),
+            fetched_at: None,
+        }
+    }
+
+    fn categories(beers: &[RatedBeer]) -> Vec<&str> {
+        let mut seen: Vec<&str> = beers.iter().map(|b| b.entry.category.as_str()).collect();
+        seen.dedup();
+        seen
+    }
+
+    #[test]
+    fn category_order_modes() {
+        let menu = || {
+            vec![
+                tap("Porters and Stouts", 1, None),
+                tap("IPAs", 5, Some(2)),
+                tap("Pilsners and Pale Lagers", 9, Some(1)),
+                tap("Guest Taps", 3, None),
+            ]
+        };
+        let sorted = |categories| {
+            let mut beers = menu();
+            let options = SortOptions {
+                categories,
+                ..Default::default()
+            };
+            sort_rated_with(&mut beers, &options);
+            beers
+        };
+
+        let venue = sorted(CategoryOrder::Venue);
+        assert_eq!(
+            categories(&venue),
+            // Explicit venue order first, the rest by where they sit on the taps.
+            [
+                "Pilsners and Pale Lagers",
+                "IPAs",
+                "Porters and Stouts",
+                "Guest Taps"
+            ]
+        );
+        let progression = sorted(CategoryOrder::Progression);
+        assert_eq!(
+            categories(&progression),
+            [
+                "Pilsners and Pale Lagers",
+                "IPAs",
+                "Porters and Stouts",
+                "Guest Taps"
+            ]
+        );
+        let custom = sorted(CategoryOrder::parse("porters and stouts, guest taps").unwrap());
+        assert_eq!(
+            categories(&custom),
+            [
+                "Porters and Stouts",
+                "Guest Taps",
+                "IPAs",
+                "Pilsners and Pale Lagers"
+            ]
+        );
+        assert_eq!(CategoryOrder::parse(""), None);
+        assert_eq!(CategoryOrder::parse("venue"), Some(CategoryOrder::Venue));
+    }
+
+    #[test]
+    fn within_category_sorts() {
+        let mut beers = vec![
+            tap("IPAs", 7, None),
+            tap("IPAs", 2, None),
+            tap("IPAs", 4, None),
+        ];
+        beers[0].entry.abv = Some(6.0);
+        beers[1].entry.abv = Some(7.5);
+        beers[2].entry.abv = Some(4.8);
+        beers[0].rating = Some(scored("3.90"));
+        beers[2].rating = Some(scored("4.10"));
+        let taps = |beers: &[RatedBeer]| -> Vec<i32> {
+            beers.iter().map(|b| b.entry.tap_number).collect()
+        };
+
+        let options = SortOptions::default().with_query("sort=tap");
+        sort_rated_with(&mut beers, &options);
+        assert_eq!(taps(&beers), [2, 4, 7]);
+        sort_rated_with(
+            &mut beers,
+            &SortOptions::default().with_query("sort=rating"),
+        );
+        assert_eq!(taps(&beers), [4, 7, 2]);
+        sort_rated_with(&mut beers, &SortOptions::default().with_query("sort=bogus"));
+        assert_eq!(taps(&beers), [4, 7, 2]);
+    }
+
+    #[test]
+    fn parses_pours() {
+        let taster = Pour::parse("5 oz", "$3.50");
+        assert_eq!((taster.oz, taster.price), (Some(5.0), Some(3.5)));
+        assert_eq!(Pour::parse("Pint", "").oz, Some(16.0));
+        assert_eq!(Pour::parse("Half Pint", "$4").oz, Some(8.0));
+        let odd = Pour::parse("Growler", "call");
+        assert_eq!((odd.oz, odd.price), (None, None));
+    }
+
+    #[test]
+    fn days_old_is_relative_to_today() {
+        let mut b = entry("IPA", Some(6.0)).entry;
+        let today = NaiveDate::from_ymd_opt(2026, 6, 1).unwrap();
+        assert_eq!(b.days_old(today), None);
+        b.date_added = NaiveDate::from_ymd_opt(2026, 5, 20);
+        assert_eq!(b.days_old(today), Some(12));
+        // A date in the future (clock skew) reads as brand new.
+        b.date_added = NaiveDate::from_ymd_opt(2026, 6, 2);
+        assert_eq!(b.days_old(today), Some(0));
+    }
+
+    #[test]
+    fn format_count_is_compact() {
+        assert_eq!(format_count(12), "12");
+        assert_eq!(format_count(3_249), "3.2k");
+        assert_eq!(format_count(548_317), "548k");
+        assert_eq!(format_count(1_250_000), "1.2M");
+    }
+
+    #[test]
+    fn cache_json_round_trips() {
+        let rating = RatingResult {
+            rating: "4.12".into(),
+            url: "https://untappd.com/b/x/1".into(),
+            rating_count: 3_249,
+            matched_by: Some(SearchVariant::FullName),
+            details: BeerDetails {
+                beer_label: Some("https://assets.untappd.com/label.jpeg".into()),
+                ibu: Some(38),
+                style: Some("Pale Ale - American".into()),
+                in_production: Some(false),
+                has_community_award: true,
+                geoloc: Some((39.7242, -121.815)),
+                ..Default::default()
+            },
+            fetched_at: crate::clock::parse_utc("2026-05-20T22:16:40Z"),
+        };
+        let back = RatingResult::from_cache_json(&rating.to_cache_json()).unwrap();
+        assert_eq!(back.fetched_at, rating.fetched_at);
+        assert_eq!(back.rating, "4.12");
+        assert_eq!(back.rating_count, 3_249);
+        assert_eq!(back.bid(), Some(1));
+        assert_eq!(back.details, rating.details);
+        // The search variant is diagnostic only; it isn't cached.
+        assert_eq!(back.matched_by, None);
+        assert_eq!(
+            RatingResult::from_cache_json("<a href=\"x\">4.12</a>"),
+            None
+        );
+        // Entries cached before `fetched_at` existed still load, undated.
+        let old = r#"{"rating":"4.12","url":"https://untappd.com/b/x/1","rating_count":3}"#;
+        assert_eq!(RatingResult::from_cache_json(old).unwrap().fetched_at, None);
+    }
+
+    #[test]
+    fn thumbnail_skips_the_placeholder_label() {
+        let details = BeerDetails {
+            beer_label: Some(
+                "https://assets.untappd.com/site/assets/images/temp/badge-beer-default.png".into(),
+            ),
+            brewery_label: Some("https://assets.untappd.com/brewery.jpeg".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            details.thumbnail(),
+            Some("https://assets.untappd.com/brewery.jpeg")
+        );
+        assert_eq!(BeerDetails::default().thumbnail(), None);
+        let scripted = BeerDetails {
+            beer_label: Some("javascript:alert(1)".into()),
+            brewery_label: Some("http://assets.untappd.com/brewery.jpeg".into()),
+            ..Default::default()
+        };
+        assert_eq!(scripted.thumbnail(), None);
+    }
 }
@@ -0,0 +1,244 @@
+//
+// Brewery/beer name normalization shared by the menu parser and the Untappd
+// query builder. TapHunter's `brewery.common_name` ("Allagash") rarely matches
+// Untappd's `brewery_name` ("Allagash Brewing Company") verbatim, and menu beer
+// names often repeat the brewery in a slightly different form ("Alvarado
+// Brewery Pink Limo Dads" under "Alvarado Street Brewery"). Everything here is
+// pure string munging so it is host-testable.
+//
+
+/// Words that only say "this is a brewery" and carry no identity. Stripped when
+/// comparing brewery names and when building search strings.
+const BREWERY_SUFFIXES: &[&str] = &[
+    "brewing",
+    "brewery",
+    "breweries",
+    "brewers",
+    "brewhouse",
+    "beer",
+    "beers",
+    "company",
+    "co",
+    "works",
+    "inc",
+    "llc",
+];
+
+/// Curated menu-spelling → canonical brewery name for known mismatches (typos
+/// on the venue's TapHunter account, renamed breweries, ...). Keys are compared
+/// after `normalize`, so punctuation/case/diacritics don't matter. The canonical
+/// name is what we display and what we search Untappd for.
+const BREWERY_ALIASES: &[(&str, &str)] = &[
+    ("racho west", "Rancho West"),
+    ("living swell kobucha", "Living Swell Kombucha"),
+    ("hubbards cave", "Hubbard's Cave"),
+    ("grains of wrath", "Grains of Wrath"),
+];
+
+/// Fold common Latin diacritics to their ASCII base letter ("Kölsch" → "Kolsch").
+/// Characters outside the table pass through unchanged.
+pub fn fold_diacritics(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' => 'a',
+            'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å' | 'Ā' => 'A',
+            'ç' | 'č' | 'ć' => 'c',
+            'Ç' | 'Č' | 'Ć' => 'C',
+            'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ě' => 'e',
+            'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ě' => 'E',
+            'ì' | 'í' | 'î' | 'ï' | 'ī' => 'i',
+            'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' => 'I',
+            'ñ' | 'ń' | 'ň' => 'n',
+            'Ñ' | 'Ń' | 'Ň' => 'N',
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' => 'o',
+            'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ö' | 'Ø' | 'Ō' => 'O',
+            'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' => 'u',
+            'Ù' | 'Ú' | 'Û' | 'Ü' | 'Ū' | 'Ů' => 'U',
+            'ý' | 'ÿ' => 'y',
+            'Ý' => 'Y',
+            'ž' | 'ź' | 'ż' => 'z',
+            'Ž' | 'Ź' | 'Ż' => 'Z',
+            'š' | 'ś' => 's',
+            'Š' | 'Ś' => 'S',
+            'ř' => 'r',
+            'Ř' => 'R',
+            other => other,
+        })
+        .collect()
+}
+
+/// Comparison form of a name: diacritics folded, lowercased, "&" spelled out as
+/// "and", apostrophes dropped ("Hubbard’s" → "hubbards"), all other punctuation
+/// treated as a word break, whitespace collapsed.
+pub fn normalize(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in fold_diacritics(text).chars() {
+        match c {
+            '\'' | '’' | '‘' | '`' => {}
+            '&' => out.push_str(" and "),
+            c if c.is_alphanumeric() => out.extend(c.to_lowercase()),
+            _ => out.push(' '),
+        }
+    }
+    out.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Canonical spelling for a menu brewery name: the alias table entry if there
+/// is one, otherwise the name unchanged.
+pub fn canonical_brewery(brewery: &str) -> &str {
+    let key = normalize(brewery);
+    BREWERY_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == key)
+        .map_or(brewery, |(_, canonical)| canonical)
+}
+
+/// Identity-bearing words of a brewery name: the normalized canonical name with
+/// the generic suffixes ("Brewing", "Co.", "Beer Company", ...) removed. Falls
+/// back to the full normalized name if it is nothing *but* suffix words.
+pub fn brewery_core(brewery: &str) -> String {
+    let full = normalize(canonical_brewery(brewery));
+    let core: Vec<&str> = full
+        .split(' ')
+        .filter(|w| !BREWERY_SUFFIXES.contains(w))
+        .collect();
+    if core.is_empty() {
+        full
+    } else {
+        core.join(" ")
+    }
+}
+
+/// True if two brewery names refer to the same brewery once aliases, suffixes,
+/// punctuation and diacritics are accounted for ("Allagash" vs "Allagash
+/// Brewing Company").
+pub fn same_brewery(a: &str, b: &str) -> bool {
+    let (a, b) = (brewery_core(a), brewery_core(b));
+    !a.is_empty() && a == b
+}
+
+/// Remove a leading brewery mention from a beer name, tolerating the usual menu
+/// variations: "Alvarado Brewery Pink Limo Dads" under "Alvarado Street Brewery"
+/// becomes "Pink Limo Dads". The brewery's core words must appear in order from
+/// the first word (suffix words may be interleaved), all of them unless a
+/// suffix word follows the ones that did, so "Russian Imperial Stout" under
+/// "Russian River" keeps its name; it is also returned unchanged if stripping
+/// would leave nothing.
+pub fn strip_brewery(name: &str, brewery: &str) -> String {
+    let core = brewery_core(brewery);
+    let core: Vec<&str> = core.split(' ').filter(|w| !w.is_empty()).collect();
+    if core.is_empty() {
+        return name.to_string();
+    }
+
+    let words: Vec<&str> = name.split_whitespace().collect();
+    let mut matched = 0; // core words consumed
+    let mut skip = 0; // name words consumed
+    let mut suffixed = false; // a suffix word followed the core words
+    for word in &words {
+        let norm = normalize(word).replace(' ', "");
+        if matched < core.len() && norm == core[matched] {
+            matched += 1;
+        } else if matched > 0 && BREWERY_SUFFIXES.contains(&norm.as_str()) {
+            // "Brewery"/"Co." between or after the core words.
+            suffixed = true;
+        } else {
+            break;
+        }
+        skip += 1;
+    }
+
+    let mentioned = matched == core.len() || (matched > 0 && suffixed);
+    if !mentioned || skip == words.len() {
+        name.to_string()
+    } else {
+        words[skip..].join(" ")
+    }
+}
+
+/// Algolia search string for a menu beer: the brewery's core words plus the beer
+/// name, both normalized. Dropping "Brewing"/"Co." matters because Algolia
+/// requires every query word to match, so a menu "Brewery" against an Untappd
+/// "Brewing Company" would otherwise miss entirely.
+pub fn search_string(brewery: &str, name: &str) -> String {
+    let name = normalize(&strip_brewery(name, brewery));
+    let brewery = brewery_core(brewery);
+    format!("{brewery} {name}").trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_folds_case_punctuation_and_diacritics() {
+        assert_eq!(normalize("Kölsch"), "kolsch");
+        assert_eq!(normalize("Hubbard’s Cave"), "hubbards cave");
+        assert_eq!(normalize("Barrel & Bottle"), "barrel and bottle");
+        assert_eq!(normalize("  Bike-Dog  Brewing Co. "), "bike dog brewing co");
+    }
+
+    #[test]
+    fn brewery_core_strips_suffixes() {
+        assert_eq!(brewery_core("Allagash Brewing Company"), "allagash");
+        assert_eq!(brewery_core("Almanac Beer Co."), "almanac");
+        assert_eq!(brewery_core("Fort George Brewery"), "fort george");
+        // Nothing but suffixes: keep the whole thing rather than nothing.
+        assert_eq!(brewery_core("Brewing Company"), "brewing company");
+    }
+
+    #[test]
+    fn same_brewery_across_menu_and_untappd_spellings() {
+        assert!(same_brewery("Allagash", "Allagash Brewing Company"));
+        assert!(same_brewery("Sierra Nevada", "Sierra Nevada Brewing Co."));
+        assert!(same_brewery("Racho West", "Rancho West Brewery"));
+        assert!(!same_brewery("Moonlight", "Moonraker Brewing Co."));
+    }
+
+    #[test]
+    fn aliases_fix_known_mismatches() {
+        assert_eq!(canonical_brewery("Racho West"), "Rancho West");
+        assert_eq!(canonical_brewery("RACHO  WEST"), "Rancho West");
+        assert_eq!(canonical_brewery("Humble Sea"), "Humble Sea");
+    }
+
+    #[test]
+    fn strip_brewery_handles_menu_variations() {
+        assert_eq!(
+            strip_brewery("Alvarado Brewery Pink Limo Dads", "Alvarado Street Brewery"),
+            "Pink Limo Dads"
+        );
+        assert_eq!(
+            strip_brewery("Grains of Wrath Overkill IPA", "Grains Of Wrath"),
+            "Overkill IPA"
+        );
+        assert_eq!(
+            strip_brewery("Rancho West NON-ALC Lager", "Racho West"),
+            "NON-ALC Lager"
+        );
+        // Only a leading mention is stripped, and never down to nothing.
+        assert_eq!(strip_brewery("Milk Stout", "Bike Dog"), "Milk Stout");
+        assert_eq!(strip_brewery("Allagash", "Allagash"), "Allagash");
+        // A beer name that merely starts like the brewery is left alone.
+        assert_eq!(
+            strip_brewery("Russian Imperial Stout", "Russian River"),
+            "Russian Imperial Stout"
+        );
+        assert_eq!(
+            strip_brewery("Green Bullet IPA", "Green Flash"),
+            "Green Bullet IPA"
+        );
+    }
+
+    #[test]
+    fn search_string_uses_core_brewery_and_folded_name() {
+        assert_eq!(
+            search_string("Grains Of Wrath", "Sacred Spires Kölsch"),
+            "grains of wrath sacred spires kolsch"
+        );
+        assert_eq!(
+            search_string("Fort George Brewery", "Fort George Ponchatoula Strawberry"),
+            "fort george ponchatoula strawberry"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//
+// Per-style rating percentiles. Untappd averages differ a lot by style (a great
+// pilsner rarely clears 3.8, an average stout sits near 3.9), so one global
+// color scale paints every lager red. These helpers rank a score within its
+// category instead: against the other beers of that category on tonight's
+// menu when there are enough of them, otherwise against a stored per-style
+// distribution.
+//
+
+use crate::taxonomy;
+
+/// Fewest scored beers in a category for the menu itself to be the yardstick.
+pub const MIN_MENU_SAMPLE: usize = 3;
+
+/// Stored Untappd score quantiles (p10, p25, p50, p75, p90) per TapHunter
+/// `style_category`, for categories too thin on the menu to rank against.
+/// Rough figures from browsing Untappd's style pages; refine as needed.
+const STYLE_DISTRIBUTIONS: &[(&str, [f64; 5])] = &[
+    ("Amber, Red, and Brown Ales", [3.35, 3.50, 3.62, 3.75, 3.87]),
+    ("Blonde and Pale Ales", [3.35, 3.50, 3.62, 3.75, 3.88]),
+    ("Hard Seltzers", [3.00, 3.20, 3.40, 3.60, 3.80]),
+    ("IPAs", [3.55, 3.70, 3.85, 3.98, 4.10]),
+    ("Pilsners and Pale Lagers", [3.25, 3.40, 3.55, 3.68, 3.80]),
+    ("Porters and Stouts", [3.55, 3.72, 3.88, 4.02, 4.20]),
+    ("Sour and Fruit Beers", [3.45, 3.60, 3.75, 3.90, 4.05]),
+    ("Specialty Beers", [3.20, 3.40, 3.60, 3.75, 3.90]),
+    ("Strong Ales", [3.55, 3.70, 3.85, 4.00, 4.15]),
+    ("Wheat Beers", [3.30, 3.45, 3.60, 3.72, 3.85]),
+    // Taxonomy families (`taxonomy::StyleFamily`), for menus grouped by family.
+    ("Amber & Brown", [3.35, 3.50, 3.62, 3.75, 3.87]),
+    ("Belgian & Farmhouse", [3.50, 3.65, 3.78, 3.90, 4.02]),
+    ("Cider", [3.20, 3.40, 3.55, 3.70, 3.85]),
+    ("IPA", [3.55, 3.70, 3.85, 3.98, 4.10]),
+    ("Lager", [3.25, 3.40, 3.55, 3.68, 3.80]),
+    ("Pale Ale", [3.35, 3.50, 3.62, 3.75, 3.88]),
+    ("Seltzer", [3.00, 3.20, 3.40, 3.60, 3.80]),
+    ("Sour", [3.45, 3.60, 3.75, 3.90, 4.05]),
+    ("Stout & Porter", [3.55, 3.72, 3.88, 4.02, 4.20]),
+    ("Strong Ale", [3.55, 3.70, 3.85, 4.00, 4.15]),
+    ("Wheat", [3.30, 3.45, 3.60, 3.72, 3.85]),
+];
+
+/// Quantiles for a category not in the table (≈ Untappd overall).
+const DEFAULT_DISTRIBUTION: [f64; 5] = [3.40, 3.60, 3.75, 3.90, 4.05];
+
+const QUANTILES: [f64; 5] = [0.10, 0.25, 0.50, 0.75, 0.90];
+/// How far past p10/p90 a score must be to reach the 0th/100th percentile.
+const TAIL_SPAN: f64 = 0.3;
+
+/// Percentile (0.0-1.0) of `score` among `peers` (which include it): the worst
+/// is 0, the best 1, ties share the midpoint. `None` if there are too few peers
+/// to rank against (see `MIN_MENU_SAMPLE`).
+pub fn menu_percentile(score: f64, peers: &[f64]) -> Option<f64> {
+    if peers.len() < MIN_MENU_SAMPLE {
+        return None;
+    }
+    let below = peers.iter().filter(|&&p| p < score).count() as f64;
+    let equal = peers.iter().filter(|&&p| p == score).count().max(1) as f64;
+    Some((below + (equal - 1.0) / 2.0) / (peers.len() as f64 - 1.0))
+}
+
+/// Percentile (0.0-1.0) of `score` within the stored distribution for
+/// `category`, interpolating linearly between the stored quantiles. A
+/// subfamily group ("IPA / Hazy") uses its family's distribution.
+pub fn stored_percentile(score: f64, category: &str) -> f64 {
+    let lookup = |name: &str| {
+        STYLE_DISTRIBUTIONS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, q)| *q)
+    };
+    let q = lookup(category)
+        .or_else(|| lookup(taxonomy::family_part(category)))
+        .unwrap_or(DEFAULT_DISTRIBUTION);
+
+    let mut points = vec![(q[0] - TAIL_SPAN, 0.0)];
+    points.extend(q.iter().copied().zip(QUANTILES));
+    points.push((q[4] + TAIL_SPAN, 1.0));
+
+    if score <= points[0].0 {
+        return 0.0;
+    }
+    for pair in points.windows(2) {
+        let ((v0, p0), (v1, p1)) = (pair[0], pair[1]);
+        if score <= v1 {
+            return p0 + (p1 - p0) * (score - v0) / (v1 - v0);
+        }
+    }
+    1.0
+}
+
+/// Percentile for `score` in `category`: ranked among the menu `peers` when
+/// there are enough of them, else from the stored distribution.
+pub fn style_percentile(score: f64, category: &str, peers: &[f64]) -> f64 {
+    menu_percentile(score, peers).unwrap_or_else(|| stored_percentile(score, category))
+}
+
+/// "best" / "top 10%" / "median" / "bottom 25%" phrasing for tooltips.
+pub fn describe(percentile: f64) -> String {
+    match (percentile * 100.0).round() as i64 {
+        100.. => "best".to_string(),
+        50 => "median".to_string(),
+        pct @ 51..=99 => format!("top {}%", 100 - pct),
+        pct @ 1..=49 => format!("bottom {pct}%"),
+        _ => "lowest".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_percentile_ranks_best_and_worst() {
+        let peers = [3.5, 3.7, 3.9];
+        assert_eq!(menu_percentile(3.9, &peers), Some(1.0));
+        assert_eq!(menu_percentile(3.5, &peers), Some(0.0));
+        assert_eq!(menu_percentile(3.7, &peers), Some(0.5));
+        // Ties share the midpoint.
+        assert_eq!(menu_percentile(3.7, &[3.7, 3.7, 3.9]), Some(0.25));
+        // Too few peers to rank against.
+        assert_eq!(menu_percentile(3.9, &[3.5, 3.9]), None);
+    }
+
+    #[test]
+    fn stored_percentile_is_style_relative() {
+        // The same 3.7 is a strong pilsner but a weak IPA.
+        let lager = stored_percentile(3.7, "Pilsners and Pale Lagers");
+        let ipa = stored_percentile(3.7, "IPAs");
+        assert!(lager > 0.75, "got {lager}");
+        assert!(ipa < 0.30, "got {ipa}");
+        // Stored quantiles are hit exactly, and the tails clamp.
+        assert!((stored_percentile(3.85, "IPAs") - 0.5).abs() < 1e-9);
+        assert_eq!(stored_percentile(2.0, "IPAs"), 0.0);
+        assert_eq!(stored_percentile(5.0, "IPAs"), 1.0);
+        // Taxonomy groups: a subfamily falls back to its family's quantiles.
+        assert_eq!(
+            stored_percentile(3.7, "IPA / Hazy"),
+            stored_percentile(3.7, "IPA")
+        );
+    }
+
+    #[test]
+    fn style_percentile_falls_back_to_stored() {
+        assert_eq!(style_percentile(3.6, "IPAs", &[3.2, 3.4, 3.6]), 1.0);
+        assert_eq!(
+            style_percentile(3.6, "IPAs", &[3.6]),
+            stored_percentile(3.6, "IPAs")
+        );
+    }
+
+    #[test]
+    fn describe_reads_naturally() {
+        assert_eq!(describe(0.9), "top 10%");
+        assert_eq!(describe(1.0), "best");
+        assert_eq!(describe(0.0), "lowest");
+        assert_eq!(describe(0.5), "median");
+        assert_eq!(describe(0.2), "bottom 20%");
+    }
+}
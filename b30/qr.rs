@@ -0,0 +1,589 @@
+//
+// Minimal QR code encoder for the printable menu's per-beer Untappd links.
+// Pure Rust with no dependencies so it runs in the wasm build: byte mode,
+// error-correction level M (survives smudges on a paper menu), versions 1-10
+// (up to 213 bytes; an Untappd beer URL needs 60-100). Follows ISO/IEC 18004;
+// the structure mirrors Project Nayuki's reference encoder.
+//
+
+use crate::error::{AppError, AppResult};
+
+/// Largest version we build (57x57 modules).
+const MAX_VERSION: usize = 10;
+/// Error-correction codewords per block at level M, by version (index 0 unused).
+const ECC_PER_BLOCK: [usize; MAX_VERSION + 1] = [0, 10, 16, 26, 18, 24, 16, 18, 22, 22, 26];
+/// Error-correction blocks at level M, by version.
+const BLOCKS: [usize; MAX_VERSION + 1] = [0, 1, 1, 1, 2, 2, 4, 4, 4, 5, 5];
+/// Format-information bits for level M.
+const LEVEL_M_BITS: u32 = 0b00;
+/// Light modules around the symbol the spec requires for scanning.
+const QUIET_ZONE: usize = 4;
+
+/// An encoded symbol: `size` x `size` modules, `true` = dark.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrCode {
+    pub version: usize,
+    pub size: usize,
+    modules: Vec<bool>,
+}
+
+impl QrCode {
+    /// Dark module at column `x`, row `y`?
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    /// Standalone SVG (one path, quiet zone included) scaled to fill its box;
+    /// size it with CSS.
+    pub fn to_svg(&self) -> String {
+        let full = self.size + 2 * QUIET_ZONE;
+        let mut path = String::new();
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if self.get(x, y) {
+                    path.push_str(&format!("M{},{}h1v1h-1z", x + QUIET_ZONE, y + QUIET_ZONE));
+                }
+            }
+        }
+        format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {full} {full}\" \
+             shape-rendering=\"crispEdges\"><rect width=\"{full}\" height=\"{full}\" fill=\"#fff\"/>\
+             <path d=\"{path}\" fill=\"#000\"/></svg>"
+        )
+    }
+}
+
+/// Encode `data` in byte mode at the smallest version that fits, choosing the
+/// mask with the lowest penalty. Too long for version 10 is a `Client` error.
+pub fn encode(data: &[u8]) -> AppResult<QrCode> {
+    let version = (1..=MAX_VERSION)
+        .find(|&v| 4 + count_bits(v) + data.len() * 8 <= data_codewords(v) * 8)
+        .ok_or_else(|| {
+            AppError::Client(format!(
+                "{} bytes is too long for a version-{MAX_VERSION} QR code",
+                data.len()
+            ))
+        })?;
+    let codewords = add_ecc_and_interleave(&data_codewords_for(data, version), version);
+
+    let mut symbol = Symbol::new(version);
+    symbol.draw_function_patterns();
+    symbol.draw_codewords(&codewords);
+    let mask = (0..8)
+        .min_by_key(|&mask| {
+            let mut trial = symbol.clone();
+            trial.apply_mask(mask);
+            trial.draw_format_bits(mask);
+            trial.penalty()
+        })
+        .unwrap_or(0);
+    symbol.apply_mask(mask);
+    symbol.draw_format_bits(mask);
+    Ok(QrCode {
+        version,
+        size: symbol.size,
+        modules: symbol.modules,
+    })
+}
+
+/// Width of the byte-mode character count field.
+fn count_bits(version: usize) -> usize {
+    if version <= 9 {
+        8
+    } else {
+        16
+    }
+}
+
+/// Modules available for data and ECC once the function patterns are placed.
+fn raw_data_modules(version: usize) -> usize {
+    let v = version;
+    let mut result = (16 * v + 128) * v + 64;
+    if v >= 2 {
+        let align = v / 7 + 2;
+        result -= (25 * align - 10) * align - 55;
+        if v >= 7 {
+            result -= 36;
+        }
+    }
+    result
+}
+
+fn data_codewords(version: usize) -> usize {
+    raw_data_modules(version) / 8 - ECC_PER_BLOCK[version] * BLOCKS[version]
+}
+
+/// Mode indicator, count, payload, terminator and padding, as codewords.
+fn data_codewords_for(data: &[u8], version: usize) -> Vec<u8> {
+    let capacity = data_codewords(version) * 8;
+    let mut bits: Vec<bool> = Vec::with_capacity(capacity);
+    let mut push = |value: u32, len: usize| {
+        for i in (0..len).rev() {
+            bits.push((value >> i) & 1 == 1);
+        }
+    };
+    push(0b0100, 4);
+    push(data.len() as u32, count_bits(version));
+    for &b in data {
+        push(u32::from(b), 8);
+    }
+    let terminator = (capacity - bits.len()).min(4);
+    bits.extend(std::iter::repeat_n(false, terminator));
+    bits.extend(std::iter::repeat_n(false, (8 - bits.len() % 8) % 8));
+
+    let mut bytes: Vec<u8> = bits
+        .chunks(8)
+        .map(|byte| {
+            byte.iter()
+                .fold(0u8, |acc, &bit| (acc << 1) | u8::from(bit))
+        })
+        .collect();
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if bytes.len() * 8 >= capacity {
+            break;
+        }
+        bytes.push(pad);
+    }
+    bytes
+}
+
+/// Split into blocks, append each block's Reed-Solomon ECC, and interleave.
+fn add_ecc_and_interleave(data: &[u8], version: usize) -> Vec<u8> {
+    let blocks = BLOCKS[version];
+    let ecc_len = ECC_PER_BLOCK[version];
+    let raw = raw_data_modules(version) / 8;
+    let short_blocks = blocks - raw % blocks;
+    let short_len = raw / blocks;
+    let divisor = rs_divisor(ecc_len);
+
+    let mut split = Vec::with_capacity(blocks);
+    let mut k = 0;
+    for i in 0..blocks {
+        let len = short_len - ecc_len + usize::from(i >= short_blocks);
+        let mut block = data[k..k + len].to_vec();
+        k += len;
+        let ecc = rs_remainder(&block, &divisor);
+        if i < short_blocks {
+            block.push(0); // placeholder so every block has the same length
+        }
+        block.extend(ecc);
+        split.push(block);
+    }
+
+    let mut result = Vec::with_capacity(raw);
+    for i in 0..split[0].len() {
+        for (j, block) in split.iter().enumerate() {
+            if i != short_len - ecc_len || j >= short_blocks {
+                result.push(block[i]);
+            }
+        }
+    }
+    result
+}
+
+/// Multiply in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1.
+fn gf_mul(x: u8, y: u8) -> u8 {
+    let mut z: u32 = 0;
+    for i in (0..8).rev() {
+        z = (z << 1) ^ ((z >> 7) * 0x11D);
+        z ^= ((u32::from(y) >> i) & 1) * u32::from(x);
+    }
+    z as u8
+}
+
+/// Generator polynomial of `degree`, highest coefficient (always 1) dropped.
+fn rs_divisor(degree: usize) -> Vec<u8> {
+    let mut result = vec![0u8; degree];
+    result[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            result[j] = gf_mul(result[j], root);
+            if j + 1 < degree {
+                result[j] ^= result[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    result
+}
+
+fn rs_remainder(data: &[u8], divisor: &[u8]) -> Vec<u8> {
+    let mut result = vec![0u8; divisor.len()];
+    for &b in data {
+        let factor = b ^ result.remove(0);
+        result.push(0);
+        for (r, &d) in result.iter_mut().zip(divisor) {
+            *r ^= gf_mul(d, factor);
+        }
+    }
+    result
+}
+
+/// The module grid while it's being built, with which modules are reserved for
+/// function patterns (and so skipped by data placement and masking).
+#[derive(Clone)]
+struct Symbol {
+    version: usize,
+    size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl Symbol {
+    fn new(version: usize) -> Symbol {
+        let size = version * 4 + 17;
+        Symbol {
+            version,
+            size,
+            modules: vec![false; size * size],
+            function: vec![false; size * size],
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+
+    fn draw_function_patterns(&mut self) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i.is_multiple_of(2));
+            self.set_function(i, 6, i.is_multiple_of(2));
+        }
+        self.draw_finder(3, 3);
+        self.draw_finder(size - 4, 3);
+        self.draw_finder(3, size - 4);
+
+        let positions = alignment_positions(self.version, size);
+        let last = positions.len().saturating_sub(1);
+        for (i, &x) in positions.iter().enumerate() {
+            for (j, &y) in positions.iter().enumerate() {
+                // Skip the three corners the finder patterns occupy.
+                let at_end = |k: usize| k == 0 || k == last;
+                let finder = at_end(i) && at_end(j) && (i, j) != (last, last);
+                if !finder {
+                    self.draw_alignment(x, y);
+                }
+            }
+        }
+
+        self.draw_format_bits(0); // reserve; redrawn once the mask is chosen
+        self.draw_version_bits();
+    }
+
+    fn draw_finder(&mut self, x: usize, y: usize) {
+        for dy in -4i32..=4 {
+            for dx in -4i32..=4 {
+                let (xx, yy) = (x as i32 + dx, y as i32 + dy);
+                if (0..self.size as i32).contains(&xx) && (0..self.size as i32).contains(&yy) {
+                    let dist = dx.abs().max(dy.abs());
+                    self.set_function(xx as usize, yy as usize, dist != 2 && dist != 4);
+                }
+            }
+        }
+    }
+
+    fn draw_alignment(&mut self, x: usize, y: usize) {
+        for dy in -2i32..=2 {
+            for dx in -2i32..=2 {
+                let dark = dx.abs().max(dy.abs()) != 1;
+                self.set_function((x as i32 + dx) as usize, (y as i32 + dy) as usize, dark);
+            }
+        }
+    }
+
+    fn draw_format_bits(&mut self, mask: u32) {
+        let data = (LEVEL_M_BITS << 3) | mask;
+        let mut rem = data;
+        for _ in 0..10 {
+            rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+        }
+        let bits = ((data << 10) | rem) ^ 0x5412;
+        let bit = |i: usize| (bits >> i) & 1 == 1;
+        let size = self.size;
+
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i));
+        }
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i));
+        }
+        self.set_function(8, size - 8, true); // the always-dark module
+    }
+
+    fn draw_version_bits(&mut self) {
+        if self.version < 7 {
+            return;
+        }
+        let mut rem = self.version as u32;
+        for _ in 0..12 {
+            rem = (rem << 1) ^ ((rem >> 11) * 0x1F25);
+        }
+        let bits = ((self.version as u32) << 12) | rem;
+        for i in 0..18 {
+            let dark = (bits >> i) & 1 == 1;
+            let (a, b) = (self.size - 11 + i % 3, i / 3);
+            self.set_function(a, b, dark);
+            self.set_function(b, a, dark);
+        }
+    }
+
+    /// Place codewords in the two-column zigzag from the bottom-right corner.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let mut i = 0;
+        for (x, y) in zigzag(self.size) {
+            if !self.function[y * self.size + x] && i < codewords.len() * 8 {
+                self.modules[y * self.size + x] = (codewords[i >> 3] >> (7 - (i & 7))) & 1 == 1;
+                i += 1;
+            }
+        }
+    }
+
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                if !self.function[y * self.size + x] && mask_bit(mask, x, y) {
+                    self.modules[y * self.size + x] ^= true;
+                }
+            }
+        }
+    }
+
+    /// ISO penalty score (lower reads better): runs, 2x2 blocks,
+    /// finder-lookalikes, and dark/light imbalance.
+    fn penalty(&self) -> u32 {
+        let size = self.size;
+        let mut score = 0;
+        let lines = (0..size).flat_map(|i| {
+            [
+                (0..size).map(|j| self.get(j, i)).collect::<Vec<bool>>(),
+                (0..size).map(|j| self.get(i, j)).collect::<Vec<bool>>(),
+            ]
+        });
+        const FINDER_LIKE: [bool; 11] = [
+            true, false, true, true, true, false, true, false, false, false, false,
+        ];
+        for line in lines {
+            let mut run = 1;
+            for j in 1..=size {
+                if j < size && line[j] == line[j - 1] {
+                    run += 1;
+                } else {
+                    if run >= 5 {
+                        score += run - 2;
+                    }
+                    run = 1;
+                }
+            }
+            for window in line.windows(11) {
+                if window.iter().eq(FINDER_LIKE.iter())
+                    || window.iter().eq(FINDER_LIKE.iter().rev())
+                {
+                    score += 40;
+                }
+            }
+        }
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let c = self.get(x, y);
+                if c == self.get(x + 1, y) && c == self.get(x, y + 1) && c == self.get(x + 1, y + 1)
+                {
+                    score += 3;
+                }
+            }
+        }
+        let total = size * size;
+        let dark = self.modules.iter().filter(|&&m| m).count();
+        let k = (dark * 20).abs_diff(total * 10).div_ceil(total) - 1;
+        score + k as u32 * 10
+    }
+}
+
+/// Centers of the alignment patterns along each axis.
+fn alignment_positions(version: usize, size: usize) -> Vec<usize> {
+    if version == 1 {
+        return Vec::new();
+    }
+    let count = version / 7 + 2;
+    let step = (version * 4 + count * 2 + 1) / (count * 2 - 2) * 2;
+    let mut positions = vec![6];
+    positions.extend((0..count - 1).rev().map(|i| size - 7 - i * step));
+    positions
+}
+
+/// Data-module visiting order: column pairs right to left (skipping the
+/// vertical timing column), alternately upward and downward.
+fn zigzag(size: usize) -> Vec<(usize, usize)> {
+    let mut order = Vec::with_capacity(size * size);
+    let mut right = size as i32 - 1;
+    while right >= 1 {
+        if right == 6 {
+            right = 5;
+        }
+        for vert in 0..size {
+            for j in 0..2 {
+                let x = (right - j) as usize;
+                let upward = (right + 1) & 2 == 0;
+                let y = if upward { size - 1 - vert } else { vert };
+                order.push((x, y));
+            }
+        }
+        right -= 2;
+    }
+    order
+}
+
+fn mask_bit(mask: u32, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y).is_multiple_of(2),
+        1 => y.is_multiple_of(2),
+        2 => x.is_multiple_of(3),
+        3 => (x + y).is_multiple_of(3),
+        4 => (x / 3 + y / 2).is_multiple_of(2),
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3).is_multiple_of(2),
+        _ => ((x + y) % 2 + x * y % 3).is_multiple_of(2),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Read a symbol back: format bits, unmask, de-interleave, check every
+    /// block's Reed-Solomon syndromes, and decode the byte-mode payload.
+    fn decode(code: &QrCode) -> Vec<u8> {
+        let mut format = 0u32;
+        for i in (0..15).rev() {
+            let (x, y) = match i {
+                0..=5 => (8, i),
+                6 => (8, 7),
+                7 => (8, 8),
+                8 => (7, 8),
+                _ => (14 - i, 8),
+            };
+            format = (format << 1) | u32::from(code.get(x, y));
+        }
+        let format = format ^ 0x5412;
+        assert_eq!(format >> 13, LEVEL_M_BITS, "error-correction level");
+        let mask = (format >> 10) & 7;
+
+        let mut reference = Symbol::new(code.version);
+        reference.draw_function_patterns();
+        let mut bits = Vec::new();
+        for (x, y) in zigzag(code.size) {
+            if !reference.function[y * code.size + x] {
+                bits.push(code.get(x, y) ^ mask_bit(mask, x, y));
+            }
+        }
+        let raw: Vec<u8> = bits
+            .chunks_exact(8)
+            .map(|b| b.iter().fold(0u8, |acc, &bit| (acc << 1) | u8::from(bit)))
+            .collect();
+
+        // De-interleave into blocks (short blocks first).
+        let v = code.version;
+        let (blocks, ecc_len) = (BLOCKS[v], ECC_PER_BLOCK[v]);
+        let total = raw_data_modules(v) / 8;
+        let short_blocks = blocks - total % blocks;
+        let short_data = total / blocks - ecc_len;
+        let mut split: Vec<Vec<u8>> = vec![Vec::new(); blocks];
+        let mut next = raw.iter();
+        for i in 0..=short_data {
+            for (j, block) in split.iter_mut().enumerate() {
+                if i < short_data || j >= short_blocks {
+                    block.push(*next.next().unwrap());
+                }
+            }
+        }
+        for _ in 0..ecc_len {
+            for block in &mut split {
+                block.push(*next.next().unwrap());
+            }
+        }
+        let mut data: Vec<u8> = Vec::new();
+        for block in &split {
+            // A valid codeword evaluates to zero at every generator root.
+            let mut root = 1u8;
+            for _ in 0..ecc_len {
+                let syndrome = block.iter().fold(0u8, |acc, &c| gf_mul(acc, root) ^ c);
+                assert_eq!(syndrome, 0);
+                root = gf_mul(root, 2);
+            }
+            data.extend(&block[..block.len() - ecc_len]);
+        }
+
+        let bit = |i: usize| (data[i / 8] >> (7 - i % 8)) & 1;
+        let read = |from: usize, len: usize| {
+            (from..from + len).fold(0usize, |acc, i| (acc << 1) | usize::from(bit(i)))
+        };
+        assert_eq!(read(0, 4), 0b0100, "byte mode");
+        let count_len = count_bits(v);
+        let len = read(4, count_len);
+        (0..len)
+            .map(|i| read(4 + count_len + i * 8, 8) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn reed_solomon_matches_the_reference_example() {
+        // "HELLO WORLD" at 1-M, from the standard's worked example.
+        let data = [
+            32, 91, 11, 120, 209, 114, 220, 77, 67, 64, 236, 17, 236, 17, 236, 17,
+        ];
+        assert_eq!(
+            rs_remainder(&data, &rs_divisor(10)),
+            [196, 35, 39, 119, 235, 215, 231, 226, 93, 23]
+        );
+    }
+
+    #[test]
+    fn capacities_match_the_spec() {
+        // Byte-mode capacity at level M for versions 1, 5 and 10.
+        assert_eq!(data_codewords(1), 16);
+        assert_eq!(data_codewords(5), 86);
+        assert_eq!(data_codewords(10), 216);
+        assert_eq!(alignment_positions(7, 45), [6, 22, 38]);
+    }
+
+    #[test]
+    fn encodes_and_decodes_untappd_urls() {
+        for url in [
+            "https://untappd.com/b/x/1",
+            "https://untappd.com/b/russian-river-brewing-company-pliny-the-elder/7936",
+            &format!("https://untappd.com/b/{}/123456", "a".repeat(150)),
+        ] {
+            let code = encode(url.as_bytes()).unwrap();
+            assert_eq!(code.size, code.version * 4 + 17);
+            assert_eq!(decode(&code), url.as_bytes());
+            // Finder pattern corners and the always-dark module.
+            assert!(code.get(0, 0) && code.get(code.size - 1, 0) && code.get(0, code.size - 1));
+            assert!(code.get(8, code.size - 8));
+        }
+        assert_eq!(encode(b"https://untappd.com/b/x/1").unwrap().version, 2);
+        assert!(encode(&[b'x'; 300]).is_err());
+    }
+
+    #[test]
+    fn svg_has_a_quiet_zone() {
+        let svg = encode(b"hi").unwrap().to_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("viewBox=\"0 0 29 29\""));
+        assert!(svg.contains("M4,4h1v1h-1z"));
+    }
+}
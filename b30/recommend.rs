@@ -0,0 +1,462 @@
+//
+// "For you" recommendations: score each tap against a taste profile (liked
+// style families, ABV range, bitterness tolerance, minimum rating) and pick the
+// best few with a one-line reason. A profile comes from the request
+// (`like=hazy,stout&abv=5-8&ibu=60&min=3.8`) or is learned from an imported
+// Untappd check-in history (see `TasteProfile::learn`).
+//
+
+use crate::model::RatedBeer;
+use crate::taxonomy::{StyleFamily, Taxonomy};
+use crate::tried::{Checkin, TriedList};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// How many picks to feature above the menu.
+pub const TOP_PICKS: usize = 5;
+/// Styles a learned profile keeps.
+const LEARNED_STYLES: usize = 5;
+/// Check-ins of a style before a learned profile counts it as liked.
+const MIN_STYLE_CHECKINS: usize = 2;
+/// Known ABVs/IBUs needed before a learned profile sets a range from them.
+const MIN_SAMPLE: usize = 5;
+/// Score baseline: a rating above this helps, below it hurts.
+const RATING_BASELINE: f64 = 3.75;
+
+/// What a user likes. Every field is optional; an empty profile recommends
+/// nothing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TasteProfile {
+    /// Style terms (`StyleFamily::matches`): "ipa", "hazy", "gose", ...
+    pub styles: Vec<String>,
+    /// Preferred ABV range, inclusive.
+    pub abv: Option<(f64, f64)>,
+    /// Bitterness tolerance: IBUs above this count against a beer.
+    pub max_ibu: Option<u32>,
+    /// Never recommend beers rated below this on Untappd.
+    pub min_rating: Option<f64>,
+}
+
+impl TasteProfile {
+    pub fn is_empty(&self) -> bool {
+        *self == TasteProfile::default()
+    }
+
+    /// A profile from query parameters: `like=hazy,stout`, `abv=5-8`,
+    /// `ibu=60`, `min=3.8`. Malformed values are ignored.
+    pub fn from_query(query: &str) -> TasteProfile {
+        let mut profile = TasteProfile::default();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "like" => {
+                    profile.styles = value
+                        .split(',')
+                        .map(|s| s.trim().to_string())
+                        .filter(|s| !s.is_empty())
+                        .collect();
+                }
+                "abv" => {
+                    profile.abv = value.split_once('-').and_then(|(lo, hi)| {
+                        let (lo, hi) = (lo.trim().parse().ok()?, hi.trim().parse().ok()?);
+                        (lo <= hi).then_some((lo, hi))
+                    });
+                }
+                "ibu" => profile.max_ibu = value.trim().parse().ok(),
+                "min" => profile.min_rating = value.trim().parse().ok(),
+                _ => {}
+            }
+        }
+        profile
+    }
+
+    /// `self`, with any field it leaves unset taken from `fallback` (an
+    /// explicit query refines a learned profile rather than replacing it).
+    pub fn or(self, fallback: TasteProfile) -> TasteProfile {
+        TasteProfile {
+            styles: if self.styles.is_empty() {
+                fallback.styles
+            } else {
+                self.styles
+            },
+            abv: self.abv.or(fallback.abv),
+            max_ibu: self.max_ibu.or(fallback.max_ibu),
+            min_rating: self.min_rating.or(fallback.min_rating),
+        }
+    }
+
+    /// Learn a profile from check-in history: the styles the user rates above
+    /// their own average (or, without ratings, drinks most), and the ABV/IBU
+    /// ranges covering the middle 80% of what they drink.
+    pub fn learn(checkins: &[Checkin], taxonomy: &Taxonomy) -> TasteProfile {
+        // Per style label: check-ins, and the user's ratings of them.
+        let mut styles: HashMap<String, (usize, Vec<f64>)> = HashMap::new();
+        for c in checkins {
+            let Some(family) = c.style.as_deref().and_then(|s| taxonomy.classify_style(s)) else {
+                continue;
+            };
+            let entry = styles.entry(family_term(&family)).or_default();
+            entry.0 += 1;
+            entry.1.extend(c.rating);
+        }
+        let ratings: Vec<f64> = checkins.iter().filter_map(|c| c.rating).collect();
+        let overall = mean(&ratings);
+
+        let mut liked: Vec<(String, usize, f64)> = styles
+            .into_iter()
+            .filter(|(_, (count, _))| *count >= MIN_STYLE_CHECKINS)
+            .map(|(term, (count, rated))| (term, count, mean(&rated).unwrap_or(0.0)))
+            .filter(|(_, _, avg)| overall.is_none_or(|overall| *avg >= overall))
+            .collect();
+        liked.sort_by(|a, b| {
+            b.2.partial_cmp(&a.2)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.1.cmp(&a.1))
+                .then(a.0.cmp(&b.0))
+        });
+
+        let abvs: Vec<f64> = checkins
+            .iter()
+            .filter_map(|c| c.abv)
+            .filter(|&a| a > 0.0)
+            .collect();
+        let ibus: Vec<f64> = checkins
+            .iter()
+            .filter_map(|c| c.ibu)
+            .map(f64::from)
+            .collect();
+        TasteProfile {
+            styles: liked
+                .into_iter()
+                .take(LEARNED_STYLES)
+                .map(|(term, _, _)| term)
+                .collect(),
+            abv: (abvs.len() >= MIN_SAMPLE).then(|| (quantile(&abvs, 0.1), quantile(&abvs, 0.9))),
+            max_ibu: (ibus.len() >= MIN_SAMPLE).then(|| quantile(&ibus, 0.9).round() as u32),
+            min_rating: None,
+        }
+    }
+
+    /// Stored form (KV `profile:<token>`).
+    pub fn to_json(&self) -> String {
+        json!({
+            "styles": self.styles,
+            "abv": self.abv.map(|(lo, hi)| [lo, hi]),
+            "max_ibu": self.max_ibu,
+            "min_rating": self.min_rating,
+        })
+        .to_string()
+    }
+
+    /// Parse the stored form; unreadable input is an empty profile.
+    pub fn from_json(json: &str) -> TasteProfile {
+        let Ok(v) = serde_json::from_str::<Value>(json) else {
+            return TasteProfile::default();
+        };
+        TasteProfile {
+            styles: v["styles"]
+                .as_array()
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|s| s.as_str().map(String::from))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            abv: v["abv"][0].as_f64().zip(v["abv"][1].as_f64()),
+            max_ibu: v["max_ibu"].as_u64().map(|i| i as u32),
+            min_rating: v["min_rating"].as_f64(),
+        }
+    }
+}
+
+/// KV key holding `user`'s learned profile.
+pub fn kv_key(user: &str) -> String {
+    format!("profile:{user}")
+}
+
+/// Filter term naming a family as specifically as known ("hazy ipa", "gose
+/// sour", "imperial stout").
+fn family_term(family: &StyleFamily) -> String {
+    style_name(family).to_lowercase()
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Nearest-rank quantile of a non-empty sample.
+fn quantile(values: &[f64], q: f64) -> f64 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let rank = ((sorted.len() - 1) as f64 * q).round() as usize;
+    sorted[rank]
+}
+
+/// How well one beer fits a profile. Higher is better; `reasons` are the
+/// positives, strongest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fit {
+    pub score: f64,
+    pub reasons: Vec<String>,
+}
+
+impl Fit {
+    /// The reasons as one line ("Hazy IPA, a style you like; 6.5% ABV, in your
+    /// range").
+    pub fn explanation(&self) -> String {
+        let line = self.reasons.join("; ");
+        let mut chars = line.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => line,
+        }
+    }
+}
+
+/// Score `beer` against `profile`; `None` if it falls below the profile's
+/// minimum rating.
+pub fn fit(beer: &RatedBeer, family: &StyleFamily, profile: &TasteProfile) -> Option<Fit> {
+    let rating = beer.rating.as_ref().and_then(|r| r.score());
+    if let Some(min) = profile.min_rating {
+        if rating.is_none_or(|r| r < min) {
+            return None;
+        }
+    }
+
+    let mut score = 0.0;
+    let mut reasons = Vec::new();
+    if profile.styles.iter().any(|s| family.matches(s)) {
+        score += 2.0;
+        reasons.push(format!("{}, a style you like", style_name(family)));
+    }
+    if let (Some((lo, hi)), Some(abv)) = (profile.abv, beer.entry.abv) {
+        if (lo..=hi).contains(&abv) {
+            score += 1.0;
+            reasons.push(format!("{abv}% ABV, in your range"));
+        } else {
+            score -= (abv - hi).max(lo - abv);
+        }
+    }
+    let ibu = beer
+        .entry
+        .ibu
+        .or_else(|| beer.rating.as_ref().and_then(|r| r.details.ibu));
+    if let (Some(max), Some(ibu)) = (profile.max_ibu, ibu) {
+        if ibu <= max {
+            score += 0.5;
+            reasons.push(format!("{ibu} IBU, not too bitter"));
+        } else {
+            score -= f64::from(ibu - max) / 20.0;
+        }
+    }
+    if let Some(r) = rating {
+        score += (r - RATING_BASELINE) * 2.0;
+        if r >= RATING_BASELINE {
+            reasons.push(format!("rated {r:.2} on Untappd"));
+        }
+    }
+    Some(Fit { score, reasons })
+}
+
+/// "Hazy IPA" / "Gose" / "Stout" for a sentence.
+fn style_name(family: &StyleFamily) -> String {
+    match &family.sub {
+        Some(sub) if sub.contains(&family.family) || family.family.contains('&') => sub.clone(),
+        Some(sub) => format!("{sub} {}", family.family),
+        None => family.family.clone(),
+    }
+}
+
+/// Each beer's fit (aligned with `beers`); beers already tried get `None`.
+pub fn fits(
+    beers: &[RatedBeer],
+    profile: &TasteProfile,
+    taxonomy: &Taxonomy,
+    tried: Option<&TriedList>,
+) -> Vec<Option<Fit>> {
+    beers
+        .iter()
+        .map(|b| {
+            let bid = b.rating.as_ref().and_then(|r| r.bid());
+            if tried.zip(bid).is_some_and(|(t, bid)| t.contains(bid)) {
+                return None;
+            }
+            fit(b, &taxonomy.classify(b), profile)
+        })
+        .collect()
+}
+
+/// A featured recommendation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pick {
+    pub tap_number: i32,
+    pub brewery: String,
+    pub name: String,
+    pub explanation: String,
+}
+
+/// The best `TOP_PICKS` fits with a positive score and at least one reason.
+pub fn top_picks(beers: &[RatedBeer], fits: &[Option<Fit>]) -> Vec<Pick> {
+    let mut ranked: Vec<(&RatedBeer, &Fit)> = beers
+        .iter()
+        .zip(fits)
+        .filter_map(|(b, f)| Some((b, f.as_ref()?)))
+        .filter(|(_, f)| f.score > 0.0 && !f.reasons.is_empty())
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.1.score
+            .partial_cmp(&a.1.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    ranked
+        .into_iter()
+        .take(TOP_PICKS)
+        .map(|(b, f)| Pick {
+            tap_number: b.entry.tap_number,
+            brewery: b.entry.brewery.clone(),
+            name: b.entry.name.clone(),
+            explanation: f.explanation(),
+        })
+        .collect()
+}
+
+/// The "for you" sort: best fit first within each (already contiguous)
+/// category, unscored beers last. Category order is left as sorted.
+pub fn sort_for_you(beers: &mut Vec<RatedBeer>, fits: Vec<Option<Fit>>) {
+    let mut group = 0;
+    let mut keyed: Vec<(usize, f64, RatedBeer)> = Vec::with_capacity(beers.len());
+    for (i, (b, f)) in beers.drain(..).zip(fits).enumerate() {
+        if i > 0 && keyed[i - 1].2.entry.category != b.entry.category {
+            group += 1;
+        }
+        keyed.push((group, f.map_or(f64::NEG_INFINITY, |f| f.score), b));
+    }
+    keyed.sort_by(|a, b| {
+        a.0.cmp(&b.0)
+            .then(b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal))
+    });
+    beers.extend(keyed.into_iter().map(|(_, _, b)| b));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BeerDetails, BeerEntry, LookupOutcome, RatingResult};
+
+    fn beer(tap: i32, category: &str, style: &str, abv: f64, ibu: u32, score: &str) -> RatedBeer {
+        RatedBeer {
+            entry: BeerEntry {
+                tap_number: tap,
+                brewery: "Brewery".into(),
+                brewery_full: "Brewery Co.".into(),
+                name: format!("Beer {tap}"),
+                full_name: format!("Brewery Beer {tap}"),
+                abv: Some(abv),
+                ibu: Some(ibu),
+                category: category.into(),
+                category_order: None,
+                origin: String::new(),
+                style: style.into(),
+                date_added: None,
+                pours: Vec::new(),
+            },
+            rating: Some(RatingResult {
+                rating: score.into(),
+                url: format!("https://untappd.com/b/beer/{tap}"),
+                rating_count: 500,
+                matched_by: None,
+                details: BeerDetails::default(),
+                fetched_at: None,
+            }),
+            outcome: LookupOutcome::Cached,
+        }
+    }
+
+    fn checkin(style: &str, abv: f64, ibu: u32, rating: Option<f64>) -> Checkin {
+        Checkin {
+            bid: 1,
+            beer_name: String::new(),
+            brewery_name: String::new(),
+            style: Some(style.into()),
+            abv: Some(abv),
+            ibu: Some(ibu),
+            rating,
+        }
+    }
+
+    fn menu() -> Vec<RatedBeer> {
+        vec![
+            beer(1, "IPAs", "Hazy IPA", 6.5, 40, "4.10"),
+            beer(2, "IPAs", "Double IPA", 9.0, 100, "4.20"),
+            beer(3, "Porters and Stouts", "Imperial Stout", 11.0, 60, "4.30"),
+            beer(4, "Pilsners and Pale Lagers", "Pilsner", 5.0, 30, "3.60"),
+        ]
+    }
+
+    #[test]
+    fn picks_fit_the_profile_and_explain_why() {
+        let profile = TasteProfile::from_query("like=hazy+ipa,pilsner&abv=4.5-7&ibu=60");
+        let beers = menu();
+        let fits = fits(&beers, &profile, &Taxonomy::builtin(), None);
+        let picks = top_picks(&beers, &fits);
+        // The hazy wins; the high-ABV stout and double IPA rate higher on
+        // Untappd but sit well outside the profile.
+        assert_eq!(picks[0].tap_number, 1);
+        assert_eq!(
+            picks[0].explanation,
+            "Hazy IPA, a style you like; 6.5% ABV, in your range; 40 IBU, not too bitter; rated 4.10 on Untappd"
+        );
+        assert!(picks.iter().all(|p| p.tap_number != 3));
+        // The minimum rating excludes outright.
+        let strict = TasteProfile::from_query("like=pilsner&min=3.8");
+        assert!(fit(&beers[3], &Taxonomy::builtin().classify(&beers[3]), &strict).is_none());
+    }
+
+    #[test]
+    fn tried_beers_are_not_recommended() {
+        let profile = TasteProfile::from_query("like=hazy");
+        let beers = menu();
+        let tried = TriedList::from_json("[1]");
+        let fits = fits(&beers, &profile, &Taxonomy::builtin(), Some(&tried));
+        assert!(fits[0].is_none());
+        assert!(top_picks(&beers, &fits).iter().all(|p| p.tap_number != 1));
+    }
+
+    #[test]
+    fn learns_styles_and_ranges_from_history() {
+        let history = vec![
+            checkin("IPA - New England / Hazy", 6.5, 35, Some(4.5)),
+            checkin("IPA - New England / Hazy", 7.0, 40, Some(4.25)),
+            checkin("Stout - Imperial / Double", 12.0, 70, Some(3.0)),
+            checkin("Stout - Imperial / Double", 11.0, 60, Some(3.25)),
+            checkin("Sour - Gose", 4.5, 10, Some(4.0)),
+            checkin("Sour - Gose", 4.2, 8, Some(4.0)),
+        ];
+        let profile = TasteProfile::learn(&history, &Taxonomy::builtin());
+        assert_eq!(profile.styles, ["hazy ipa", "gose sour"]);
+        assert_eq!(profile.abv, Some((4.5, 12.0)));
+        assert_eq!(profile.max_ibu, Some(70));
+        assert_eq!(TasteProfile::from_json(&profile.to_json()), profile);
+        // An explicit query refines, rather than replaces, what was learned.
+        let merged = TasteProfile::from_query("abv=4-6").or(profile.clone());
+        assert_eq!(merged.abv, Some((4.0, 6.0)));
+        assert_eq!(merged.styles, profile.styles);
+    }
+
+    #[test]
+    fn for_you_sort_keeps_categories_together() {
+        let mut beers = menu();
+        let profile = TasteProfile::from_query("like=hazy&abv=4-7");
+        let fits = fits(&beers, &profile, &Taxonomy::builtin(), None);
+        // Move the double IPA first so the sort has something to fix.
+        beers.swap(0, 1);
+        let fits = vec![
+            fits[1].clone(),
+            fits[0].clone(),
+            fits[2].clone(),
+            fits[3].clone(),
+        ];
+        sort_for_you(&mut beers, fits);
+        let taps: Vec<i32> = beers.iter().map(|b| b.entry.tap_number).collect();
+        assert_eq!(taps, [1, 2, 3, 4]);
+    }
+}
@@ -4,7 +4,17 @@
 // it is pure and host-testable, and drops a heavy dependency.
 //
 
-use crate::model::RatedBeer;
+use crate::clock::{self, VenueClock, VenueTimezone};
+use crate::error::AppError;
+use crate::flight::{Flight, FlightRequest};
+use crate::model::{format_count, LookupOutcome, LookupStats, RatedBeer, RatingResult};
+use crate::percentile;
+use crate::recommend::Pick;
+use crate::tried::TriedList;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 
 const HEADERS: [&str; 9] = [
     "category", "tap", "brewery", "name", "abv", "origin", "style", "age", "rating",
@@ -61,10 +71,129 @@ const STYLE: &str = r#"
     }
     /* ABV and rating cells get a continuously-interpolated background color
        (Excel-style color scale) emitted inline per cell — see ColorScale. */
+    /* Unknown ABV/age: neutral, like an unrated beer. */
+    .unknown {
+        background-color: #e0e0e0 !important;
+        color: #888;
+    }
     .rating-na {
         background-color: #e0e0e0 !important;
         color: #888;
     }
+    /* Accessible mode: dark enough for WCAG AA on the gray. */
+    .accessible .unknown,
+    .accessible .rating-na {
+        color: #555;
+    }
+    /* We couldn't ask Untappd (not "no rating"): hatched, and says so. */
+    .rating-failed {
+        background: repeating-linear-gradient(135deg, #f1f3f5 0 6px, #e0e0e0 6px 12px);
+        color: #555;
+        font-size: 0.85em;
+        font-style: italic;
+    }
+    /* Not looked up yet (subrequest budget): a quieter "coming soon". */
+    .rating-pending {
+        background-color: #f1f3f5 !important;
+        color: #777;
+        font-size: 0.85em;
+        font-style: italic;
+    }
+    .tier {
+        white-space: nowrap;
+        letter-spacing: -0.1em;
+    }
+    /* Read by screen readers, not shown. */
+    .visually-hidden {
+        position: absolute;
+        width: 1px;
+        height: 1px;
+        overflow: hidden;
+        clip: rect(0 0 0 0);
+        white-space: nowrap;
+    }
+    /* Untappd label art in front of the beer name. */
+    .label-thumb {
+        width: 28px;
+        height: 28px;
+        object-fit: cover;
+        border-radius: 4px;
+        vertical-align: middle;
+        margin-right: 6px;
+    }
+    .badge {
+        font-size: 0.75em;
+        white-space: nowrap;
+    }
+    .badge-retired {
+        padding: 1px 4px;
+        border-radius: 3px;
+        background-color: #eee;
+        color: #666;
+    }
+    /* Style backfilled from Untappd when the menu has none. */
+    .untappd-style {
+        font-style: italic;
+    }
+    .legend {
+        font-family: Arial, sans-serif;
+        font-size: 0.9em;
+        color: #555;
+    }
+    /* Rows the viewer has already tried fade back. */
+    tr.tried td:not(.category-cell) {
+        opacity: 0.55;
+    }
+    .tried-toggle {
+        font-size: 1.2em;
+        background: none;
+        border: none;
+        cursor: pointer;
+    }
+    td form {
+        margin: 0;
+    }
+    .picks {
+        font-family: Arial, sans-serif;
+        margin: 20px 0;
+    }
+    .picks h2 {
+        font-size: 1.2em;
+        margin: 0 0 8px 0;
+    }
+    .picks .pick-tap {
+        font-weight: bold;
+        color: #1971c2;
+        margin-right: 6px;
+    }
+    .flight-total {
+        font-family: Arial, sans-serif;
+        font-weight: bold;
+    }
+    .picks .pick-why {
+        color: #555;
+        font-size: 0.9em;
+    }
+    .stale-banner, .lookup-banner, .error-page {
+        font-family: Arial, sans-serif;
+        padding: 10px 14px;
+        border-radius: 4px;
+        background-color: #fff3bf;
+        border: 1px solid #f0c36d;
+    }
+    .error-page {
+        max-width: 40em;
+        margin: 40px auto;
+    }
+    .error-page h1 {
+        font-size: 1.4em;
+        margin: 0 0 8px 0;
+    }
+    /* Check-in count beside the score: present but secondary. */
+    .rating-count {
+        font-size: 0.8em;
+        opacity: 0.7;
+    }
     /* Review links inherit the cell's (black) text color. */
     td a {
         color: inherit;
@@ -81,6 +210,9 @@ type Rgb = (u8, u8, u8);
 /// only differ in the stops they supply.
 struct ColorScale {
     stops: &'static [(f64, Rgb)],
+    /// Text is white below this value and black from it on: where the ramp
+    /// turns light enough for black text to read.
+    white_text_below: f64,
 }
 
 impl ColorScale {
@@ -109,11 +241,15 @@ impl ColorScale {
         hi_c
     }
 
-    /// Inline `background-color`/`color` style for a heatmapped cell. Text is
-    /// always black; the stops below are bright/light enough to keep it legible.
+    /// Inline `background-color`/`color` style for a heatmapped cell.
     fn style(&self, value: f64) -> String {
         let (r, g, b) = self.color(value);
-        format!("background-color:#{r:02x}{g:02x}{b:02x};color:black")
+        let text = if value < self.white_text_below {
+            "white"
+        } else {
+            "black"
+        };
+        format!("background-color:#{r:02x}{g:02x}{b:02x};color:{text}")
     }
 }
 
@@ -124,39 +260,709 @@ fn lerp(a: u8, b: u8, t: f64) -> u8 {
 /// Rating heatmap. Bright RdYlGn palette (black text reads on all of it), tuned
 /// for Untappd's distribution so a ~3.95 already reads green ("mostly
 /// drinkable"), not yellow.
+const RATING_STOPS: [(f64, Rgb); 7] = [
+    (3.0, (0xd7, 0x30, 0x27)),  // red
+    (3.4, (0xf4, 0x6d, 0x43)),  // orange
+    (3.6, (0xfd, 0xae, 0x61)),  // light orange
+    (3.8, (0xfe, 0xe0, 0x8b)),  // yellow
+    (3.95, (0xa6, 0xd9, 0x6a)), // light green
+    (4.15, (0x66, 0xbd, 0x63)), // green
+    (4.4, (0x1a, 0x98, 0x50)),  // dark green
+];
 const RATING_SCALE: ColorScale = ColorScale {
-    stops: &[
-        (3.0, (0xd7, 0x30, 0x27)),  // red
-        (3.4, (0xf4, 0x6d, 0x43)),  // orange
-        (3.6, (0xfd, 0xae, 0x61)),  // light orange
-        (3.8, (0xfe, 0xe0, 0x8b)),  // yellow
-        (3.95, (0xa6, 0xd9, 0x6a)), // light green
-        (4.15, (0x66, 0xbd, 0x63)), // green
-        (4.4, (0x1a, 0x98, 0x50)),  // dark green
-    ],
+    stops: &RATING_STOPS,
+    white_text_below: f64::NEG_INFINITY,
 };
 
+/// Per-style percentile heatmap: the rating palette spread evenly over 0-100%,
+/// so the best beer of its style reads dark green whatever its raw score.
+const PERCENTILE_STOPS: [(f64, Rgb); 7] = [
+    (0.0, (0xd7, 0x30, 0x27)),  // red
+    (0.15, (0xf4, 0x6d, 0x43)), // orange
+    (0.3, (0xfd, 0xae, 0x61)),  // light orange
+    (0.5, (0xfe, 0xe0, 0x8b)),  // yellow
+    (0.7, (0xa6, 0xd9, 0x6a)),  // light green
+    (0.85, (0x66, 0xbd, 0x63)), // green
+    (1.0, (0x1a, 0x98, 0x50)),  // dark green
+];
+const PERCENTILE_SCALE: ColorScale = ColorScale {
+    stops: &PERCENTILE_STOPS,
+    white_text_below: f64::NEG_INFINITY,
+};
+
+/// How rating cells are colored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RatingColors {
+    /// One Untappd-wide scale (`RATING_SCALE`) for every beer.
+    #[default]
+    Global,
+    /// Percentile within the beer's category (see `percentile`), so the best
+    /// lager on tap stands out even at 3.7.
+    StylePercentile,
+}
+
+impl RatingColors {
+    /// Parse the `colors=` query value ("global" / "style").
+    pub fn from_param(value: &str) -> Option<RatingColors> {
+        match value {
+            "global" => Some(RatingColors::Global),
+            "style" => Some(RatingColors::StylePercentile),
+            _ => None,
+        }
+    }
+}
+
+/// Presentation knobs for `render_with`; `Default` matches `render`.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// How ABV and ratings are colored (`colors=`, `palette=`, `glyphs=`).
+    pub heatmap: Heatmap,
+    /// Date keg ages are computed against; `None` means today in the default
+    /// venue timezone (`clock::PACIFIC`).
+    pub today: Option<NaiveDate>,
+    /// The viewer's tried list; `Some` adds a "tried" toggle column.
+    pub tried: Option<TriedList>,
+    /// Leave out beers on the tried list (`hide=tried`).
+    pub hide_tried: bool,
+    /// Another tried list's token, from a `?user=` link opened in a browser
+    /// that has its own; the page offers to switch to it.
+    pub offered_user: Option<String>,
+    /// The request's query string, kept so toolbar links can flip one setting
+    /// and preserve the rest.
+    pub query: String,
+    /// "For you" recommendations featured above the table.
+    pub picks: Vec<Pick>,
+    /// Set when the live menu couldn't be fetched and this is the last good
+    /// copy: when (venue time) that copy was fetched. Shown as a banner.
+    pub stale_since: Option<DateTime<FixedOffset>>,
+    /// Timezone for the "rated as of" dates in rating tooltips; `None` means
+    /// `clock::PACIFIC`.
+    pub timezone: Option<VenueTimezone>,
+}
+
+impl RenderOptions {
+    /// Options from a request query string (`hide=tried`, plus the
+    /// `Heatmap` keys). Unknown keys and values are ignored.
+    pub fn from_query(query: &str) -> RenderOptions {
+        let mut options = RenderOptions {
+            query: query.to_string(),
+            ..Default::default()
+        };
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "hide" => options.hide_tried = value == "tried",
+                _ => options.heatmap.apply(&key, &value),
+            }
+        }
+        options
+    }
+
+    /// This page's query with `key` set to `value` (or removed if `None`),
+    /// escaped for use in an `href`.
+    fn query_with(&self, key: &str, value: Option<&str>) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        for (k, v) in url::form_urlencoded::parse(self.query.as_bytes()) {
+            if k != key {
+                query.append_pair(&k, &v);
+            }
+        }
+        if let Some(value) = value {
+            query.append_pair(key, value);
+        }
+        format!("?{}", query.finish()).replace('&', "&amp;")
+    }
+}
+
 /// ABV heatmap (monotonic). The green ramp is spread across the populated
 /// 4.5-7% range so low vs mid strengths read as visibly different greens; 7% is
 /// a deliberate jump to yellowish-green, then orange through ~10% and red above.
+const ABV_STOPS: [(f64, Rgb); 8] = [
+    (0.0, (0x1a, 0x98, 0x50)),  // dark green
+    (4.5, (0x4c, 0xb0, 0x50)),  // green
+    (5.5, (0x84, 0xc9, 0x5a)),  // medium-light green
+    (6.5, (0xc4, 0xe0, 0x7c)),  // light green
+    (7.0, (0xd9, 0xe8, 0x4c)),  // yellowish-green (noticeable jump at 7%)
+    (7.5, (0xfd, 0xae, 0x61)),  // light orange
+    (9.5, (0xf4, 0x6d, 0x43)),  // orange
+    (10.0, (0xd7, 0x30, 0x27)), // red (10%+)
+];
 const ABV_SCALE: ColorScale = ColorScale {
+    stops: &ABV_STOPS,
+    white_text_below: f64::NEG_INFINITY,
+};
+
+/// The standard palette's red, and the lighter one accessible mode swaps in:
+/// the darkest red black text clears WCAG AA (4.5:1) on.
+const STANDARD_RED: Rgb = (0xd7, 0x30, 0x27);
+const AA_RED: Rgb = (0xe0, 0x40, 0x2f);
+
+/// `stops` with `STANDARD_RED` lifted to `AA_RED`.
+const fn lift_red<const N: usize>(stops: [(f64, Rgb); N]) -> [(f64, Rgb); N] {
+    let mut lifted = stops;
+    let mut i = 0;
+    while i < N {
+        let (r, g, b) = stops[i].1;
+        if r == STANDARD_RED.0 && g == STANDARD_RED.1 && b == STANDARD_RED.2 {
+            lifted[i].1 = AA_RED;
+        }
+        i += 1;
+    }
+    lifted
+}
+
+/// The standard scales as accessible mode draws them.
+const AA_RATING_STOPS: [(f64, Rgb); 7] = lift_red(RATING_STOPS);
+const AA_RATING_SCALE: ColorScale = ColorScale {
+    stops: &AA_RATING_STOPS,
+    white_text_below: f64::NEG_INFINITY,
+};
+const AA_PERCENTILE_STOPS: [(f64, Rgb); 7] = lift_red(PERCENTILE_STOPS);
+const AA_PERCENTILE_SCALE: ColorScale = ColorScale {
+    stops: &AA_PERCENTILE_STOPS,
+    white_text_below: f64::NEG_INFINITY,
+};
+const AA_ABV_STOPS: [(f64, Rgb); 8] = lift_red(ABV_STOPS);
+const AA_ABV_SCALE: ColorScale = ColorScale {
+    stops: &AA_ABV_STOPS,
+    white_text_below: f64::NEG_INFINITY,
+};
+
+/// The colorblind-safe scales keep the standard ones' breakpoints (so tiers
+/// line up) but swap in perceptually uniform ramps that read the same with
+/// any color vision: viridis (purple to yellow) and cividis (blue to yellow),
+/// both sampled evenly, low values dark (so white text, up to where each
+/// ramp turns light).
+const VIRIDIS_RATING_SCALE: ColorScale = ColorScale {
+    stops: &[
+        (3.0, (0x44, 0x01, 0x54)),
+        (3.4, (0x44, 0x39, 0x83)),
+        (3.6, (0x31, 0x68, 0x8e)),
+        (3.8, (0x21, 0x91, 0x8c)),
+        (3.95, (0x35, 0xb7, 0x79)),
+        (4.15, (0x90, 0xd7, 0x43)),
+        (4.4, (0xfd, 0xe7, 0x25)),
+    ],
+    white_text_below: 3.72,
+};
+const VIRIDIS_PERCENTILE_SCALE: ColorScale = ColorScale {
+    stops: &[
+        (0.0, (0x44, 0x01, 0x54)),
+        (0.15, (0x44, 0x39, 0x83)),
+        (0.3, (0x31, 0x68, 0x8e)),
+        (0.5, (0x21, 0x91, 0x8c)),
+        (0.7, (0x35, 0xb7, 0x79)),
+        (0.85, (0x90, 0xd7, 0x43)),
+        (1.0, (0xfd, 0xe7, 0x25)),
+    ],
+    white_text_below: 0.42,
+};
+const VIRIDIS_ABV_SCALE: ColorScale = ColorScale {
     stops: &[
-        (0.0, (0x1a, 0x98, 0x50)),  // dark green
-        (4.5, (0x4c, 0xb0, 0x50)),  // green
-        (5.5, (0x84, 0xc9, 0x5a)),  // medium-light green
-        (6.5, (0xc4, 0xe0, 0x7c)),  // light green
-        (7.0, (0xd9, 0xe8, 0x4c)),  // yellowish-green (noticeable jump at 7%)
-        (7.5, (0xfd, 0xae, 0x61)),  // light orange
-        (9.5, (0xf4, 0x6d, 0x43)),  // orange
-        (10.0, (0xd7, 0x30, 0x27)), // red (10%+)
+        (0.0, (0x44, 0x01, 0x54)),
+        (4.5, (0x46, 0x32, 0x7e)),
+        (5.5, (0x36, 0x5c, 0x8d)),
+        (6.5, (0x27, 0x7f, 0x8e)),
+        (7.0, (0x1f, 0xa1, 0x87)),
+        (7.5, (0x4a, 0xc1, 0x6d)),
+        (9.5, (0xa0, 0xda, 0x39)),
+        (10.0, (0xfd, 0xe7, 0x25)),
     ],
+    white_text_below: 6.5,
 };
+const CIVIDIS_RATING_SCALE: ColorScale = ColorScale {
+    stops: &[
+        (3.0, (0x00, 0x22, 0x4e)),
+        (3.4, (0x2a, 0x3f, 0x6c)),
+        (3.6, (0x57, 0x5d, 0x6d)),
+        (3.8, (0x7c, 0x7b, 0x78)),
+        (3.95, (0xa5, 0x9c, 0x74)),
+        (4.15, (0xd3, 0xc1, 0x64)),
+        (4.4, (0xfe, 0xe8, 0x38)),
+    ],
+    white_text_below: 3.76,
+};
+const CIVIDIS_PERCENTILE_SCALE: ColorScale = ColorScale {
+    stops: &[
+        (0.0, (0x00, 0x22, 0x4e)),
+        (0.15, (0x2a, 0x3f, 0x6c)),
+        (0.3, (0x57, 0x5d, 0x6d)),
+        (0.5, (0x7c, 0x7b, 0x78)),
+        (0.7, (0xa5, 0x9c, 0x74)),
+        (0.85, (0xd3, 0xc1, 0x64)),
+        (1.0, (0xfe, 0xe8, 0x38)),
+    ],
+    white_text_below: 0.46,
+};
+const CIVIDIS_ABV_SCALE: ColorScale = ColorScale {
+    stops: &[
+        (0.0, (0x00, 0x22, 0x4e)),
+        (4.5, (0x23, 0x3e, 0x6c)),
+        (5.5, (0x4a, 0x56, 0x6c)),
+        (6.5, (0x6b, 0x6f, 0x72)),
+        (7.0, (0x8b, 0x8a, 0x76)),
+        (7.5, (0xae, 0xa5, 0x70)),
+        (9.5, (0xd3, 0xc1, 0x64)),
+        (10.0, (0xfe, 0xe8, 0x38)),
+    ],
+    white_text_below: 6.63,
+};
+
+/// Heatmap color set for the menu table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Palette {
+    /// Red-yellow-green ratings, green-to-red ABV.
+    #[default]
+    Standard,
+    /// Colorblind-safe viridis.
+    Viridis,
+    /// Colorblind-safe cividis (also legible with no color vision at all).
+    Cividis,
+}
+
+impl Palette {
+    /// Parse the `palette=` query value.
+    pub fn from_param(value: &str) -> Option<Palette> {
+        match value {
+            "standard" => Some(Palette::Standard),
+            "viridis" => Some(Palette::Viridis),
+            "cividis" => Some(Palette::Cividis),
+            _ => None,
+        }
+    }
+
+    /// (rating, percentile, ABV) scales. `accessible` swaps the standard
+    /// scales for their `AA_` versions; the colorblind-safe ones always pass.
+    fn scales(self, accessible: bool) -> [&'static ColorScale; 3] {
+        match self {
+            Palette::Standard if accessible => {
+                [&AA_RATING_SCALE, &AA_PERCENTILE_SCALE, &AA_ABV_SCALE]
+            }
+            Palette::Standard => [&RATING_SCALE, &PERCENTILE_SCALE, &ABV_SCALE],
+            Palette::Viridis => [
+                &VIRIDIS_RATING_SCALE,
+                &VIRIDIS_PERCENTILE_SCALE,
+                &VIRIDIS_ABV_SCALE,
+            ],
+            Palette::Cividis => [
+                &CIVIDIS_RATING_SCALE,
+                &CIVIDIS_PERCENTILE_SCALE,
+                &CIVIDIS_ABV_SCALE,
+            ],
+        }
+    }
+}
+
+/// How a page colors ABV and ratings. The menu, `/flight`, `/screen` and
+/// `/print` all take it from the same query keys, so a link keeps its colors
+/// from one page to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Heatmap {
+    /// `colors=global` / `style`.
+    pub rating_colors: RatingColors,
+    /// Heatmap colors (`palette=viridis` / `cividis`).
+    pub palette: Palette,
+    /// Accessible mode: tier glyphs beside ABV and ratings and spoken labels
+    /// on those cells (`glyphs=1`; on by default with a colorblind palette).
+    pub glyphs: bool,
+}
+
+impl Heatmap {
+    /// Heatmap settings from a query string; other keys are ignored.
+    pub fn from_query(query: &str) -> Heatmap {
+        let mut heatmap = Heatmap::default();
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            heatmap.apply(&key, &value);
+        }
+        heatmap
+    }
+
+    /// Apply one query pair; keys and values it doesn't know are ignored.
+    fn apply(&mut self, key: &str, value: &str) {
+        match key {
+            "colors" => {
+                if let Some(colors) = RatingColors::from_param(value) {
+                    self.rating_colors = colors;
+                }
+            }
+            "palette" => {
+                if let Some(palette) = Palette::from_param(value) {
+                    self.palette = palette;
+                    self.glyphs |= palette != Palette::Standard;
+                }
+            }
+            "glyphs" => self.glyphs = value == "1",
+            _ => {}
+        }
+    }
+
+    /// (rating, percentile, ABV) scales for this palette and mode.
+    fn scales(&self) -> [&'static ColorScale; 3] {
+        self.palette.scales(self.glyphs)
+    }
+
+    /// The tiers ratings are sorted into, and how a legend writes their cuts.
+    fn rating_tiers(&self) -> (&'static [Tier; 4], CutLabel) {
+        match self.rating_colors {
+            RatingColors::Global => (&RATING_TIERS, score_cut),
+            RatingColors::StylePercentile => (&PERCENTILE_TIERS, percentile::describe),
+        }
+    }
+
+    /// Color and tier for a rating of `adjusted` (the shrunk score) in
+    /// `category`: by the score itself, or by its rank within the category.
+    fn shade_rating(&self, adjusted: f64, category: &str, scores: &MenuScores) -> RatingShade {
+        let [rating_scale, percentile_scale, _] = self.scales();
+        let (value, scale, percentile) = match self.rating_colors {
+            RatingColors::Global => (adjusted, rating_scale, None),
+            RatingColors::StylePercentile => {
+                let pct = scores.percentile(adjusted, category);
+                (pct, percentile_scale, Some(pct))
+            }
+        };
+        let (tiers, _) = self.rating_tiers();
+        let index = tier_index(value, tiers);
+        RatingShade {
+            style: scale.style(value),
+            filled: tiers.len() - index,
+            name: tiers[index].1,
+            glyph: tiers[index].2,
+            percentile,
+        }
+    }
+}
+
+/// A rating's heatmap color and tier.
+struct RatingShade {
+    style: String,
+    /// Tier dots filled, out of four.
+    filled: usize,
+    name: &'static str,
+    /// Print glyph (empty for the lower tiers).
+    glyph: &'static str,
+    /// Rank within its category, when colored by that.
+    percentile: Option<f64>,
+}
+
+/// (threshold, name, print glyph): one step of a heatmap's four tiers.
+type Tier = (f64, &'static str, &'static str);
+
+/// Tiers behind the accessible mode's "●●●○" glyphs and spoken labels and the
+/// print menu's stars and triangles, highest first, so every legend agrees.
+/// Rating cuts are `RATING_SCALE`'s light-green, green and dark-green stops,
+/// and percentile cuts the same stops of `PERCENTILE_SCALE`; strength cuts are
+/// `ABV_SCALE`'s light-orange and red stops, plus 5%.
+const RATING_TIERS: [Tier; 4] = [
+    (4.4, "excellent", "&#9733;&#9733;&#9733;"),
+    (4.15, "very good", "&#9733;&#9733;"),
+    (3.95, "good", "&#9733;"),
+    (f64::NEG_INFINITY, "fair", ""),
+];
+const PERCENTILE_TIERS: [Tier; 4] = [
+    (1.0, "excellent", "&#9733;&#9733;&#9733;"),
+    (0.85, "very good", "&#9733;&#9733;"),
+    (0.7, "good", "&#9733;"),
+    (f64::NEG_INFINITY, "fair", ""),
+];
+const ABV_TIERS: [Tier; 4] = [
+    (10.0, "very strong", "&#9650;&#9650;"),
+    (7.5, "strong", "&#9650;"),
+    (5.0, "medium", ""),
+    (f64::NEG_INFINITY, "light", ""),
+];
+
+fn tier_index(value: f64, tiers: &[Tier; 4]) -> usize {
+    tiers
+        .iter()
+        .position(|(threshold, _, _)| value >= *threshold)
+        .unwrap_or(tiers.len() - 1)
+}
+
+/// (filled dots out of four, tier name) for `value`.
+fn tier(value: f64, tiers: &[Tier; 4]) -> (usize, &'static str) {
+    let index = tier_index(value, tiers);
+    (tiers.len() - index, tiers[index].1)
+}
+
+/// The print glyph for `value` (empty for the lower tiers).
+fn tier_glyph(value: f64, tiers: &[Tier; 4]) -> &'static str {
+    tiers[tier_index(value, tiers)].2
+}
+
+/// How a legend writes a cut point: "7.5%+", "3.95+", "top 30%".
+type CutLabel = fn(f64) -> String;
+
+fn abv_cut(threshold: f64) -> String {
+    format!("{threshold}%+")
+}
+
+fn score_cut(threshold: f64) -> String {
+    format!("{threshold}+")
+}
+
+/// Tier names, lowest first, with their cut points: "light, medium (5%+), ...".
+fn tier_names(tiers: &[Tier; 4], cut: CutLabel) -> String {
+    let names: Vec<String> = tiers
+        .iter()
+        .rev()
+        .map(|(threshold, name, _)| {
+            if threshold.is_finite() {
+                format!("{name} ({})", cut(*threshold))
+            } else {
+                name.to_string()
+            }
+        })
+        .collect();
+    names.join(", ")
+}
+
+/// Print glyphs, lowest first, with their cut points: "&#9650; 7.5%+ ...".
+fn tier_glyphs(tiers: &[Tier; 4], cut: CutLabel) -> String {
+    let glyphs: Vec<String> = tiers
+        .iter()
+        .rev()
+        .filter(|(_, _, glyph)| !glyph.is_empty())
+        .map(|(threshold, _, glyph)| format!("{glyph} {}", cut(*threshold)))
+        .collect();
+    glyphs.join(" &middot; ")
+}
+
+/// "●●●○", hidden from screen readers (the cell's label says it in words).
+fn tier_dots(filled: usize) -> String {
+    format!(
+        " <span class=\"tier\" aria-hidden=\"true\">{}{}</span>",
+        "&#9679;".repeat(filled),
+        "&#9675;".repeat(4 - filled)
+    )
+}
+
+/// Check-ins' worth of weight the category mean gets when shrinking a score, so
+/// a 4.4 from a dozen check-ins is colored closer to its style's average than a
+/// 4.4 from fifty thousand.
+const RATING_PRIOR_WEIGHT: f64 = 50.0;
+/// Prior for a category with no scored beers to average (≈ Untappd overall).
+const DEFAULT_RATING_MEAN: f64 = 3.75;
+
+/// Bayesian-shrunk score: `count` observations at `score` blended with
+/// `RATING_PRIOR_WEIGHT` pseudo-observations at `mean`. An unknown (0) count is
+/// left unshrunk rather than guessed at.
+fn shrunk_score(score: f64, count: u64, mean: f64) -> f64 {
+    if count == 0 {
+        return score;
+    }
+    let n = count as f64;
+    (RATING_PRIOR_WEIGHT * mean + n * score) / (RATING_PRIOR_WEIGHT + n)
+}
+
+/// Mean raw score per category label, over the beers that have one.
+fn category_means(beers: &[RatedBeer]) -> HashMap<&str, f64> {
+    let mut sums: HashMap<&str, (f64, u32)> = HashMap::new();
+    for b in beers {
+        if let Some(score) = b.rating.as_ref().and_then(|r| r.score()) {
+            let sum = sums.entry(category_label(&b.entry.category)).or_default();
+            sum.0 += score;
+            sum.1 += 1;
+        }
+    }
+    sums.into_iter()
+        .map(|(category, (sum, n))| (category, sum / f64::from(n)))
+        .collect()
+}
+
+/// Menu-wide rating statistics: category means to shrink scores toward, and
+/// each category's shrunk scores to rank within. Built from the whole menu, so
+/// a page showing part of it (a flight, one kiosk page, the menu with tried
+/// beers hidden) colors every beer as the full menu would.
+struct MenuScores<'a> {
+    means: HashMap<&'a str, f64>,
+    peers: HashMap<&'a str, Vec<f64>>,
+}
+
+impl<'a> MenuScores<'a> {
+    fn new(beers: &'a [RatedBeer]) -> MenuScores<'a> {
+        let mut scores = MenuScores {
+            means: category_means(beers),
+            peers: HashMap::new(),
+        };
+        for b in beers {
+            if let Some(adjusted) = scores.shrunk(b) {
+                scores
+                    .peers
+                    .entry(category_label(&b.entry.category))
+                    .or_default()
+                    .push(adjusted);
+            }
+        }
+        scores
+    }
+
+    /// `b`'s shrunk score (`None` if unrated).
+    fn shrunk(&self, b: &RatedBeer) -> Option<f64> {
+        let r = b.rating.as_ref()?;
+        let mean = self
+            .means
+            .get(category_label(&b.entry.category))
+            .copied()
+            .unwrap_or(DEFAULT_RATING_MEAN);
+        Some(shrunk_score(r.score()?, r.rating_count, mean))
+    }
+
+    /// Where a shrunk score of `adjusted` ranks among `category`'s.
+    fn percentile(&self, adjusted: f64, category: &str) -> f64 {
+        let peers = self.peers.get(category).map_or(&[][..], Vec::as_slice);
+        percentile::style_percentile(adjusted, category, peers)
+    }
+}
+
+/// ABV as shown: at least one decimal ("5.0", "6.66").
+fn format_abv(abv: f64) -> String {
+    if abv.fract() == 0.0 {
+        format!("{abv:.1}")
+    } else {
+        format!("{abv}")
+    }
+}
+
+fn display_or<'a>(display: &'a str, fallback: &'a str) -> &'a str {
+    if display.is_empty() {
+        fallback
+    } else {
+        display
+    }
+}
+
+/// Tooltip explaining a rating's color: the check-in count, plus the adjusted
+/// score when shrinkage visibly moved it.
+fn confidence_title(score: f64, count: u64, shrunk: f64) -> String {
+    let checkins = match count {
+        0 => "unknown check-ins".to_string(),
+        1 => "1 check-in".to_string(),
+        n => format!("{} check-ins", format_count(n)),
+    };
+    if (score - shrunk).abs() >= 0.01 {
+        format!(
+            "{checkins}; colored as {shrunk:.2} (few check-ins, pulled toward the style average)"
+        )
+    } else {
+        checkins
+    }
+}
+
+/// Beer name with the Untappd label thumbnail in front and badges after (award
+/// winner, no longer in production).
+fn name_cell(b: &RatedBeer) -> String {
+    let Some(details) = b.rating.as_ref().map(|r| &r.details) else {
+        return b.entry.name.clone();
+    };
+    let mut cell = String::new();
+    if let Some(src) = details.thumbnail() {
+        cell.push_str(&format!(
+            "<img class=\"label-thumb\" src=\"{}\" alt=\"\" loading=\"lazy\">",
+            escape(src)
+        ));
+    }
+    cell.push_str(&b.entry.name);
+    if details.has_community_award {
+        cell.push_str(
+            " <span class=\"badge badge-award\" title=\"Untappd community award\">&#127942;</span>",
+        );
+    }
+    if details.in_production == Some(false) {
+        cell.push_str(
+            " <span class=\"badge badge-retired\" title=\"No longer in production per Untappd\">retired</span>",
+        );
+    }
+    cell
+}
+
+/// Untappd text made safe for HTML text or a double-quoted attribute.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+/// TapHunter's style, falling back to Untappd's (marked as such) when the menu
+/// leaves it blank.
+fn style_cell(b: &RatedBeer) -> String {
+    if !b.entry.style.is_empty() {
+        return format!("<td>{}</td>", b.entry.style);
+    }
+    match b.rating.as_ref().and_then(|r| r.details.style.as_deref()) {
+        Some(style) => format!(
+            "<td class=\"untappd-style\" title=\"Style from Untappd\">{}</td>",
+            escape(style)
+        ),
+        None => "<td></td>".to_string(),
+    }
+}
+
+/// Toggle button for the tried column: posts to `/tried/<bid>`, which flips it
+/// and redirects back here. Beers without an Untappd id get an empty cell.
+fn tried_cell(b: &RatedBeer, tried: &TriedList) -> String {
+    let Some(bid) = b.rating.as_ref().and_then(|r| r.bid()) else {
+        return "<td></td>".to_string();
+    };
+    let (glyph, title) = if tried.contains(bid) {
+        ("&#9745;", "Tried it (click to unmark)")
+    } else {
+        ("&#9744;", "Mark as tried")
+    };
+    format!(
+        "<td class=\"center\"><form method=\"post\" action=\"/tried/{bid}\">\
+         <button class=\"tried-toggle\" title=\"{title}\">{glyph}</button></form></td>"
+    )
+}
+
+/// The "For you" list: tap, beer, and the one-line reason it was picked.
+fn picks_section(picks: &[Pick]) -> String {
+    let mut html = String::from("<section class=\"picks\">\n<h2>For you</h2>\n<ol>\n");
+    for p in picks {
+        html.push_str(&format!(
+            "<li><span class=\"pick-tap\">#{}</span>{} {} <span class=\"pick-why\">&mdash; {}</span></li>\n",
+            p.tap_number, p.brewery, p.name, p.explanation
+        ));
+    }
+    html.push_str("</ol>\n</section>\n");
+    html
+}
+
+/// Tried count plus a show/hide link that keeps the rest of the query.
+fn tried_legend(beers: &[RatedBeer], tried: &TriedList, options: &RenderOptions) -> String {
+    let on_menu = beers
+        .iter()
+        .filter(|b| {
+            b.rating
+                .as_ref()
+                .and_then(|r| r.bid())
+                .is_some_and(|bid| tried.contains(bid))
+        })
+        .count();
+    let link = if options.hide_tried {
+        format!(
+            "<a href=\"{}\">Show tried</a>",
+            options.query_with("hide", None)
+        )
+    } else {
+        format!(
+            "<a href=\"{}\">Hide tried</a>",
+            options.query_with("hide", Some("tried"))
+        )
+    };
+    format!(
+        "<p class=\"legend\">You've tried {on_menu} of the {} beers on tap. {link}</p>\n",
+        beers.len()
+    )
+}
 
-/// Extract the numeric score from a rendered rating cell (our own
-/// `<a href=...>3.62</a>` format). Returns `None` for "N/A" / unrated cells.
-fn rating_value(cell: &str) -> Option<f64> {
-    let inner = cell.strip_suffix("</a>")?;
-    inner.rsplit('>').next()?.trim().parse::<f64>().ok()
+/// The button that moves this browser to the list a `?user=` link named
+/// (`user` is a validated token, safe to echo).
+fn switch_offer(user: &str) -> String {
+    format!(
+        "<form class=\"legend\" method=\"post\" action=\"/tried/switch\">This link is for \
+         another tried list; this browser is still on its own. \
+         <input type=\"hidden\" name=\"user\" value=\"{user}\">\
+         <button>Switch to that list</button></form>\n"
+    )
 }
 
 fn category_label(category: &str) -> &str {
@@ -167,26 +973,144 @@ fn category_label(category: &str) -> &str {
     }
 }
 
+/// Share of failed rating lookups at which the page warns that ratings are
+/// missing, rather than leaving a sea of "retrying" cells unexplained.
+const LOOKUP_BANNER_FAILURE_RATE: f64 = 0.2;
+
+/// Banner for a menu where many rating lookups failed, or `None`.
+fn lookup_banner(beers: &[RatedBeer]) -> Option<String> {
+    let stats = LookupStats::tally(beers);
+    if stats.failure_rate() < LOOKUP_BANNER_FAILURE_RATE {
+        return None;
+    }
+    let reason = stats
+        .main_failure()
+        .map(|o| format!(" ({})", o.label()))
+        .unwrap_or_default();
+    Some(format!(
+        "<p class=\"lookup-banner\" role=\"status\">Couldn't look up {} of {} ratings \
+         on Untappd{reason}; they'll be retried on the next load.</p>\n",
+        stats.failed(),
+        stats.total()
+    ))
+}
+
+/// Rating cell for a beer with no rating: "N/A" when Untappd has none, a
+/// "retrying" note when we couldn't ask, "pending" when we haven't yet.
+fn missing_rating_cell(outcome: LookupOutcome) -> String {
+    if outcome == LookupOutcome::Pending {
+        "<td class=\"rating-pending center\" title=\"Not looked up yet on this load; \
+         reload in a moment\">pending</td>"
+            .to_string()
+    } else if outcome.is_failure() {
+        format!(
+            "<td class=\"rating-failed center\" title=\"Untappd lookup failed ({}); \
+             retried on the next load\">lookup failed, retrying</td>",
+            outcome.label()
+        )
+    } else {
+        "<td class=\"rating-na center\">N/A</td>".to_string()
+    }
+}
+
 /// Render the (already sorted) rated beers into an HTML table. Consecutive rows
 /// sharing a category are merged into a single rowspanned category cell.
 pub fn render(beers: &[RatedBeer]) -> String {
+    render_with(beers, &RenderOptions::default())
+}
+
+/// `render` with explicit presentation options.
+pub fn render_with(beers: &[RatedBeer], options: &RenderOptions) -> String {
     let mut html = String::from(STYLE);
+    if let Some(since) = options.stale_since {
+        html.push_str(&format!(
+            "<p class=\"stale-banner\" role=\"status\">The live tap list is unavailable right now; \
+             this is the menu as of {}. Some of these may have kicked.</p>\n",
+            since.format("%a %b %-d, %-I:%M %p")
+        ));
+    }
+    if let Some(banner) = lookup_banner(beers) {
+        html.push_str(&banner);
+    }
+    let heatmap = &options.heatmap;
+    if heatmap.rating_colors == RatingColors::StylePercentile {
+        html.push_str(
+            "<p class=\"legend\">Ratings are colored by how they rank within their style.</p>\n",
+        );
+    }
 
-    html.push_str("<table>\n<thead>\n<tr>");
+    if !options.picks.is_empty() {
+        html.push_str(&picks_section(&options.picks));
+    }
+    if let Some(tried) = &options.tried {
+        html.push_str(&tried_legend(beers, tried, options));
+    }
+    if let Some(user) = &options.offered_user {
+        html.push_str(&switch_offer(user));
+    }
+
+    if heatmap.glyphs {
+        let (tiers, cut) = heatmap.rating_tiers();
+        let ratings = match heatmap.rating_colors {
+            RatingColors::Global => format!("rating {}", tier_names(tiers, cut)),
+            RatingColors::StylePercentile => {
+                format!("rating within its style {}", tier_names(tiers, cut))
+            }
+        };
+        html.push_str(&format!(
+            "<p class=\"legend\">&#9679; to &#9679;&#9679;&#9679;&#9679;: ABV {}; {ratings}.</p>\n",
+            tier_names(&ABV_TIERS, abv_cut)
+        ));
+    }
+
+    // Accessible mode also gives screen readers a caption and scoped headers.
+    let scope = if heatmap.glyphs {
+        html.push_str(
+            "<table class=\"accessible\">\n<caption class=\"visually-hidden\">Beers on tap by \
+             category, with ABV, keg age and Untappd rating</caption>\n",
+        );
+        " scope=\"col\""
+    } else {
+        html.push_str("<table>\n");
+        ""
+    };
+    html.push_str("<thead>\n<tr>");
     for header in HEADERS {
-        html.push_str(&format!("<th>{header}</th>"));
+        html.push_str(&format!("<th{scope}>{header}</th>"));
+    }
+    if options.tried.is_some() {
+        html.push_str(&format!("<th{scope}>tried</th>"));
     }
     html.push_str("</tr>\n</thead>\n<tbody>\n");
 
+    let today = options
+        .today
+        .unwrap_or_else(|| VenueClock::system(clock::PACIFIC).today());
+    let scores = MenuScores::new(beers);
+
+    // Statistics cover the whole menu; hiding tried beers only drops
+    // their rows.
+    let is_tried = |b: &RatedBeer| {
+        let bid = b.rating.as_ref().and_then(|r| r.bid());
+        options
+            .tried
+            .as_ref()
+            .zip(bid)
+            .is_some_and(|(tried, bid)| tried.contains(bid))
+    };
+    let visible: Vec<usize> = (0..beers.len())
+        .filter(|&i| !(options.hide_tried && is_tried(&beers[i])))
+        .collect();
+
     let mut row = 0;
     let mut category_number = 0;
-    while row < beers.len() {
-        let category = category_label(&beers[row].entry.category);
+    while row < visible.len() {
+        let category = category_label(&beers[visible[row]].entry.category);
 
         // How many consecutive rows share this category.
         let mut count = 1;
-        while row + count < beers.len()
-            && category_label(&beers[row + count].entry.category) == category
+        while row + count < visible.len()
+            && category_label(&beers[visible[row + count]].entry.category) == category
         {
             count += 1;
         }
@@ -202,36 +1126,62 @@ pub fn render(beers: &[RatedBeer]) -> String {
             category
         };
 
-        for offset in 0..count {
-            let b = &beers[row + offset];
+        for &index in &visible[row..row + count] {
+            let b = &beers[index];
             let e = &b.entry;
-            html.push_str("<tr>");
-            if offset == 0 {
+            if is_tried(b) {
+                html.push_str("<tr class=\"tried\">");
+            } else {
+                html.push_str("<tr>");
+            }
+            if index == visible[row] {
+                let (tag, scope) = if heatmap.glyphs {
+                    ("th", " scope=\"row\"")
+                } else {
+                    ("td", "")
+                };
                 html.push_str(&format!(
-                    "<td class=\"{category_class}\" rowspan=\"{count}\">{display}</td>"
+                    "<{tag}{scope} class=\"{category_class}\" rowspan=\"{count}\">{display}</{tag}>"
                 ));
             }
             html.push_str(&format!("<td class=\"tap-cell\">{}</td>", e.tap_number));
             html.push_str(&format!("<td>{}</td>", e.brewery));
-            html.push_str(&format!("<td>{}</td>", e.name));
-            html.push_str(&format!(
-                "<td class=\"center\" style=\"{}\">{}</td>",
-                ABV_SCALE.style(e.abv_value()),
-                e.abv
-            ));
+            html.push_str(&format!("<td>{}</td>", name_cell(b)));
+            html.push_str(&abv_cell(e.abv, heatmap));
             html.push_str(&format!("<td class=\"center\">{}</td>", e.origin));
-            html.push_str(&format!("<td>{}</td>", e.style));
-            html.push_str(&format!("<td class=\"center\">{}</td>", e.days_old));
-            match rating_value(&b.rating_html) {
-                Some(score) => html.push_str(&format!(
-                    "<td class=\"center\" style=\"{}\">{}</td>",
-                    RATING_SCALE.style(score),
-                    b.rating_html
-                )),
-                None => html.push_str(&format!(
-                    "<td class=\"rating-na center\">{}</td>",
-                    b.rating_html
-                )),
+            html.push_str(&style_cell(b));
+            match e.days_old(today) {
+                Some(days) => html.push_str(&format!("<td class=\"center\">{days}</td>")),
+                None => html.push_str("<td class=\"unknown center\">?</td>"),
+            }
+            match &b.rating {
+                Some(r) => match (r.score(), scores.shrunk(b)) {
+                    (Some(score), Some(adjusted)) => {
+                        let mut title = confidence_title(score, r.rating_count, adjusted);
+                        if let Some(at) = r.fetched_at {
+                            let timezone = options.timezone.unwrap_or(clock::PACIFIC);
+                            title.push_str(&format!(
+                                "; rated as of {}",
+                                timezone.local_date(at).format("%b %-d")
+                            ));
+                        }
+                        // The color, and the tier that says it without color.
+                        let shade = heatmap.shade_rating(adjusted, category, &scores);
+                        let spoken = spoken_tier(&shade, display);
+                        if shade.percentile.is_some() {
+                            title.push_str(&format!("; {spoken}"));
+                        }
+                        html.push_str(&rating_cell(r, score, &shade, &spoken, &title, heatmap));
+                    }
+                    _ => html.push_str(&format!(
+                        "<td class=\"rating-na center\">{}</td>",
+                        r.to_cell()
+                    )),
+                },
+                None => html.push_str(&missing_rating_cell(b.outcome)),
+            }
+            if let Some(tried) = &options.tried {
+                html.push_str(&tried_cell(b, tried));
             }
             html.push_str("</tr>\n");
         }
@@ -245,24 +1195,689 @@ pub fn render(beers: &[RatedBeer]) -> String {
     html
 }
 
+/// An ABV table cell: heatmap color and, in accessible mode, tier dots and a
+/// spoken label.
+fn abv_cell(abv: Option<f64>, heatmap: &Heatmap) -> String {
+    let [_, _, abv_scale] = heatmap.scales();
+    match abv {
+        Some(abv) if heatmap.glyphs => {
+            let (filled, name) = tier(abv, &ABV_TIERS);
+            format!(
+                "<td class=\"center\" style=\"{}\" aria-label=\"{}% ABV, {name}\">{}{}</td>",
+                abv_scale.style(abv),
+                format_abv(abv),
+                format_abv(abv),
+                tier_dots(filled)
+            )
+        }
+        Some(abv) => format!(
+            "<td class=\"center\" style=\"{}\">{}</td>",
+            abv_scale.style(abv),
+            format_abv(abv)
+        ),
+        None => "<td class=\"unknown center\">?</td>".to_string(),
+    }
+}
+
+/// A rating's tier in words: "very good", or by style "top 10% for IPA"
+/// (`category` as displayed; empty when uncategorized).
+fn spoken_tier(shade: &RatingShade, category: &str) -> String {
+    match shade.percentile {
+        Some(pct) => format!(
+            "{} for {}",
+            percentile::describe(pct),
+            display_or(category, "its style")
+        ),
+        None => shade.name.to_string(),
+    }
+}
+
+/// A scored beer's rating cell, colored by `shade`; accessible mode adds its
+/// tier dots and a spoken label.
+fn rating_cell(
+    r: &RatingResult,
+    score: f64,
+    shade: &RatingShade,
+    spoken: &str,
+    title: &str,
+    heatmap: &Heatmap,
+) -> String {
+    let style = &shade.style;
+    if heatmap.glyphs {
+        format!(
+            "<td class=\"center\" style=\"{style}\" title=\"{title}\" \
+             aria-label=\"Rated {score:.2}, {spoken}\">{}{}</td>",
+            r.to_cell(),
+            tier_dots(shade.filled)
+        )
+    } else {
+        format!(
+            "<td class=\"center\" style=\"{style}\" title=\"{title}\">{}</td>",
+            r.to_cell()
+        )
+    }
+}
+
+/// What to tell a visitor about `error`: a headline and a line of advice.
+/// Upstream details stay in the logs.
+pub fn error_message(error: &AppError) -> (&'static str, String) {
+    match error {
+        AppError::Network(_) => (
+            "We couldn't reach the tap list.",
+            "TapHunter or Untappd didn't answer. That's usually brief; try again in a minute.".to_string(),
+        ),
+        AppError::Blocked(_) => (
+            "The tap list is taking a break.",
+            format!(
+                "An upstream service is turning requests away for now. Try again in about {} minutes.",
+                error.retry_after().unwrap_or(60).div_ceil(60)
+            ),
+        ),
+        AppError::Parse(_) => (
+            "The tap list came back in a form we didn't understand.",
+            "The menu provider may have changed its format. If this keeps happening, pass the reference below to whoever runs this page.".to_string(),
+        ),
+        AppError::Client(_) => (
+            "That request didn't look right.",
+            "Check the link and try again.".to_string(),
+        ),
+        AppError::NotFound => ("Nothing here.", "That page doesn't exist.".to_string()),
+        AppError::Internal(_) => (
+            "Something went wrong on our side.",
+            "Try again shortly.".to_string(),
+        ),
+    }
+}
+
+/// Friendly page for a request that failed with `error` (served with
+/// `error.status()`), worded by `error_message`. `reference` (the request's
+/// id, logged alongside the error) ties a report back to the logs.
+pub fn render_error(error: &AppError, reference: &str) -> String {
+    let (headline, advice) = error_message(error);
+    format!(
+        "{STYLE}<div class=\"error-page\" role=\"alert\">\n<h1>{headline}</h1>\n<p>{advice}</p>\n\
+         <p class=\"legend\">Error {} &middot; reference {reference}</p>\n</div>\n</body>",
+        error.status()
+    )
+}
+
+/// The `/flight` page: the chosen taps lightest first, with the constraints
+/// that shaped them and the totals. `None` means nothing on tap fits. Ratings
+/// are colored against `menu`, the full tap list the flight was drawn from.
+pub fn render_flight(
+    flight: Option<&Flight>,
+    request: &FlightRequest,
+    menu: &[RatedBeer],
+    heatmap: &Heatmap,
+) -> String {
+    let mut html = String::from(STYLE);
+    let plural = if request.size == 1 { "" } else { "s" };
+    let mut rules = vec![format!("{} tap{plural}", request.size)];
+    if let Some(drinks) = request.max_drinks {
+        rules.push(format!("at most {drinks} standard drinks"));
+    }
+    if let Some(price) = request.max_price {
+        rules.push(format!("at most ${price:.2}"));
+    }
+    if let Some(min) = request.min_rating {
+        rules.push(format!("rated {min} or better"));
+    }
+    if request.distinct_styles {
+        rules.push("one per style".to_string());
+    }
+    if request.distinct_breweries {
+        rules.push("one per brewery".to_string());
+    }
+    html.push_str(&format!(
+        "<p class=\"legend\">Flight: {}. Lightest first.</p>\n",
+        rules.join(", ")
+    ));
+
+    let Some(flight) = flight else {
+        html.push_str(
+            "<p class=\"legend\">Nothing on tap fits all of that; try loosening a limit.</p>\n",
+        );
+        html.push_str("</body>");
+        return html;
+    };
+
+    html.push_str("<table>\n<thead>\n<tr>");
+    for header in [
+        "tap", "brewery", "name", "style", "abv", "pour", "price", "rating",
+    ] {
+        html.push_str(&format!("<th>{header}</th>"));
+    }
+    html.push_str("</tr>\n</thead>\n<tbody>\n");
+    let scores = MenuScores::new(menu);
+    for p in &flight.pours {
+        let e = &p.beer.entry;
+        html.push_str("<tr>");
+        html.push_str(&format!("<td class=\"tap-cell\">{}</td>", e.tap_number));
+        html.push_str(&format!("<td>{}</td>", e.brewery));
+        html.push_str(&format!("<td>{}</td>", name_cell(p.beer)));
+        html.push_str(&format!("<td>{}</td>", p.style));
+        html.push_str(&abv_cell(e.abv, heatmap));
+        match p.pour {
+            Some(pour) => html.push_str(&format!("<td class=\"center\">{}</td>", pour.size)),
+            None => html.push_str("<td class=\"unknown center\">?</td>"),
+        }
+        match p.price() {
+            Some(price) => html.push_str(&format!("<td class=\"center\">${price:.2}</td>")),
+            None => html.push_str("<td class=\"unknown center\">?</td>"),
+        }
+        match p.beer.rating.as_ref() {
+            Some(r) => match (r.score(), scores.shrunk(p.beer)) {
+                (Some(score), Some(adjusted)) => {
+                    let category = category_label(&e.category);
+                    let shade = heatmap.shade_rating(adjusted, category, &scores);
+                    let spoken = spoken_tier(&shade, category);
+                    let mut title = confidence_title(score, r.rating_count, adjusted);
+                    if shade.percentile.is_some() {
+                        title.push_str(&format!("; {spoken}"));
+                    }
+                    html.push_str(&rating_cell(r, score, &shade, &spoken, &title, heatmap));
+                }
+                _ => html.push_str(&format!(
+                    "<td class=\"rating-na center\">{}</td>",
+                    r.to_cell()
+                )),
+            },
+            None => html.push_str(&missing_rating_cell(p.beer.outcome)),
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    let price = flight.total_price().map_or_else(
+        || "price not listed for every pour".to_string(),
+        |p| format!("${p:.2}"),
+    );
+    html.push_str(&format!(
+        "<p class=\"flight-total\">Total: {:.1} standard drinks, {price}.</p>\n",
+        flight.total_drinks()
+    ));
+    html.push_str("</body>");
+    html
+}
+
+/// Full-viewport stylesheet for `/screen`: dark, large type scaled to the
+/// viewport width so it reads across a taproom.
+const SCREEN_STYLE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <meta name="viewport" content="width=device-width, initial-scale=1">
+  <style>
+    html, body {
+        margin: 0;
+        height: 100%;
+        overflow: hidden;
+        background-color: #111;
+        color: #f5f5f5;
+        font-family: Arial, sans-serif;
+    }
+    .screen-page {
+        display: grid;
+        grid-template-columns: repeat(3, 1fr);
+        grid-auto-flow: column;
+        gap: 0.6vh 1.5vw;
+        height: 100vh;
+        box-sizing: border-box;
+        padding: 1.5vh 1.5vw;
+    }
+    .screen-page[hidden] {
+        display: none;
+    }
+    .screen-tap {
+        display: flex;
+        align-items: center;
+        gap: 0.8vw;
+        min-height: 0;
+        border-bottom: 1px solid #333;
+    }
+    .screen-num {
+        flex: 0 0 4vw;
+        text-align: right;
+        font-size: 2.6vw;
+        font-weight: bold;
+        color: #4dabf7;
+    }
+    .screen-beer {
+        flex: 1;
+        min-width: 0;
+    }
+    .screen-name {
+        font-size: 1.4vw;
+        font-weight: bold;
+        white-space: nowrap;
+        overflow: hidden;
+        text-overflow: ellipsis;
+    }
+    .screen-brewery {
+        font-size: 1vw;
+        color: #aaa;
+        white-space: nowrap;
+        overflow: hidden;
+        text-overflow: ellipsis;
+    }
+    .screen-chip {
+        flex: 0 0 auto;
+        min-width: 3.6vw;
+        padding: 0.3vh 0.4vw;
+        border-radius: 0.4vw;
+        text-align: center;
+        font-size: 1.2vw;
+        font-weight: bold;
+    }
+    .screen-chip.unknown {
+        background-color: #444;
+        color: #999;
+    }
+  </style>
+</head>
+<body>
+"#;
+
+/// Default taps per `/screen` page (three columns of eight).
+const SCREEN_PER_PAGE: usize = 24;
+/// Default seconds each page stays up before rotating.
+const SCREEN_ROTATE_SECONDS: u32 = 15;
+/// Default seconds between ETag polls for a changed menu.
+const SCREEN_POLL_SECONDS: u32 = 60;
+
+/// Knobs for the `/screen` kiosk display.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenOptions {
+    /// Taps per page; more pages rotate in turn.
+    pub per_page: usize,
+    pub rotate_seconds: u32,
+    pub poll_seconds: u32,
+    pub heatmap: Heatmap,
+}
+
+impl Default for ScreenOptions {
+    fn default() -> ScreenOptions {
+        ScreenOptions {
+            per_page: SCREEN_PER_PAGE,
+            rotate_seconds: SCREEN_ROTATE_SECONDS,
+            poll_seconds: SCREEN_POLL_SECONDS,
+            heatmap: Heatmap::default(),
+        }
+    }
+}
+
+impl ScreenOptions {
+    /// From a query string (`per_page=30&rotate=20&poll=120`, plus the
+    /// `Heatmap` keys), clamped to values a TV can show and the worker can
+    /// afford. Malformed values are ignored.
+    pub fn from_query(query: &str) -> ScreenOptions {
+        let mut options = ScreenOptions {
+            heatmap: Heatmap::from_query(query),
+            ..ScreenOptions::default()
+        };
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            let Ok(n) = value.trim().parse::<u32>() else {
+                continue;
+            };
+            match key.as_ref() {
+                "per_page" => options.per_page = n.clamp(6, 60) as usize,
+                "rotate" => options.rotate_seconds = n.clamp(5, 600),
+                "poll" => options.poll_seconds = n.clamp(10, 3600),
+                _ => {}
+            }
+        }
+        options
+    }
+}
+
+/// A rendered `/screen` page and the ETag of what it shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenPage {
+    pub html: String,
+    /// Quoted strong validator (`"1f3a…"`), for the `ETag` header.
+    pub etag: String,
+}
+
+/// The kiosk display: every tap (in the order given) as a row of tap number,
+/// beer, and ABV and rating heatmap chips, split into viewport-sized pages that
+/// rotate client-side. The page polls itself with `If-None-Match` and reloads
+/// when the ETag changes, so a TV can run it unattended.
+pub fn render_screen(beers: &[RatedBeer], options: &ScreenOptions) -> ScreenPage {
+    let mut html = String::from(SCREEN_STYLE);
+    let rows = options.per_page.div_ceil(3);
+    let scores = MenuScores::new(beers);
+    let pages = beers.chunks(options.per_page.max(1));
+    for (number, page) in pages.enumerate() {
+        let hidden = if number == 0 { "" } else { " hidden" };
+        html.push_str(&format!(
+            "<section class=\"screen-page\" style=\"grid-template-rows:repeat({rows},1fr)\"{hidden}>\n"
+        ));
+        for b in page {
+            html.push_str(&screen_tap(b, &scores, &options.heatmap));
+        }
+        html.push_str("</section>\n");
+    }
+
+    // The validator covers everything visible, so an unchanged menu polls as
+    // a cheap 304 and any change (a new keg, a rating update) reloads.
+    let mut hasher = DefaultHasher::new();
+    html.hash(&mut hasher);
+    let etag = format!("\"{:016x}\"", hasher.finish());
+
+    html.push_str(&format!(
+        "<script>\n\
+         (function () {{\n\
+         var pages = document.querySelectorAll('.screen-page'), shown = 0;\n\
+         if (pages.length > 1) setInterval(function () {{\n\
+         pages[shown].hidden = true;\n\
+         shown = (shown + 1) % pages.length;\n\
+         pages[shown].hidden = false;\n\
+         }}, {rotate});\n\
+         var etag = '{etag}';\n\
+         setInterval(function () {{\n\
+         fetch(location.href, {{ headers: {{ 'If-None-Match': etag }}, cache: 'no-store' }})\n\
+         .then(function (r) {{ if (r.status === 200 && r.headers.get('ETag') !== etag) location.reload(); }})\n\
+         .catch(function () {{}});\n\
+         }}, {poll});\n\
+         }})();\n\
+         </script>\n</body>\n</html>",
+        rotate = u64::from(options.rotate_seconds) * 1000,
+        poll = u64::from(options.poll_seconds) * 1000,
+    ));
+    ScreenPage { html, etag }
+}
+
+fn screen_tap(b: &RatedBeer, scores: &MenuScores, heatmap: &Heatmap) -> String {
+    let [_, _, abv_scale] = heatmap.scales();
+    let dots = |filled| {
+        if heatmap.glyphs {
+            tier_dots(filled)
+        } else {
+            String::new()
+        }
+    };
+    let e = &b.entry;
+    let style = if e.style.is_empty() {
+        b.rating
+            .as_ref()
+            .and_then(|r| r.details.style.as_deref())
+            .map(escape)
+            .unwrap_or_default()
+    } else {
+        e.style.clone()
+    };
+    let byline = if style.is_empty() {
+        e.brewery.clone()
+    } else {
+        format!("{} &middot; {style}", e.brewery)
+    };
+    let abv = match e.abv {
+        Some(abv) => format!(
+            "<span class=\"screen-chip\" style=\"{}\">{}%{}</span>",
+            abv_scale.style(abv),
+            format_abv(abv),
+            dots(tier(abv, &ABV_TIERS).0)
+        ),
+        None => "<span class=\"screen-chip unknown\">?</span>".to_string(),
+    };
+    let score = b.rating.as_ref().and_then(|r| r.score());
+    let rating = match score.zip(scores.shrunk(b)) {
+        Some((score, adjusted)) => {
+            let shade = heatmap.shade_rating(adjusted, category_label(&e.category), scores);
+            format!(
+                "<span class=\"screen-chip\" style=\"{}\">{score:.2}{}</span>",
+                shade.style,
+                dots(shade.filled)
+            )
+        }
+        None if b.outcome.is_failure() => {
+            "<span class=\"screen-chip unknown\">retrying</span>".to_string()
+        }
+        None if b.outcome == LookupOutcome::Pending => {
+            "<span class=\"screen-chip unknown\">pending</span>".to_string()
+        }
+        None => "<span class=\"screen-chip unknown\">N/A</span>".to_string(),
+    };
+    format!(
+        "<div class=\"screen-tap\"><span class=\"screen-num\">{}</span>\
+         <div class=\"screen-beer\"><div class=\"screen-name\">{}</div>\
+         <div class=\"screen-brewery\">{byline}</div></div>{abv}{rating}</div>\n",
+        e.tap_number, e.name
+    )
+}
+
+/// Letter-paper stylesheet for `/print`: three newspaper columns, category
+/// headers that stay with their first beer, and heatmap colors forced on (they
+/// are backed by glyphs for black-and-white printers).
+const PRINT_STYLE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <style>
+    @page {
+        size: letter;
+        margin: 0.4in;
+    }
+    body {
+        font-family: Arial, sans-serif;
+        font-size: 8.5pt;
+        column-count: 3;
+        column-gap: 0.25in;
+        margin: 0;
+        -webkit-print-color-adjust: exact;
+        print-color-adjust: exact;
+    }
+    @media screen {
+        body {
+            max-width: 8.5in;
+            margin: 0.4in auto;
+        }
+    }
+    h1 {
+        column-span: all;
+        font-size: 14pt;
+        margin: 0 0 2pt 0;
+    }
+    .print-legend {
+        column-span: all;
+        font-size: 7.5pt;
+        color: #333;
+        margin: 0 0 6pt 0;
+    }
+    h2 {
+        font-size: 10pt;
+        border-bottom: 1px solid #000;
+        margin: 6pt 0 2pt 0;
+        break-after: avoid;
+    }
+    .print-beer {
+        display: flex;
+        align-items: center;
+        gap: 4pt;
+        padding: 1.5pt 0;
+        break-inside: avoid;
+    }
+    .print-tap {
+        min-width: 1.6em;
+        text-align: right;
+        font-weight: bold;
+        font-size: 10pt;
+    }
+    .print-main {
+        flex: 1;
+        min-width: 0;
+    }
+    .print-name {
+        font-weight: bold;
+    }
+    .print-meta {
+        font-size: 7.5pt;
+        color: #333;
+    }
+    .print-chip {
+        padding: 0 2pt;
+        border-radius: 2pt;
+        white-space: nowrap;
+    }
+    .print-qr {
+        flex: none;
+        width: 0.55in;
+        height: 0.55in;
+    }
+  </style>
+</head>
+<body>
+"#;
+
+/// Knobs for the `/print` menu.
+#[derive(Debug, Clone, Default)]
+pub struct PrintOptions {
+    /// Add a QR code per beer linking to its Untappd page (`qr=1`).
+    pub qr: bool,
+    /// Date in the heading; `None` leaves it off.
+    pub today: Option<NaiveDate>,
+    pub heatmap: Heatmap,
+}
+
+impl PrintOptions {
+    /// Options from a query string (`qr=1`, plus the `Heatmap` keys).
+    pub fn from_query(query: &str) -> PrintOptions {
+        let mut options = PrintOptions {
+            heatmap: Heatmap::from_query(query),
+            ..PrintOptions::default()
+        };
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            if key == "qr" {
+                options.qr = matches!(value.as_ref(), "1" | "true" | "yes");
+            }
+        }
+        options
+    }
+}
+
+/// The printable menu: the (already sorted) beers under category headers in
+/// three columns, sized so a typical board fits one letter page (two with QR
+/// codes). ABV and ratings keep their heatmap colors and add tier glyphs.
+pub fn render_print(beers: &[RatedBeer], options: &PrintOptions) -> String {
+    let mut html = String::from(PRINT_STYLE);
+    match options.today {
+        Some(day) => html.push_str(&format!(
+            "<h1>On tap &middot; {}</h1>\n",
+            day.format("%B %-d, %Y")
+        )),
+        None => html.push_str("<h1>On tap</h1>\n"),
+    }
+    let (tiers, cut) = options.heatmap.rating_tiers();
+    let ratings = match options.heatmap.rating_colors {
+        RatingColors::Global => "Untappd rating",
+        RatingColors::StylePercentile => "Rating within its style",
+    };
+    html.push_str(&format!(
+        "<p class=\"print-legend\">{ratings}: {}. Strength: {}.</p>\n",
+        tier_glyphs(tiers, cut),
+        tier_glyphs(&ABV_TIERS, abv_cut)
+    ));
+
+    let scores = MenuScores::new(beers);
+    let mut current: Option<&str> = None;
+    for b in beers {
+        let category = category_label(&b.entry.category);
+        if current != Some(category) {
+            html.push_str(&format!("<h2>{category}</h2>\n"));
+            current = Some(category);
+        }
+        html.push_str(&print_beer(b, &scores, options));
+    }
+    html.push_str("</body>\n</html>");
+    html
+}
+
+fn print_beer(b: &RatedBeer, scores: &MenuScores, options: &PrintOptions) -> String {
+    let e = &b.entry;
+    let heatmap = &options.heatmap;
+    let [_, _, abv_scale] = heatmap.scales();
+    let mut meta = vec![e.brewery.clone()];
+    if !e.style.is_empty() {
+        meta.push(e.style.clone());
+    }
+    if let Some(abv) = e.abv {
+        meta.push(format!(
+            "<span class=\"print-chip\" style=\"{}\">{}% {}</span>",
+            abv_scale.style(abv),
+            format_abv(abv),
+            tier_glyph(abv, &ABV_TIERS)
+        ));
+    }
+    let score = b.rating.as_ref().and_then(|r| r.score());
+    if let Some((score, adjusted)) = score.zip(scores.shrunk(b)) {
+        let shade = heatmap.shade_rating(adjusted, category_label(&e.category), scores);
+        meta.push(format!(
+            "<span class=\"print-chip\" style=\"{}\">{score:.2} {}</span>",
+            shade.style, shade.glyph
+        ));
+    }
+    let qr = match &b.rating {
+        Some(r) if options.qr => crate::qr::encode(r.url.as_bytes())
+            .map(|code| format!("<span class=\"print-qr\">{}</span>", code.to_svg()))
+            .unwrap_or_default(),
+        _ => String::new(),
+    };
+    format!(
+        "<div class=\"print-beer\"><span class=\"print-tap\">{}</span>\
+         <div class=\"print-main\"><div class=\"print-name\">{}</div>\
+         <div class=\"print-meta\">{}</div></div>{qr}</div>\n",
+        e.tap_number,
+        e.name,
+        meta.join(" &middot; ")
+    )
+}
+
+/// True if an `If-None-Match` header value matches `etag` (weak comparison,
+/// as RFC 9110 requires for this header; `*` matches anything).
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let bare = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    if_none_match
+        .split(',')
+        .any(|tag| tag.trim() == "*" || bare(tag) == bare(etag))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::model::{sort_rated, BeerEntry};
+    use crate::model::{sort_rated, BeerDetails, BeerEntry, LookupOutcome, RatingResult};
+
+    fn rating(score: &str, count: u64) -> RatingResult {
+        RatingResult {
+            rating: score.into(),
+            url: "https://untappd.com/b/x/1".into(),
+            rating_count: count,
+            matched_by: None,
+            details: BeerDetails::default(),
+            fetched_at: None,
+        }
+    }
 
-    fn beer(category: &str, abv: &str, name: &str, rating: &str) -> RatedBeer {
+    fn beer(category: &str, abv: &str, name: &str, score: &str) -> RatedBeer {
         RatedBeer {
             entry: BeerEntry {
                 tap_number: 1,
                 brewery: "Test Brewery".into(),
+                brewery_full: "Test Brewery Co.".into(),
                 name: name.into(),
-                abv: abv.into(),
+                full_name: format!("Test Brewery {name}"),
+                abv: crate::model::parse_abv(abv),
+                ibu: None,
                 category: category.into(),
+                category_order: None,
                 origin: "Somewhere".into(),
                 style: "IPA".into(),
-                days_old: 3,
+                date_added: NaiveDate::from_ymd_opt(2026, 5, 20),
+                pours: Vec::new(),
+            },
+            rating: (score != "N/A").then(|| rating(score, 1_000)),
+            outcome: if score == "N/A" {
+                LookupOutcome::NotFound
+            } else {
+                LookupOutcome::Cached
             },
-            rating_html: rating.into(),
         }
     }
 
@@ -287,6 +1902,339 @@ mod tests {
         assert!(html.contains("class=\"tap-cell\""));
     }
 
+    #[test]
+    fn tried_column_marks_and_hides_tried_beers() {
+        let mut beers = vec![
+            beer("IPA", "5.0", "Had It", "4.10"),
+            beer("IPA", "6.0", "New One", "3.90"),
+            beer("Sour", "4.0", "No Link", "N/A"),
+        ];
+        beers[1].rating.as_mut().unwrap().url = "https://untappd.com/b/new-one/42".into();
+        let tried = TriedList::from_json("[1]");
+
+        let mut options = RenderOptions::from_query("colors=style");
+        options.tried = Some(tried.clone());
+        let html = render_with(&beers, &options);
+        assert!(html.contains("<th>tried</th>"));
+        assert!(html.contains("action=\"/tried/1\""));
+        assert!(html.contains("action=\"/tried/42\""));
+        assert!(html.contains("<tr class=\"tried\">"));
+        assert!(html.contains("You've tried 1 of the 3 beers on tap."));
+        assert!(html.contains("href=\"?colors=style&amp;hide=tried\">Hide tried</a>"));
+
+        let mut options = RenderOptions::from_query("hide=tried&colors=style");
+        options.tried = Some(tried);
+        let html = render_with(&beers, &options);
+        assert!(!html.contains(">Had It<"));
+        assert!(html.contains("New One"));
+        // The remaining IPA is its category's only row now.
+        assert!(!html.contains("rowspan=\"2\""));
+        assert!(html.contains("href=\"?colors=style\">Show tried</a>"));
+
+        // No tried list, no column.
+        assert!(!render(&beers).contains("<th>tried</th>"));
+        assert!(!render(&beers).contains("/tried/switch"));
+
+        // A shared link to another list is offered, not adopted.
+        let options = RenderOptions {
+            offered_user: Some("0123456789abcdef0123456789abcdef".into()),
+            ..Default::default()
+        };
+        assert!(render_with(&beers, &options).contains(
+            "action=\"/tried/switch\">This link is for another tried list; this browser is \
+             still on its own. <input type=\"hidden\" name=\"user\" \
+             value=\"0123456789abcdef0123456789abcdef\">"
+        ));
+    }
+
+    #[test]
+    fn picks_render_above_the_table() {
+        let beers = vec![beer("IPA", "6.5", "Juice Bomb", "4.10")];
+        let options = RenderOptions {
+            picks: vec![Pick {
+                tap_number: 12,
+                brewery: "Test Brewery".into(),
+                name: "Juice Bomb".into(),
+                explanation: "Hazy IPA, a style you like".into(),
+            }],
+            ..Default::default()
+        };
+        let html = render_with(&beers, &options);
+        let picks = html.find("<h2>For you</h2>").unwrap();
+        assert!(picks < html.find("<table>").unwrap());
+        assert!(html.contains("#12</span>Test Brewery Juice Bomb"));
+        assert!(html.contains("&mdash; Hazy IPA, a style you like"));
+        assert!(!render(&beers).contains("For you"));
+    }
+
+    #[test]
+    fn flight_page_lists_pours_and_totals() {
+        let mut beers = vec![beer("Stout", "12.0", "Big Dark", "4.40")];
+        beers[0].entry.pours = vec![crate::model::Pour::parse("5 oz", "$6")];
+        let taxonomy = crate::taxonomy::Taxonomy::builtin();
+        let request = FlightRequest::from_query("n=1&price=10");
+        let flight = crate::flight::build_flight(&beers, &taxonomy, &request);
+        let html = render_flight(flight.as_ref(), &request, &beers, &Heatmap::default());
+        assert!(html.contains("Flight: 1 tap, at most $10.00"));
+        assert!(html.contains("<td class=\"center\">5 oz</td><td class=\"center\">$6.00</td>"));
+        assert!(html.contains("Total: 1.0 standard drinks, $6.00."));
+
+        let html = render_flight(None, &request, &beers, &Heatmap::default());
+        assert!(html.contains("Nothing on tap fits"));
+        assert!(!html.contains("<table>"));
+    }
+
+    #[test]
+    fn screen_paginates_and_tags_content() {
+        let beers: Vec<RatedBeer> = (1..=8)
+            .map(|n| {
+                let mut b = beer("IPA", "6.5", &format!("Beer {n}"), "4.05");
+                b.entry.tap_number = n;
+                b
+            })
+            .collect();
+        let options = ScreenOptions::from_query("per_page=6&rotate=20&poll=1");
+        assert_eq!(
+            (
+                options.per_page,
+                options.rotate_seconds,
+                options.poll_seconds
+            ),
+            (6, 20, 10)
+        );
+
+        let page = render_screen(&beers, &options);
+        assert_eq!(
+            page.html.matches("<section class=\"screen-page\"").count(),
+            2
+        );
+        assert_eq!(page.html.matches(" hidden>").count(), 1);
+        assert!(page.html.contains("<span class=\"screen-num\">8</span>"));
+        assert!(page.html.contains("Test Brewery &middot; IPA"));
+        assert!(page.html.contains(">6.5%</span>"));
+        assert!(page.html.contains(">4.05</span>"));
+        assert!(page.html.contains(&format!("var etag = '{}';", page.etag)));
+        assert!(page.html.contains("}, 20000);"));
+
+        // Same menu, same tag; any visible change, a new one.
+        assert_eq!(render_screen(&beers, &options).etag, page.etag);
+        let mut changed = beers.clone();
+        changed[3].rating.as_mut().unwrap().rating = "4.06".into();
+        assert_ne!(render_screen(&changed, &options).etag, page.etag);
+    }
+
+    #[test]
+    fn if_none_match_comparison() {
+        assert!(etag_matches("\"abc\"", "\"abc\""));
+        assert!(etag_matches("\"x\", W/\"abc\"", "\"abc\""));
+        assert!(etag_matches("*", "\"abc\""));
+        assert!(!etag_matches("\"abd\"", "\"abc\""));
+    }
+
+    #[test]
+    fn print_menu_groups_categories_with_glyphs_and_qr() {
+        let beers = vec![
+            beer("IPA", "10.5", "Big One", "4.45"),
+            beer("IPA", "6.0", "Session", "3.70"),
+            beer("Lager", "5.0", "Crisp", "N/A"),
+        ];
+        let options = PrintOptions {
+            today: NaiveDate::from_ymd_opt(2026, 5, 20),
+            ..PrintOptions::from_query("qr=1")
+        };
+        let html = render_print(&beers, &options);
+        assert!(html.contains("<h1>On tap &middot; May 20, 2026</h1>"));
+        assert!(html.contains(
+            "Untappd rating: &#9733; 3.95+ &middot; &#9733;&#9733; 4.15+ &middot; \
+             &#9733;&#9733;&#9733; 4.4+. Strength: &#9650; 7.5%+ &middot; &#9650;&#9650; 10%+."
+        ));
+        assert_eq!(html.matches("<h2>IPA</h2>").count(), 1);
+        assert!(html.contains("<h2>Lager</h2>"));
+        assert!(html.contains("10.5% &#9650;&#9650;</span>"));
+        assert!(html.contains("4.45 &#9733;&#9733;&#9733;</span>"));
+        assert!(html.contains("3.70 </span>"));
+        // Every beer with an Untappd link gets a code; the unmatched one not.
+        assert_eq!(html.matches("<span class=\"print-qr\"><svg").count(), 2);
+        assert!(!render_print(&beers, &PrintOptions::default()).contains("<svg"));
+    }
+
+    /// WCAG relative luminance of an sRGB color.
+    fn luminance((r, g, b): Rgb) -> f64 {
+        let linear = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if c <= 0.040_45 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b)
+    }
+
+    /// WCAG contrast ratio between two colors (1 to 21).
+    fn contrast(a: Rgb, b: Rgb) -> f64 {
+        let (la, lb) = (luminance(a), luminance(b));
+        (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+    }
+
+    #[test]
+    fn every_heatmap_color_meets_wcag_contrast() {
+        let palettes = [Palette::Standard, Palette::Viridis, Palette::Cividis];
+        for palette in palettes {
+            for scale in palette.scales(true) {
+                let lo = scale.stops[0].0 - 1.0;
+                let hi = scale.stops[scale.stops.len() - 1].0 + 1.0;
+                for step in 0..=1000 {
+                    let value = lo + (hi - lo) * f64::from(step) / 1000.0;
+                    let background = scale.color(value);
+                    // The text color the palette is designed for, as rendered.
+                    let text = if scale.style(value).ends_with("color:white") {
+                        (0xff, 0xff, 0xff)
+                    } else {
+                        (0, 0, 0)
+                    };
+                    if palette == Palette::Standard {
+                        assert_eq!(text, (0, 0, 0), "{palette:?} at {value}");
+                    }
+                    let ratio = contrast(background, text);
+                    assert!(
+                        ratio >= 4.5,
+                        "{palette:?} at {value}: {text:?} on {background:?} gives {ratio:.2}:1"
+                    );
+                }
+            }
+        }
+        // The neutral "?" / "N/A" cells too.
+        assert!(contrast((0x55, 0x55, 0x55), (0xe0, 0xe0, 0xe0)) >= 4.5);
+        // Why accessible mode lifts the standard red: black falls short on it.
+        assert!(contrast(RATING_SCALE.color(3.0), (0, 0, 0)) < 4.5);
+        // Sanity: the formula's extremes.
+        assert!((contrast((0, 0, 0), (0xff, 0xff, 0xff)) - 21.0).abs() < 1e-9);
+        assert_eq!(
+            VIRIDIS_RATING_SCALE.style(3.0),
+            "background-color:#440154;color:white"
+        );
+    }
+
+    #[test]
+    fn accessible_mode_adds_glyphs_labels_and_markup() {
+        let beers = vec![
+            beer("IPA", "10.5", "Big One", "4.45"),
+            beer("IPA", "4.2", "Small One", "3.50"),
+        ];
+        let options = RenderOptions::from_query("palette=cividis");
+        assert_eq!(
+            (options.heatmap.palette, options.heatmap.glyphs),
+            (Palette::Cividis, true)
+        );
+        let html = render_with(&beers, &options);
+        assert!(html.contains("aria-label=\"10.5% ABV, very strong\">10.5 <span class=\"tier\" aria-hidden=\"true\">&#9679;&#9679;&#9679;&#9679;</span>"));
+        assert!(html.contains("aria-label=\"4.2% ABV, light\">4.2 <span class=\"tier\" aria-hidden=\"true\">&#9679;&#9675;&#9675;&#9675;</span>"));
+        assert!(html.contains("aria-label=\"Rated 4.45, excellent\""));
+        assert!(html.contains(
+            "ABV light, medium (5%+), strong (7.5%+), very strong (10%+); \
+             rating fair, good (3.95+), very good (4.15+), excellent (4.4+)."
+        ));
+        assert!(html.contains("background-color:#fee838"));
+
+        assert!(html.contains("<table class=\"accessible\">\n<caption class=\"visually-hidden\">"));
+        assert!(html.contains("<th scope=\"col\">abv</th>"));
+        assert!(html.contains(
+            "<th scope=\"row\" class=\"category-cell category-cell-even\" rowspan=\"2\">IPA</th>"
+        ));
+
+        // The standard page is unchanged unless asked.
+        let plain = render(&beers);
+        assert!(plain.contains("<table>\n<thead>\n<tr><th>"));
+        assert!(!plain.contains("aria-hidden"));
+        assert!(!plain.contains("<th scope"));
+        let glyphs = RenderOptions::from_query("glyphs=1");
+        assert!(glyphs.heatmap.glyphs);
+        let strong = vec![beer("IPA", "11.0", "Huge One", "4.00")];
+        assert!(render_with(&strong, &glyphs).contains("background-color:#e0402f;color:black"));
+        assert!(render(&strong).contains("background-color:#d73027;color:black"));
+        assert!(
+            !RenderOptions::from_query("palette=viridis&glyphs=0")
+                .heatmap
+                .glyphs
+        );
+    }
+
+    #[test]
+    fn stale_menu_shows_a_banner() {
+        let beers = vec![beer("IPA", "6.0", "Hazy Thing", "4.10")];
+        let since = chrono::DateTime::parse_from_rfc3339("2026-05-20T15:16:00-07:00").unwrap();
+        let options = RenderOptions {
+            stale_since: Some(since),
+            ..Default::default()
+        };
+        let html = render_with(&beers, &options);
+        assert!(html.contains("this is the menu as of Wed May 20, 3:16 PM."));
+        assert!(html.contains("Hazy Thing"));
+        assert!(!render(&beers).contains("<p class=\"stale-banner\""));
+    }
+
+    #[test]
+    fn failed_lookups_say_retrying_and_trip_the_banner() {
+        let failed = |name, outcome| RatedBeer {
+            outcome,
+            ..beer("IPA", "6.0", name, "N/A")
+        };
+        let mut beers = vec![
+            failed("Timed Out", LookupOutcome::NetworkError),
+            failed("Refused", LookupOutcome::Blocked),
+            failed("Also Timed Out", LookupOutcome::NetworkError),
+            beer("IPA", "6.0", "Unknown", "N/A"),
+        ];
+        beers.extend((0..6).map(|_| beer("IPA", "6.0", "Fine", "4.00")));
+
+        let html = render(&beers);
+        assert_eq!(html.matches("lookup failed, retrying").count(), 3);
+        assert!(html.contains("title=\"Untappd lookup failed (blocked); retried"));
+        assert_eq!(html.matches(">N/A</td>").count(), 1);
+        assert!(html.contains(
+            "Couldn't look up 3 of 10 ratings on Untappd (network error); \
+             they'll be retried on the next load."
+        ));
+
+        // One failure in eight is noted on its cell but doesn't warrant a banner.
+        let html = render(&beers[2..]);
+        assert_eq!(html.matches("lookup failed, retrying").count(), 1);
+        assert!(!html.contains("<p class=\"lookup-banner\""));
+    }
+
+    #[test]
+    fn deferred_lookups_say_pending_without_a_banner() {
+        let mut beers: Vec<RatedBeer> = (0..5)
+            .map(|_| RatedBeer {
+                outcome: LookupOutcome::Pending,
+                ..beer("IPA", "6.0", "Later", "N/A")
+            })
+            .collect();
+        beers.push(beer("IPA", "6.0", "Now", "4.00"));
+        let html = render(&beers);
+        assert_eq!(html.matches(">pending</td>").count(), 5);
+        assert!(html.contains("class=\"rating-pending center\""));
+        assert!(!html.contains("<p class=\"lookup-banner\""));
+        let screen = render_screen(&beers, &ScreenOptions::default());
+        assert_eq!(screen.html.matches(">pending</span>").count(), 5);
+    }
+
+    #[test]
+    fn error_page_explains_without_leaking_details() {
+        let html = render_error(
+            &AppError::Parse("missing items at $.menu".into()),
+            "8f1c2a3b-SJC",
+        );
+        assert!(html.contains("didn't understand"));
+        assert!(html.contains("Error 502 &middot; reference 8f1c2a3b-SJC"));
+        assert!(!html.contains("missing items"));
+        let html = render_error(&AppError::Blocked("403".into()), "x");
+        assert!(html.contains("in about 5 minutes"));
+        let (headline, _) = error_message(&AppError::Network("connect timed out".into()));
+        assert_eq!(headline, "We couldn't reach the tap list.");
+    }
+
     #[test]
     fn scale_blends_between_stops() {
         // Exact stop values reproduce the stop color.
@@ -350,9 +2298,310 @@ mod tests {
     }
 
     #[test]
-    fn rating_value_parsing() {
-        let cell = "<a href=\"https://untappd.com/b/x/1\">3.62</a>";
-        assert_eq!(rating_value(cell), Some(3.62));
-        assert_eq!(rating_value("N/A"), None);
+    fn renders_untappd_label_badges_and_style_fallback() {
+        let mut b = beer("IPA", "6.0", "Hazy Thing", "4.10");
+        b.entry.style = String::new();
+        if let Some(r) = b.rating.as_mut() {
+            r.details = BeerDetails {
+                beer_label: Some("https://assets.untappd.com/label.jpeg".into()),
+                style: Some("IPA - New England / Hazy".into()),
+                in_production: Some(false),
+                has_community_award: true,
+                ..Default::default()
+            };
+        }
+        let html = render(&[b]);
+        assert!(html
+            .contains("<img class=\"label-thumb\" src=\"https://assets.untappd.com/label.jpeg\""));
+        assert!(html.contains("badge-award"));
+        assert!(html.contains("badge-retired"));
+        assert!(html.contains(">IPA - New England / Hazy</td>"));
+
+        // Untappd's strings are escaped; labels must be https.
+        let mut hostile = beer("IPA", "6.0", "Hazy Thing", "4.10");
+        hostile.entry.style = String::new();
+        if let Some(r) = hostile.rating.as_mut() {
+            r.details = BeerDetails {
+                beer_label: Some("https://x.test/a.png\" onerror=\"alert(1)".into()),
+                style: Some("IPA <script>alert(1)</script> & Co".into()),
+                ..Default::default()
+            };
+        }
+        let html = render(&[hostile]);
+        assert!(html.contains("src=\"https://x.test/a.png&quot; onerror=&quot;alert(1)\""));
+        assert!(html.contains(">IPA &lt;script>alert(1)&lt;/script> &amp; Co</td>"));
+        assert!(!html.contains("<script>alert"));
+
+        // A menu style wins over Untappd's, and unrated rows have no extras.
+        let plain = render(&[beer("IPA", "6.0", "Plain", "N/A")]);
+        assert!(plain.contains("<td>IPA</td>"));
+        assert!(!plain.contains("<img"));
+    }
+
+    #[test]
+    fn style_percentile_mode_highlights_the_best_of_each_style() {
+        let mut beers = vec![
+            beer("Pilsners and Pale Lagers", "4.8", "Lager A", "3.40"),
+            beer("Pilsners and Pale Lagers", "4.9", "Lager B", "3.55"),
+            beer("Pilsners and Pale Lagers", "5.0", "Lager C", "3.70"),
+            beer("IPAs", "6.5", "IPA A", "3.90"),
+        ];
+        sort_rated(&mut beers);
+        let options = RenderOptions {
+            heatmap: Heatmap {
+                rating_colors: RatingColors::StylePercentile,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let html = render_with(&beers, &options);
+
+        // The best lager gets the top color despite a middling raw score...
+        let best = PERCENTILE_SCALE.style(1.0);
+        let lager_c = html.find(">Lager C<").unwrap();
+        assert!(html[lager_c..].find(&best).unwrap() < html[lager_c..].find("</tr>").unwrap());
+        assert!(html.contains("best for Pilsners and Pale Lagers"));
+        // ... the lone IPA is ranked against the stored IPA distribution.
+        assert!(html.contains("for IPAs"));
+        assert!(html.contains("class=\"legend\""));
+
+        // Global mode is the default and unchanged.
+        assert_eq!(
+            render(&beers),
+            render_with(&beers, &RenderOptions::default())
+        );
+        assert!(!render(&beers).contains("class=\"legend\""));
+    }
+
+    #[test]
+    fn accessible_percentile_mode_tiers_by_style_rank() {
+        let mut beers = vec![
+            beer("Pilsners and Pale Lagers", "4.8", "Lager A", "3.40"),
+            beer("Pilsners and Pale Lagers", "4.9", "Lager B", "3.55"),
+            beer("Pilsners and Pale Lagers", "5.0", "Lager C", "3.70"),
+        ];
+        sort_rated(&mut beers);
+        let html = render_with(
+            &beers,
+            &RenderOptions::from_query("colors=style&palette=cividis"),
+        );
+        let cell = |name: &str| {
+            let row = &html[html.find(&format!(">{name}<")).unwrap()..];
+            row[..row.find("</tr>").unwrap()].to_string()
+        };
+        // A 3.70 is "fair" on Untappd's scale but the best lager on tap: the
+        // dots and label follow the color.
+        let best = cell("Lager C");
+        assert!(best.contains("aria-label=\"Rated 3.70, best for Pilsners and Pale Lagers\""));
+        assert!(
+            best.contains("&#9679;&#9679;&#9679;&#9679;</span>"),
+            "{best}"
+        );
+        let lowest = cell("Lager A");
+        assert!(lowest.contains("aria-label=\"Rated 3.40, lowest for Pilsners and Pale Lagers\""));
+        assert!(
+            lowest.contains("&#9679;&#9675;&#9675;&#9675;</span>"),
+            "{lowest}"
+        );
+        assert!(html.contains(
+            "rating within its style fair, good (top 30%), very good (top 15%), excellent (best)."
+        ));
+    }
+
+    #[test]
+    fn flight_screen_and_print_color_like_the_menu() {
+        let mut beers = vec![
+            beer("Pilsners and Pale Lagers", "4.8", "Lager A", "3.40"),
+            beer("Pilsners and Pale Lagers", "4.9", "Lager B", "3.55"),
+            beer("Pilsners and Pale Lagers", "5.0", "Lager C", "3.70"),
+            beer("Barleywine", "11.0", "Big One", "3.60"),
+        ];
+        sort_rated(&mut beers);
+        let query = "colors=style&palette=cividis";
+        let best = CIVIDIS_PERCENTILE_SCALE.style(1.0);
+        let row = |html: &str, name: &str| {
+            let row = &html[html.find(&format!(">{name}<")).unwrap()..];
+            row[..row.find('\n').unwrap()].to_string()
+        };
+
+        let screen = render_screen(&beers, &ScreenOptions::from_query(query)).html;
+        let lager = row(&screen, "Lager C");
+        assert!(lager.contains(&best), "{lager}");
+        assert!(
+            lager.contains("&#9679;&#9679;&#9679;&#9679;</span>"),
+            "{lager}"
+        );
+
+        let print = render_print(&beers, &PrintOptions::from_query(query));
+        let lager = row(&print, "Lager C");
+        assert!(
+            lager.contains(&format!("{best}\">3.70 &#9733;&#9733;&#9733;")),
+            "{lager}"
+        );
+        assert!(print.contains(
+            "Rating within its style: &#9733; top 30% &middot; &#9733;&#9733; top 15% \
+             &middot; &#9733;&#9733;&#9733; best."
+        ));
+
+        let request = FlightRequest::from_query("n=1");
+        let flight =
+            crate::flight::build_flight(&beers, &crate::taxonomy::Taxonomy::builtin(), &request);
+        let html = render_flight(
+            flight.as_ref(),
+            &request,
+            &beers,
+            &Heatmap::from_query(query),
+        );
+        let lager = row(&html, "Lager C");
+        assert!(lager.contains(&best), "{lager}");
+        assert!(lager.contains("aria-label=\"Rated 3.70, best for Pilsners and Pale Lagers\""));
+
+        // Accessible mode lifts the standard red on every page.
+        let heatmap = Heatmap::from_query("glyphs=1");
+        let strong = AA_ABV_SCALE.style(11.0);
+        let screen = render_screen(
+            &beers,
+            &ScreenOptions {
+                heatmap,
+                ..ScreenOptions::default()
+            },
+        )
+        .html;
+        assert!(row(&screen, "Big One").contains(&strong));
+        let print = render_print(
+            &beers,
+            &PrintOptions {
+                heatmap,
+                ..PrintOptions::default()
+            },
+        );
+        assert!(row(&print, "Big One").contains(&strong));
+        assert!(!screen.contains("#d73027") && !print.contains("#d73027"));
+    }
+
+    #[test]
+    fn accessible_scales_lift_only_the_red() {
+        let pairs = [
+            (&RATING_SCALE, &AA_RATING_SCALE),
+            (&PERCENTILE_SCALE, &AA_PERCENTILE_SCALE),
+            (&ABV_SCALE, &AA_ABV_SCALE),
+        ];
+        for (standard, accessible) in pairs {
+            assert_eq!(standard.stops.len(), accessible.stops.len());
+            for (stop, lifted) in standard.stops.iter().zip(accessible.stops) {
+                let expected = if stop.1 == STANDARD_RED {
+                    AA_RED
+                } else {
+                    stop.1
+                };
+                assert_eq!(*lifted, (stop.0, expected));
+            }
+            assert!(accessible.stops.iter().any(|stop| stop.1 == AA_RED));
+        }
+    }
+
+    #[test]
+    fn rating_colors_from_param() {
+        assert_eq!(
+            RatingColors::from_param("style"),
+            Some(RatingColors::StylePercentile)
+        );
+        assert_eq!(
+            RatingColors::from_param("global"),
+            Some(RatingColors::Global)
+        );
+        assert_eq!(RatingColors::from_param("rainbow"), None);
+        assert_eq!(
+            RenderOptions::from_query("colors=style&x=1")
+                .heatmap
+                .rating_colors,
+            RatingColors::StylePercentile
+        );
+        assert_eq!(
+            RenderOptions::from_query("colors=rainbow")
+                .heatmap
+                .rating_colors,
+            RatingColors::Global
+        );
+    }
+
+    #[test]
+    fn unknown_abv_and_age_render_neutral_question_marks() {
+        let mut beers = vec![
+            beer("IPA", "", "Mystery", "N/A"),
+            beer("IPA", "6.0", "Known", "N/A"),
+        ];
+        beers[0].entry.date_added = None;
+        sort_rated(&mut beers);
+        let options = RenderOptions {
+            today: NaiveDate::from_ymd_opt(2026, 5, 23),
+            ..Default::default()
+        };
+        let html = render_with(&beers, &options);
+
+        // Unknown ABV sorts after the known one and isn't heatmapped.
+        assert!(html.find(">Known<").unwrap() < html.find(">Mystery<").unwrap());
+        assert_eq!(
+            html.matches("<td class=\"unknown center\">?</td>").count(),
+            2
+        );
+        // Ages are computed at render time against `today`.
+        assert!(html.contains("<td class=\"center\">3</td>"));
+        assert!(html.contains(">6.0</td>"));
+    }
+
+    #[test]
+    fn rating_score_parsing() {
+        assert_eq!(rating("3.62", 10).score(), Some(3.62));
+        assert_eq!(rating("N/A", 10).score(), None);
+    }
+
+    #[test]
+    fn low_count_scores_shrink_toward_the_mean() {
+        // A dozen check-ins barely moves the needle off the style mean...
+        let few = shrunk_score(4.4, 12, 3.8);
+        assert!(few > 3.8 && few < 4.0, "got {few}");
+        // ... fifty thousand keep the raw score.
+        assert!((shrunk_score(4.4, 50_000, 3.8) - 4.4).abs() < 0.001);
+        // Unknown counts are left alone.
+        assert_eq!(shrunk_score(4.4, 0, 3.8), 4.4);
+    }
+
+    #[test]
+    fn renders_count_and_dims_low_confidence_ratings() {
+        let mut beers = vec![
+            beer("IPA", "6.0", "Popular", "4.40"),
+            beer("IPA", "6.5", "Obscure", "4.40"),
+            beer("IPA", "7.0", "Average", "3.60"),
+        ];
+        beers[0].rating = Some(rating("4.40", 50_000));
+        beers[1].rating = Some(rating("4.40", 12));
+        let html = render(&beers);
+
+        assert!(html.contains("4.40 <span class=\"rating-count\">(50k)</span>"));
+        assert!(html.contains("4.40 <span class=\"rating-count\">(12)</span>"));
+        // Same raw score, but only the 12-check-in one is colored as shrunk.
+        assert!(html.contains("title=\"50k check-ins\""));
+        assert!(html.contains("title=\"12 check-ins; colored as"));
+    }
+
+    #[test]
+    fn tooltip_says_when_the_rating_was_fetched() {
+        let mut beers = vec![beer("IPA", "6.0", "Dated", "4.10")];
+        let mut dated = rating("4.10", 50_000);
+        // Late evening in California is already the next day in UTC.
+        dated.fetched_at = clock::parse_utc("2026-06-23T05:30:00Z");
+        beers[0].rating = Some(dated);
+        let html = render(&beers);
+        assert!(html.contains("title=\"50k check-ins; rated as of Jun 22\""));
+
+        let options = RenderOptions {
+            timezone: Some(VenueTimezone {
+                std_offset_minutes: 0,
+                us_dst: false,
+            }),
+            ..Default::default()
+        };
+        assert!(render_with(&beers, &options).contains("rated as of Jun 23"));
     }
 }
@@ -0,0 +1,375 @@
+//
+// Retries and pacing for upstream HTTP calls. The policy is pure: each runner
+// (the wasm glue over `worker::Fetch`, the dev runner over reqwest) makes the
+// request, hands back an `Attempt`, and sleeps for whatever `decide` says.
+// Statuses become `AppError` variants here, so a 403 is `Blocked` and a 503 a
+// retryable `Network` error whichever transport saw it. A token bucket per
+// upstream host keeps bursts (five concurrent rating lookups) polite.
+//
+
+use crate::error::{AppError, AppResult};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// What one try got back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attempt {
+    /// The upstream answered, with any status.
+    Response {
+        status: u16,
+        /// The raw `Retry-After` header, if sent.
+        retry_after: Option<String>,
+        body: String,
+    },
+    /// No usable answer: connection failure, timeout, unreadable body.
+    Transport(String),
+}
+
+/// What to do after an attempt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    Done(AppResult<String>),
+    /// Wait this long, then try again.
+    Retry {
+        wait_ms: u64,
+    },
+}
+
+/// Bounded retries with jittered exponential backoff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Tries in all, including the first.
+    pub max_attempts: u32,
+    /// Backoff before the second try; doubles each time after.
+    pub base_delay_ms: u64,
+    /// Longest we'll wait between tries. A `Retry-After` asking for more gives
+    /// up instead: someone is waiting on the page.
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay_ms: 250,
+            max_delay_ms: 4_000,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Judge attempt number `attempt` (from 1) against `host`. `jitter` in
+    /// [0, 1) spreads the backoff between half and all of its nominal length.
+    pub fn decide(
+        &self,
+        host: &str,
+        attempt: u32,
+        outcome: Attempt,
+        now: DateTime<Utc>,
+        jitter: f64,
+    ) -> Decision {
+        let (result, retry_after) = match outcome {
+            Attempt::Transport(reason) => {
+                (Err(AppError::Network(format!("{host}: {reason}"))), None)
+            }
+            Attempt::Response {
+                status,
+                retry_after,
+                body,
+            } => (
+                classify(host, status, body),
+                retry_after.and_then(|v| parse_retry_after(&v, now)),
+            ),
+        };
+        let retryable = match &result {
+            Ok(_) => false,
+            Err(AppError::Network(_)) => true,
+            // Rate limited (429) passes; refused (401/403) won't change.
+            Err(AppError::Blocked(_)) => retry_after.is_some(),
+            Err(_) => false,
+        };
+        if !retryable || attempt >= self.max_attempts {
+            return Decision::Done(result);
+        }
+        let wait_ms = match retry_after {
+            Some(ms) if ms > self.max_delay_ms => return Decision::Done(result),
+            Some(ms) => ms,
+            None => {
+                let nominal = self
+                    .base_delay_ms
+                    .saturating_mul(1 << (attempt - 1).min(16))
+                    .min(self.max_delay_ms);
+                nominal / 2 + (nominal as f64 / 2.0 * jitter.clamp(0.0, 1.0)) as u64
+            }
+        };
+        Decision::Retry { wait_ms }
+    }
+}
+
+/// Map an HTTP status to a result: 2xx is the body; 401/403 and 429 are
+/// `Blocked`; 408 and 5xx are (retryable) `Network`; any other status means the
+/// upstream didn't give us what we asked for, a `Parse` error.
+pub fn classify(host: &str, status: u16, body: String) -> AppResult<String> {
+    match status {
+        200..=299 => Ok(body),
+        401 | 403 => Err(AppError::Blocked(format!(
+            "{host} refused us (HTTP {status})"
+        ))),
+        429 => Err(AppError::Blocked(format!(
+            "{host} rate limited us (HTTP 429)"
+        ))),
+        408 | 500..=599 => Err(AppError::Network(format!("{host} answered HTTP {status}"))),
+        _ => Err(AppError::Parse(format!("{host} answered HTTP {status}"))),
+    }
+}
+
+/// A `Retry-After` value (seconds, or an HTTP date) as milliseconds from `now`.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(seconds.saturating_mul(1_000));
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).num_milliseconds().max(0) as u64)
+}
+
+/// Run `send` under `policy`, sleeping between tries with `sleep`. For
+/// blocking callers (the dev runner); the wasm glue runs the same loop with
+/// async sends and timers.
+pub fn with_retries(
+    policy: &RetryPolicy,
+    host: &str,
+    seed: u64,
+    now: DateTime<Utc>,
+    mut send: impl FnMut() -> Attempt,
+    mut sleep: impl FnMut(u64),
+) -> AppResult<String> {
+    let mut attempt = 1;
+    loop {
+        match policy.decide(host, attempt, send(), now, jitter(seed, attempt)) {
+            Decision::Done(result) => return result,
+            Decision::Retry { wait_ms } => {
+                sleep(wait_ms);
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// A number in [0, 1) from `seed` and `attempt` (splitmix64), so concurrent
+/// retries with different seeds don't wake in lockstep.
+pub fn jitter(seed: u64, attempt: u32) -> f64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15_u64.wrapping_mul(attempt as u64 + 1));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// The host part of a URL, for limits and messages.
+pub fn host_of(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// Burst size and sustained requests per second, by host suffix. Algolia
+/// takes a menu's worth of lookups in a burst; TapHunter gets two pages a
+/// load and no reason to see more.
+const HOST_LIMITS: [(&str, f64, f64); 2] =
+    [("algolia.net", 5.0, 10.0), ("taphunter.com", 2.0, 1.0)];
+/// Limits for any other host.
+const DEFAULT_LIMIT: (f64, f64) = (4.0, 4.0);
+
+/// A token bucket: `burst` requests at once, refilled at `per_second`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenBucket {
+    burst: f64,
+    per_second: f64,
+    tokens: f64,
+    updated_ms: u64,
+}
+
+impl TokenBucket {
+    pub fn new(burst: f64, per_second: f64, now_ms: u64) -> TokenBucket {
+        TokenBucket {
+            burst,
+            per_second,
+            tokens: burst,
+            updated_ms: now_ms,
+        }
+    }
+
+    /// Take a token, returning how long to wait before using it (0 if one is
+    /// free now). Tokens can be borrowed ahead, so concurrent callers queue up
+    /// behind each other rather than all waking at once.
+    pub fn reserve(&mut self, now_ms: u64) -> u64 {
+        let elapsed = now_ms.saturating_sub(self.updated_ms) as f64 / 1_000.0;
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.burst);
+        self.updated_ms = now_ms.max(self.updated_ms);
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            0
+        } else {
+            (-self.tokens / self.per_second * 1_000.0).ceil() as u64
+        }
+    }
+}
+
+/// A token bucket per upstream host, with limits from `HOST_LIMITS`.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    buckets: HashMap<String, TokenBucket>,
+}
+
+impl RateLimiter {
+    /// Milliseconds to wait before the next request to `host`.
+    pub fn reserve(&mut self, host: &str, now_ms: u64) -> u64 {
+        self.buckets
+            .entry(host.to_string())
+            .or_insert_with(|| {
+                let (burst, per_second) = HOST_LIMITS
+                    .iter()
+                    .find(|(suffix, _, _)| host.ends_with(suffix))
+                    .map_or(DEFAULT_LIMIT, |&(_, burst, rate)| (burst, rate));
+                TokenBucket::new(burst, per_second, now_ms)
+            })
+            .reserve(now_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock;
+    use std::cell::RefCell;
+
+    const HOST: &str = "9wbo4rq3ho-dsn.algolia.net";
+
+    fn now() -> DateTime<Utc> {
+        clock::parse_utc("2026-05-20T22:00:00Z").unwrap()
+    }
+
+    fn answer(status: u16, retry_after: Option<&str>) -> Attempt {
+        Attempt::Response {
+            status,
+            retry_after: retry_after.map(str::to_string),
+            body: format!("body {status}"),
+        }
+    }
+
+    /// A scripted transport: hands out `answers` in order and records sleeps.
+    fn run(policy: &RetryPolicy, answers: Vec<Attempt>) -> (AppResult<String>, usize, Vec<u64>) {
+        let answers = RefCell::new(answers.into_iter());
+        let mut sent = 0;
+        let mut slept = Vec::new();
+        let result = with_retries(
+            policy,
+            HOST,
+            42,
+            now(),
+            || {
+                sent += 1;
+                answers
+                    .borrow_mut()
+                    .next()
+                    .expect("sent more than scripted")
+            },
+            |ms| slept.push(ms),
+        );
+        (result, sent, slept)
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        assert_eq!(classify(HOST, 200, "ok".into()), Ok("ok".into()));
+        assert!(matches!(
+            classify(HOST, 403, String::new()),
+            Err(AppError::Blocked(_))
+        ));
+        assert!(matches!(
+            classify(HOST, 429, String::new()),
+            Err(AppError::Blocked(_))
+        ));
+        assert!(matches!(
+            classify(HOST, 503, String::new()),
+            Err(AppError::Network(_))
+        ));
+        assert!(matches!(
+            classify(HOST, 404, String::new()),
+            Err(AppError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn retries_transient_failures_with_growing_jittered_backoff() {
+        let policy = RetryPolicy::default();
+        let (result, sent, slept) = run(
+            &policy,
+            vec![
+                Attempt::Transport("connection reset".into()),
+                answer(502, None),
+                answer(200, None),
+            ],
+        );
+        assert_eq!(result, Ok("body 200".into()));
+        assert_eq!(sent, 3);
+        assert_eq!(slept.len(), 2);
+        assert!((125..=250).contains(&slept[0]), "{slept:?}");
+        assert!((250..=500).contains(&slept[1]), "{slept:?}");
+
+        // Bounded: the last failure is the answer.
+        let (result, sent, _) = run(&policy, vec![answer(503, None); 3]);
+        assert!(matches!(result, Err(AppError::Network(_))));
+        assert_eq!(sent, 3);
+    }
+
+    #[test]
+    fn honors_retry_after_and_gives_up_on_refusals() {
+        let policy = RetryPolicy::default();
+        let (result, sent, slept) = run(&policy, vec![answer(429, Some("2")), answer(200, None)]);
+        assert_eq!(result, Ok("body 200".into()));
+        assert_eq!((sent, slept), (2, vec![2_000]));
+
+        // An HTTP-date works too.
+        let (_, _, slept) = run(
+            &policy,
+            vec![
+                answer(503, Some("Wed, 20 May 2026 22:00:03 GMT")),
+                answer(200, None),
+            ],
+        );
+        assert_eq!(slept, vec![3_000]);
+
+        // Longer than we'll make a visitor wait: fail now.
+        let (result, sent, _) = run(&policy, vec![answer(429, Some("120"))]);
+        assert!(matches!(result, Err(AppError::Blocked(_))));
+        assert_eq!(sent, 1);
+
+        // A refusal or a bad request won't improve with another try.
+        assert_eq!(run(&policy, vec![answer(403, None)]).1, 1);
+        assert_eq!(run(&policy, vec![answer(404, None)]).1, 1);
+    }
+
+    #[test]
+    fn token_buckets_pace_each_host() {
+        let mut limiter = RateLimiter::default();
+        // Algolia: a burst of five, then 10/s.
+        let waits: Vec<u64> = (0..7).map(|_| limiter.reserve(HOST, 1_000)).collect();
+        assert_eq!(waits, vec![0, 0, 0, 0, 0, 100, 200]);
+        // TapHunter has its own, smaller bucket.
+        assert_eq!(limiter.reserve("www.taphunter.com", 1_000), 0);
+        assert_eq!(limiter.reserve("www.taphunter.com", 1_000), 0);
+        assert_eq!(limiter.reserve("www.taphunter.com", 1_000), 1_000);
+        // Refilled after a quiet second.
+        assert_eq!(limiter.reserve(HOST, 2_300), 0);
+
+        assert_eq!(
+            host_of("https://9wbo4rq3ho-dsn.algolia.net/1/indexes/beer/query"),
+            HOST
+        );
+        assert!((0.0..1.0).contains(&jitter(42, 1)));
+        assert_ne!(jitter(42, 1), jitter(43, 1));
+    }
+}
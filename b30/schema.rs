@@ -0,0 +1,258 @@
+//
+// Schema drift detection for the TapHunter menu JSON. `parse_menu` treats a
+// missing field as blank, so a renamed `beer_name` would quietly render empty
+// cells. This counts, per field `parse_menu` reads, the items where it is
+// missing (absent, null or "") or mistyped, and warns when a key field is
+// unusable in more than `KEY_FIELD_THRESHOLD` of the items.
+//
+
+use crate::error::{AppError, AppResult};
+use serde_json::Value;
+
+/// Share of items a key field may be missing or mistyped in before we warn.
+/// Individual kegs do lack an ABV or style now and then; a fifth of the menu
+/// doing so means the field moved.
+pub const KEY_FIELD_THRESHOLD: f64 = 0.2;
+
+/// The JSON type a field should have.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Number,
+    Text,
+    /// Numeric fields TapHunter sends as strings, or sometimes bare numbers.
+    TextOrNumber,
+    List,
+}
+
+impl FieldKind {
+    fn accepts(self, value: &Value) -> bool {
+        match self {
+            FieldKind::Number => value.is_number(),
+            FieldKind::Text => value.is_string(),
+            FieldKind::TextOrNumber => value.is_string() || value.is_number(),
+            FieldKind::List => value.is_array(),
+        }
+    }
+}
+
+/// A field `parse_menu` reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldSpec {
+    /// Dotted JSON path within a menu item.
+    pub path: &'static str,
+    pub kind: FieldKind,
+    /// Whether the menu is broken without it (otherwise it's only counted).
+    pub key: bool,
+}
+
+const fn field(path: &'static str, kind: FieldKind, key: bool) -> FieldSpec {
+    FieldSpec { path, kind, key }
+}
+
+/// Every field `taphunter::parse_menu` reads.
+pub const MENU_FIELDS: [FieldSpec; 15] = [
+    field("serving_info.tap_number", FieldKind::Number, true),
+    field("beer.beer_name", FieldKind::Text, true),
+    field("beer.name", FieldKind::Text, true),
+    field("beer.style_category", FieldKind::Text, true),
+    field("beer.style", FieldKind::Text, true),
+    field("beer.abv", FieldKind::TextOrNumber, true),
+    field("brewery.common_name", FieldKind::Text, true),
+    field("brewery.name", FieldKind::Text, false),
+    field("brewery.origin", FieldKind::Text, false),
+    field("beer.ibu", FieldKind::TextOrNumber, false),
+    field("category.order", FieldKind::TextOrNumber, false),
+    field("serving_info.sized_pricing", FieldKind::List, false),
+    field("date_added_iso8601", FieldKind::Text, false),
+    field("date_added_timestamp", FieldKind::Number, false),
+    field("date_added", FieldKind::Text, false),
+];
+
+/// How one field fared across the menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldCount {
+    pub spec: FieldSpec,
+    /// Items where it is absent, null or an empty string.
+    pub missing: usize,
+    /// Items where it is present with the wrong JSON type.
+    pub mistyped: usize,
+}
+
+impl FieldCount {
+    pub fn unusable(&self) -> usize {
+        self.missing + self.mistyped
+    }
+}
+
+/// Field counts for a whole menu, plus warnings for drifted key fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaReport {
+    pub items: usize,
+    pub fields: Vec<FieldCount>,
+    pub warnings: Vec<String>,
+}
+
+impl SchemaReport {
+    pub fn is_clean(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn field(&self, path: &str) -> Option<&FieldCount> {
+        self.fields.iter().find(|f| f.spec.path == path)
+    }
+}
+
+/// Check a menu JSON body against `MENU_FIELDS`. Errors only when the body
+/// isn't a JSON array of items at all (which `parse_menu` rejects too).
+pub fn validate_menu(json: &str) -> AppResult<SchemaReport> {
+    let items: Vec<Value> = serde_json::from_str(json)
+        .map_err(|e| AppError::Parse(format!("Failed to parse menu JSON: {e}")))?;
+
+    let fields: Vec<FieldCount> = MENU_FIELDS
+        .iter()
+        .map(|&spec| {
+            let mut count = FieldCount {
+                spec,
+                missing: 0,
+                mistyped: 0,
+            };
+            for item in &items {
+                let value = lookup(item, spec.path);
+                if value.is_null() || value.as_str().is_some_and(|s| s.trim().is_empty()) {
+                    count.missing += 1;
+                } else if !spec.kind.accepts(value) {
+                    count.mistyped += 1;
+                }
+            }
+            count
+        })
+        .collect();
+
+    let warnings = fields
+        .iter()
+        .filter(|f| f.spec.key && f.unusable() as f64 > KEY_FIELD_THRESHOLD * items.len() as f64)
+        .map(|f| {
+            let mut why = Vec::new();
+            if f.missing > 0 {
+                why.push(format!("missing in {}", f.missing));
+            }
+            if f.mistyped > 0 {
+                why.push(format!("mistyped in {}", f.mistyped));
+            }
+            format!(
+                "{} {} of {} items",
+                f.spec.path,
+                why.join(", "),
+                items.len()
+            )
+        })
+        .collect();
+
+    Ok(SchemaReport {
+        items: items.len(),
+        fields,
+        warnings,
+    })
+}
+
+/// The value at a dotted path (`Null` if absent).
+fn lookup<'a>(item: &'a Value, path: &str) -> &'a Value {
+    path.split('.').fold(item, |value, key| &value[key])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MENU: &str = include_str!("fixtures/taphunter_menu.json");
+
+    /// The fixture menu with `mutate` applied to the first `n` items.
+    fn mutated(n: usize, mutate: impl Fn(&mut Value)) -> String {
+        let mut items: Vec<Value> = serde_json::from_str(MENU).unwrap();
+        items.iter_mut().take(n).for_each(mutate);
+        Value::from(items).to_string()
+    }
+
+    #[test]
+    fn fixture_menu_is_clean() {
+        let report = validate_menu(MENU).unwrap();
+        assert_eq!(report.items, 30);
+        assert!(report.is_clean(), "{:?}", report.warnings);
+        // Blank-but-present values are counted, just not warned about.
+        assert_eq!(report.field("beer.ibu").unwrap().missing, 24);
+        assert_eq!(report.field("beer.abv").unwrap().missing, 1);
+    }
+
+    #[test]
+    fn renamed_key_field_warns() {
+        let menu = MENU.replace("\"beer_name\"", "\"beerName\"");
+        let report = validate_menu(&menu).unwrap();
+        assert_eq!(
+            report.warnings,
+            vec!["beer.beer_name missing in 30 of 30 items"]
+        );
+    }
+
+    #[test]
+    fn every_date_field_is_counted() {
+        let menu = MENU.replace("\"date_added_iso8601\"", "\"dateAdded\"");
+        let report = validate_menu(&menu).unwrap();
+        assert_eq!(report.field("date_added_iso8601").unwrap().missing, 30);
+        assert_eq!(report.field("date_added_timestamp").unwrap().unusable(), 0);
+        assert_eq!(report.field("date_added").unwrap().unusable(), 0);
+    }
+
+    #[test]
+    fn mistyped_fields_are_told_apart_from_missing() {
+        let menu = mutated(10, |item| {
+            item["serving_info"]["tap_number"] = json!("7");
+            item["beer"]["abv"] = json!(null);
+        });
+        let report = validate_menu(&menu).unwrap();
+        assert_eq!(
+            report.field("serving_info.tap_number").unwrap().mistyped,
+            10
+        );
+        assert_eq!(report.field("beer.abv").unwrap().missing, 11);
+        assert_eq!(
+            report.warnings,
+            vec![
+                "serving_info.tap_number mistyped in 10 of 30 items",
+                "beer.abv missing in 11 of 30 items",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_few_gaps_or_optional_fields_stay_quiet() {
+        let drop_category = |n| {
+            mutated(n, |item| {
+                item["beer"]
+                    .as_object_mut()
+                    .unwrap()
+                    .remove("style_category");
+            })
+        };
+        // The fixture already has one blank category: 5 more make 6 of 30,
+        // exactly the threshold, and one more tips it over.
+        let report = validate_menu(&drop_category(5)).unwrap();
+        assert_eq!(report.field("beer.style_category").unwrap().missing, 6);
+        assert!(report.is_clean());
+        assert_eq!(
+            validate_menu(&drop_category(6)).unwrap().warnings,
+            vec!["beer.style_category missing in 7 of 30 items"]
+        );
+
+        let menu = mutated(30, |item| {
+            item.as_object_mut().unwrap().remove("category");
+        });
+        assert!(validate_menu(&menu).unwrap().is_clean());
+    }
+
+    #[test]
+    fn non_array_menu_is_an_error() {
+        assert!(matches!(validate_menu("{}"), Err(AppError::Parse(_))));
+        assert!(validate_menu("[]").unwrap().is_clean());
+    }
+}
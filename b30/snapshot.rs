@@ -0,0 +1,77 @@
+//
+// Last-known-good menu. Every TapHunter menu that parses is kept in KV (the
+// raw JSON plus when we fetched it), so when TapHunter is down, blocks us, or
+// changes shape, the worker serves that copy with a "stale as of" banner
+// instead of an error page. Ratings need no snapshot: they come from the KV
+// rating cache either way.
+//
+
+use crate::clock;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Value};
+
+/// KV key holding the snapshot.
+pub const KV_KEY: &str = "snapshot:taphunter";
+/// Re-save an unchanged menu at most this often, so "as of" stays close to the
+/// last good fetch without a KV write per request.
+const REFRESH_MINUTES: i64 = 15;
+
+/// A TapHunter menu JSON body that parsed, and when it was fetched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuSnapshot {
+    pub saved_at: DateTime<Utc>,
+    pub menu_json: String,
+}
+
+impl MenuSnapshot {
+    /// The stored form: `{"saved_at": <RFC 3339>, "menu": <raw JSON text>}`.
+    pub fn to_json(&self) -> String {
+        json!({
+            "saved_at": self.saved_at.to_rfc3339(),
+            "menu": self.menu_json,
+        })
+        .to_string()
+    }
+
+    /// Parse the stored form; `None` if it's unreadable.
+    pub fn from_json(json: &str) -> Option<MenuSnapshot> {
+        let value: Value = serde_json::from_str(json).ok()?;
+        Some(MenuSnapshot {
+            saved_at: clock::parse_utc(value["saved_at"].as_str()?)?,
+            menu_json: value["menu"].as_str()?.to_string(),
+        })
+    }
+}
+
+/// Whether a freshly fetched `menu_json` should replace `stored`: always when
+/// the menu changed, otherwise once the stored copy is `REFRESH_MINUTES` old.
+pub fn needs_save(stored: Option<&MenuSnapshot>, menu_json: &str, now: DateTime<Utc>) -> bool {
+    stored.is_none_or(|s| {
+        s.menu_json != menu_json || now - s.saved_at >= Duration::minutes(REFRESH_MINUTES)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_and_refreshes_on_change_or_age() {
+        let saved_at = clock::parse_utc("2026-05-20T22:00:00Z").unwrap();
+        let snapshot = MenuSnapshot {
+            saved_at,
+            menu_json: r#"{"taps": ["a \"quoted\" name"]}"#.into(),
+        };
+        assert_eq!(
+            MenuSnapshot::from_json(&snapshot.to_json()),
+            Some(snapshot.clone())
+        );
+        assert_eq!(MenuSnapshot::from_json("{}"), None);
+
+        let at = |minutes| saved_at + Duration::minutes(minutes);
+        assert!(!needs_save(Some(&snapshot), &snapshot.menu_json, at(5)));
+        assert!(needs_save(Some(&snapshot), &snapshot.menu_json, at(15)));
+        assert!(needs_save(Some(&snapshot), "{}", at(1)));
+        assert!(needs_save(None, "{}", at(0)));
+    }
+}
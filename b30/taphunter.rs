@@ -3,79 +3,201 @@
 // dependency, so it builds and tests on the host target.
 //
 
+use crate::clock::{self, VenueClock};
 use crate::error::{AppError, AppResult};
-use crate::model::BeerEntry;
-use chrono::NaiveDateTime;
+use crate::model::{parse_abv, parse_ibu, BeerEntry, Pour};
+use crate::normalize;
+use chrono::{DateTime, NaiveDate};
 use regex::Regex;
 use serde_json::Value;
 
 pub const BASE_TAPHUNTER_URL: &str = "http://www.taphunter.com/bigscreen";
 /// Beer Thirty's TapHunter venue id.
-pub const BEER_THIRTY_VENUE_ID: &str = "5469327503392768";
+pub const BEER_THIRTY_VENUE_ID: u64 = 5469327503392768;
 
 /// URL of the bigscreen HTML page that embeds the JSON endpoint.
 pub fn bigscreen_url() -> String {
     format!("{BASE_TAPHUNTER_URL}/{BEER_THIRTY_VENUE_ID}")
 }
 
-/// Extract the absolute JSON menu URL from the bigscreen page HTML. The page
-/// contains a `getJSON('json/<token>')` call whose token we resolve to a full
-/// URL.
+/// KV key remembering the last menu JSON URL found on the bigscreen page.
+pub const JSON_URL_KV_KEY: &str = "taphunter:json_url";
+
+/// How the menu JSON URL was found, most to least direct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UrlStrategy {
+    /// The page's `$.getJSON('json/<token>')` call.
+    GetJson,
+    /// A `$.ajax({url: 'json/<token>'})` call.
+    Ajax,
+    /// A `fetch('json/<token>')` call.
+    Fetch,
+    /// A full `https://www.taphunter.com/bigscreen/json/<token>` anywhere.
+    AbsoluteUrl,
+    /// Any other quoted `json/<token>` path in a script.
+    JsonPath,
+    /// Not on the page: the last URL found there (from KV).
+    CachedUrl,
+    /// Not on the page: the token derived from the venue id.
+    VenueKey,
+}
+
+impl UrlStrategy {
+    pub fn label(self) -> &'static str {
+        match self {
+            UrlStrategy::GetJson => "getJSON",
+            UrlStrategy::Ajax => "$.ajax",
+            UrlStrategy::Fetch => "fetch",
+            UrlStrategy::AbsoluteUrl => "absolute URL",
+            UrlStrategy::JsonPath => "json/ path",
+            UrlStrategy::CachedUrl => "cached URL",
+            UrlStrategy::VenueKey => "venue key",
+        }
+    }
+}
+
+/// Page strategies in the order tried, each a regex whose first group is the
+/// URL (relative to the bigscreen page, or absolute).
+const PAGE_STRATEGIES: [(UrlStrategy, &str); 5] = [
+    (
+        UrlStrategy::GetJson,
+        r#"getJSON\(\s*['"]((?:\./)?json/[^'"]+)['"]"#,
+    ),
+    (
+        UrlStrategy::Ajax,
+        r#"\$\.ajax\(\s*\{[^}]*?\burl\s*:\s*['"]([^'"]*json/[^'"]+)['"]"#,
+    ),
+    (
+        UrlStrategy::Fetch,
+        r#"\bfetch\(\s*['"`]([^'"`]*json/[^'"`]+)['"`]"#,
+    ),
+    (
+        UrlStrategy::AbsoluteUrl,
+        r#"(https?://(?:www\.)?taphunter\.com/bigscreen/json/[A-Za-z0-9_-]+)"#,
+    ),
+    (
+        UrlStrategy::JsonPath,
+        r#"['"]((?:\./|/bigscreen/)?json/[A-Za-z0-9_-]+)['"]"#,
+    ),
+];
+
+/// Whether `url` is on TapHunter (`taphunter.com` or a subdomain). Most page
+/// strategies take any URL with `json/` in it, and the one found is fetched
+/// and cached, so a URL anywhere else is never trusted.
+pub fn is_taphunter_url(url: &str) -> bool {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        .is_some_and(|host| host == "taphunter.com" || host.ends_with(".taphunter.com"))
+}
+
+/// Find the menu JSON URL in the bigscreen page HTML, trying each of
+/// `PAGE_STRATEGIES` in turn, and say which one worked. A match pointing off
+/// TapHunter (see `is_taphunter_url`) doesn't count.
+pub fn discover_json_url(html: &str) -> AppResult<(String, UrlStrategy)> {
+    let base = url::Url::parse(&bigscreen_url())
+        .map_err(|e| AppError::Internal(format!("Bad bigscreen URL: {e}")))?;
+    for (strategy, pattern) in PAGE_STRATEGIES {
+        let re = Regex::new(pattern)
+            .map_err(|e| AppError::Parse(format!("Regex creation failed: {e}")))?;
+        let found = re
+            .captures(html)
+            .and_then(|c| c.get(1))
+            .and_then(|path| base.join(path.as_str()).ok())
+            .filter(|url| is_taphunter_url(url.as_str()));
+        if let Some(url) = found {
+            return Ok((url.to_string(), strategy));
+        }
+    }
+    Err(AppError::Parse("Could not find the menu JSON URL".into()))
+}
+
+/// Extract the absolute JSON menu URL from the bigscreen page HTML (see
+/// `discover_json_url`).
 pub fn parse_json_url(html: &str) -> AppResult<String> {
-    let re = Regex::new(r#"getJSON\(['"](\./)?json/([^'"]+)['"]"#)
-        .map_err(|e| AppError::Parse(format!("Regex creation failed: {e}")))?;
-
-    let captures = re
-        .captures(html)
-        .ok_or_else(|| AppError::Parse("Could not find getJSON URL".into()))?;
-    let relative_path = captures
-        .get(2)
-        .ok_or_else(|| AppError::Parse("Failed to capture JSON path".into()))?
-        .as_str();
-
-    Ok(format!("{BASE_TAPHUNTER_URL}/json/{relative_path}"))
+    discover_json_url(html).map(|(url, _)| url)
+}
+
+/// The menu JSON URL for a venue without asking the bigscreen page. The page's
+/// token is the App Engine datastore key of the venue's `taphunter_location`
+/// entity, which we can build from the venue id: a protobuf `Reference` (app,
+/// then a path of one kind/id element), base64url-encoded without padding.
+pub fn venue_json_url(venue_id: u64) -> String {
+    const APP: &str = "s~thetaphunter-hrd";
+    const KIND: &str = "taphunter_location";
+    let mut key = vec![0x6a, APP.len() as u8];
+    key.extend_from_slice(APP.as_bytes());
+    let mut element = vec![0x0b, 0x12, KIND.len() as u8];
+    element.extend_from_slice(KIND.as_bytes());
+    element.push(0x18);
+    let mut id = venue_id;
+    loop {
+        let byte = (id & 0x7f) as u8;
+        id >>= 7;
+        if id == 0 {
+            element.push(byte);
+            break;
+        }
+        element.push(byte | 0x80);
+    }
+    element.push(0x0c);
+    key.push(0x72);
+    key.push(element.len() as u8);
+    key.extend_from_slice(&element);
+    format!("{BASE_TAPHUNTER_URL}/json/{}", base64url(&key))
 }
 
-/// Parse the TapHunter menu JSON into beer entries.
-pub fn parse_menu(json: &str) -> AppResult<Vec<BeerEntry>> {
+/// Unpadded base64url.
+fn base64url(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+        }
+    }
+    out
+}
+
+/// Parse the TapHunter menu JSON into beer entries. `clock` supplies the venue
+/// timezone (keg dates are localized to it) and "now" (for rejecting dates in
+/// the future).
+pub fn parse_menu(json: &str, clock: &VenueClock) -> AppResult<Vec<BeerEntry>> {
     let items: Vec<Value> = serde_json::from_str(json)
         .map_err(|e| AppError::Parse(format!("Failed to parse menu JSON: {e}")))?;
 
     let mut entries = Vec::with_capacity(items.len());
     for item in items {
-        let date_str = item["date_added"].as_str().unwrap_or("");
-        let days_old = calculate_days_old(date_str).unwrap_or(0);
-
-        let abv = clean_text(item["beer"]["abv"].as_str().unwrap_or(""));
         let mut entry = BeerEntry {
             tap_number: item["serving_info"]["tap_number"].as_i64().unwrap_or(0) as i32,
             brewery: clean_text(item["brewery"]["common_name"].as_str().unwrap_or("")),
+            brewery_full: clean_text(item["brewery"]["name"].as_str().unwrap_or("")),
             name: clean_text(item["beer"]["beer_name"].as_str().unwrap_or("")),
-            abv: if abv.is_empty() {
-                "0.0".to_string()
-            } else {
-                abv
-            },
+            full_name: clean_text(item["beer"]["name"].as_str().unwrap_or("")),
+            abv: parse_abv(&scalar_text(&item["beer"]["abv"])),
+            ibu: parse_ibu(&scalar_text(&item["beer"]["ibu"])),
             category: clean_text(item["beer"]["style_category"].as_str().unwrap_or("")),
+            category_order: scalar_text(&item["category"]["order"]).parse().ok(),
             origin: clean_text(item["brewery"]["origin"].as_str().unwrap_or("")),
             style: clean_text(item["beer"]["style"].as_str().unwrap_or("")),
-            days_old: days_old as i32,
+            date_added: parse_date_added(&item, clock),
+            pours: parse_pours(&item),
         };
 
         // Strip "**Nitro**" markers used to flag nitro taps.
         entry.brewery = entry.brewery.replace("**Nitro**", "").trim().to_string();
-        entry.name = entry
-            .name
-            .replace("**NITRO**", "")
-            .replace("**Nitro**", "")
-            .replace("NITRO", "")
-            .replace("Nitro", "")
-            .trim()
-            .to_string();
-
-        // Remove the brewery name from the beer name when duplicated.
+        entry.name = strip_nitro(&entry.name);
+        entry.full_name = strip_nitro(&entry.full_name);
+
+        // Fix known brewery misspellings, then remove a (possibly differently
+        // spelled) leading brewery mention from the beer name.
+        entry.brewery = normalize::canonical_brewery(&entry.brewery).to_string();
         if !entry.brewery.is_empty() {
-            entry.name = entry.name.replace(&entry.brewery, "").trim().to_string();
+            entry.name = normalize::strip_brewery(&entry.name, &entry.brewery);
         }
 
         entries.push(entry);
@@ -84,6 +206,15 @@ pub fn parse_menu(json: &str) -> AppResult<Vec<BeerEntry>> {
     Ok(entries)
 }
 
+fn strip_nitro(name: &str) -> String {
+    name.replace("**NITRO**", "")
+        .replace("**Nitro**", "")
+        .replace("NITRO", "")
+        .replace("Nitro", "")
+        .trim()
+        .to_string()
+}
+
 /// Collapse runs of whitespace and tidy up stray spaces before commas.
 pub(crate) fn clean_text(text: &str) -> String {
     text.split_whitespace()
@@ -94,20 +225,73 @@ pub(crate) fn clean_text(text: &str) -> String {
         .to_string()
 }
 
-/// Days between `date_str` (MM/DD/YYYY) and now.
-fn calculate_days_old(date_str: &str) -> AppResult<i64> {
-    NaiveDateTime::parse_from_str(&format!("{date_str} 00:00:00"), "%m/%d/%Y %H:%M:%S")
-        .map_err(|e| AppError::Parse(format!("Failed to parse date: {e}")))
-        .map(|date| {
-            let now = chrono::Local::now().naive_local();
-            (now - date).num_days()
-        })
+/// TapHunter numeric fields arrive as strings ("10.2") but occasionally as bare
+/// numbers; either way hand back the text to validate.
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Pour sizes from `serving_info.sized_pricing`, smallest (by ounces) first;
+/// sizes we can't measure keep their menu order after those we can.
+fn parse_pours(item: &Value) -> Vec<Pour> {
+    let mut pours: Vec<Pour> = item["serving_info"]["sized_pricing"]
+        .as_array()
+        .map_or(&[][..], Vec::as_slice)
+        .iter()
+        .map(|p| Pour::parse(&scalar_text(&p["size"]), &scalar_text(&p["price"])))
+        .filter(|p| !p.size.is_empty())
+        .collect();
+    pours.sort_by(|a, b| match (a.oz, b.oz) {
+        (Some(a), Some(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    pours
+}
+
+/// Furthest in the future a keg date may be before we call it bad data (a day
+/// covers TapHunter's own clock being slightly ahead).
+const MAX_FUTURE_DAYS: i64 = 1;
+
+/// The day the keg went on, in the venue's timezone. TapHunter's `date_added`
+/// (MM/DD/YYYY) is the *UTC* date — a keg tapped at 6pm Pacific shows up as
+/// tomorrow — so prefer localizing the `date_added_iso8601` /
+/// `date_added_timestamp` instant, and only fall back to the plain date.
+fn parse_date_added(item: &Value, clock: &VenueClock) -> Option<NaiveDate> {
+    let instant = item["date_added_iso8601"]
+        .as_str()
+        .and_then(clock::parse_utc)
+        .or_else(|| {
+            let secs = item["date_added_timestamp"].as_f64()?;
+            DateTime::from_timestamp(secs as i64, 0)
+        });
+    let date = match instant {
+        Some(instant) => clock.local_date(instant),
+        None => {
+            let raw = item["date_added"].as_str().unwrap_or("").trim();
+            NaiveDate::parse_from_str(raw, "%m/%d/%Y").ok()?
+        }
+    };
+    ((date - clock.today()).num_days() <= MAX_FUTURE_DAYS).then_some(date)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Shortly after midnight Pacific on June 28, 2026 (the fixture's capture).
+    fn fixture_clock() -> VenueClock {
+        VenueClock::at(
+            clock::parse_utc("2026-06-28T07:30:00Z").unwrap(),
+            clock::PACIFIC,
+        )
+    }
+
     #[test]
     fn parses_json_url_from_bigscreen_fixture() {
         let html = include_str!("fixtures/taphunter_bigscreen.html");
@@ -121,21 +305,157 @@ mod tests {
         assert!(parse_json_url("<html>no script here</html>").is_err());
     }
 
+    const FIXTURE_TOKEN: &str =
+        "ahJzfnRoZXRhcGh1bnRlci1ocmRyHwsSEnRhcGh1bnRlcl9sb2NhdGlvbhiAgIDYsMrbCQw";
+
+    /// The bigscreen fixture with its `$.getJSON(...)` call swapped for `loader`.
+    fn bigscreen_loading_with(loader: &str) -> String {
+        let html = include_str!("fixtures/taphunter_bigscreen.html");
+        let call = format!("$.getJSON('./json/{FIXTURE_TOKEN}', function(beers) {{");
+        assert!(html.contains(&call));
+        html.replace(&call, loader)
+    }
+
+    #[test]
+    fn discovers_the_json_url_by_each_page_strategy() {
+        let expected = format!("http://www.taphunter.com/bigscreen/json/{FIXTURE_TOKEN}");
+        let cases = [
+            (
+                format!("$.getJSON('./json/{FIXTURE_TOKEN}', function(beers) {{"),
+                UrlStrategy::GetJson,
+            ),
+            (
+                format!("$.ajax({{ dataType: 'json', url: \"json/{FIXTURE_TOKEN}\", success: function(beers) {{"),
+                UrlStrategy::Ajax,
+            ),
+            (
+                format!("fetch('/bigscreen/json/{FIXTURE_TOKEN}').then(r => r.json()).then(function(beers) {{"),
+                UrlStrategy::Fetch,
+            ),
+            (
+                format!("loadMenu('https://www.taphunter.com/bigscreen/json/{FIXTURE_TOKEN}', function(beers) {{"),
+                UrlStrategy::AbsoluteUrl,
+            ),
+            (
+                format!("var menuUrl = './json/{FIXTURE_TOKEN}'; $.get(menuUrl, function(beers) {{"),
+                UrlStrategy::JsonPath,
+            ),
+        ];
+        for (loader, strategy) in cases {
+            let (url, found_by) = discover_json_url(&bigscreen_loading_with(&loader)).unwrap();
+            assert_eq!(found_by, strategy, "{loader}");
+            let expected = if strategy == UrlStrategy::AbsoluteUrl {
+                expected.replace("http://", "https://")
+            } else {
+                expected.clone()
+            };
+            assert_eq!(url, expected, "{loader}");
+        }
+    }
+
+    #[test]
+    fn json_urls_off_taphunter_are_ignored() {
+        let elsewhere = format!(
+            "$.ajax({{ url: 'https://evil.example/json/{FIXTURE_TOKEN}' }}); \
+             fetch('//taphunter.com.evil.example/json/x'); \
+             loadMenu('https://www.taphunter.com/bigscreen/json/{FIXTURE_TOKEN}', function(beers) {{"
+        );
+        let (url, found_by) = discover_json_url(&bigscreen_loading_with(&elsewhere)).unwrap();
+        assert_eq!(found_by, UrlStrategy::AbsoluteUrl);
+        assert!(url.starts_with("https://www.taphunter.com/"), "{url}");
+
+        let only_elsewhere = bigscreen_loading_with("fetch('https://evil.example/json/abc');");
+        assert!(discover_json_url(&only_elsewhere).is_err());
+        assert!(is_taphunter_url("https://TapHunter.com/bigscreen/json/x"));
+        assert!(!is_taphunter_url("not a url"));
+    }
+
+    #[test]
+    fn venue_key_rebuilds_the_page_token() {
+        assert_eq!(
+            venue_json_url(BEER_THIRTY_VENUE_ID),
+            format!("http://www.taphunter.com/bigscreen/json/{FIXTURE_TOKEN}")
+        );
+        assert_eq!(base64url(b"ab"), "YWI");
+        assert_eq!(base64url(b"\xfb\xff"), "-_8");
+    }
+
     #[test]
     fn parses_menu_fixture() {
         let json = include_str!("fixtures/taphunter_menu.json");
-        let entries = parse_menu(json).expect("should parse menu");
+        let entries = parse_menu(json, &fixture_clock()).expect("should parse menu");
         assert!(!entries.is_empty(), "fixture has taps");
 
         let first = &entries[0];
         assert!(!first.brewery.is_empty());
         assert!(!first.name.is_empty());
-        // ABV is always populated (defaults to "0.0").
-        assert!(!first.abv.is_empty());
+        // ABV and the keg date are typed; the fixture's first tap has both.
+        assert_eq!(first.abv, Some(10.2));
+        assert_eq!(first.ibu, Some(30));
+        assert_eq!(first.date_added, NaiveDate::from_ymd_opt(2026, 5, 20));
+        // Pours from `sized_pricing`, taster first.
+        let sizes: Vec<(&str, Option<f64>)> = first
+            .pours
+            .iter()
+            .map(|p| (p.size.as_str(), p.price))
+            .collect();
+        assert_eq!(sizes, [("5 oz", Some(5.0)), ("10 oz", Some(10.0))]);
+        // A blank menu ABV is unknown, not 0.0.
+        let kombucha = entries.iter().find(|e| e.tap_number == 30).unwrap();
+        assert_eq!(kombucha.abv, None);
         // Every entry parsed a tap number.
         assert!(entries.iter().all(|e| e.tap_number >= 0));
     }
 
+    #[test]
+    fn strips_variant_brewery_prefixes_from_names() {
+        let json = include_str!("fixtures/taphunter_menu.json");
+        let entries = parse_menu(json, &fixture_clock()).expect("should parse menu");
+        let by_tap = |tap: i32| entries.iter().find(|e| e.tap_number == tap).unwrap();
+        // "Alvarado Brewery Pink Limo Dads" under "Alvarado Street Brewery".
+        assert_eq!(by_tap(8).name, "Pink Limo Dads");
+        // Menu typo "Racho West" is aliased, then stripped from the name.
+        assert_eq!(by_tap(29).brewery, "Rancho West");
+        assert_eq!(by_tap(29).name, "NON-ALC Lager");
+        // The full names are kept alongside for the search variants.
+        assert_eq!(by_tap(28).full_name, "Allagash Curieux");
+        assert_eq!(by_tap(28).brewery_full, "Allagash Brewing Company");
+    }
+
+    #[test]
+    fn keg_dates_are_localized_to_the_venue() {
+        let json = include_str!("fixtures/taphunter_menu.json");
+        let clock = fixture_clock();
+        let entries = parse_menu(json, &clock).unwrap();
+        let by_tap = |tap: i32| entries.iter().find(|e| e.tap_number == tap).unwrap();
+        let date = |m, d| NaiveDate::from_ymd_opt(2026, m, d);
+
+        // Tapped 2026-06-27T01:22Z = 6:22pm Pacific on the 26th (TapHunter's
+        // plain `date_added` says 06/27, the UTC date).
+        assert_eq!(by_tap(6).date_added, date(6, 26));
+        assert_eq!(by_tap(6).days_old(clock.today()), Some(2));
+        // Midday taps are the same date either way.
+        assert_eq!(by_tap(28).date_added, date(5, 20));
+        assert_eq!(by_tap(28).days_old(clock.today()), Some(39));
+
+        // An hour earlier it's still the 27th in Santa Cruz: one day younger.
+        let before_midnight = VenueClock::at(
+            clock::parse_utc("2026-06-28T06:30:00Z").unwrap(),
+            clock::PACIFIC,
+        );
+        assert_eq!(by_tap(28).days_old(before_midnight.today()), Some(38));
+    }
+
+    #[test]
+    fn future_keg_dates_are_rejected() {
+        let json = r#"[{"date_added": "07/04/2026", "beer": {}, "brewery": {}, "serving_info": {}},
+                       {"date_added": "06/29/2026", "beer": {}, "brewery": {}, "serving_info": {}}]"#;
+        let entries = parse_menu(json, &fixture_clock()).unwrap();
+        assert_eq!(entries[0].date_added, None);
+        // Plain dates (no timestamp) still parse; a day ahead is tolerated.
+        assert_eq!(entries[1].date_added, NaiveDate::from_ymd_opt(2026, 6, 29));
+    }
+
     #[test]
     fn clean_text_collapses_whitespace() {
         assert_eq!(
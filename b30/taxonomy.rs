@@ -0,0 +1,419 @@
+//
+// Style taxonomy: maps TapHunter's coarse, inconsistent `style_category`
+// ("Strong Ales") and Untappd's fine-grained `type_name` ("IPA - New England /
+// Hazy") into one stable two-level hierarchy (IPA > Hazy, Sour > Gose, ...).
+// Used to group and filter the menu and to pool per-style statistics. The
+// mapping itself is data (`data/style_taxonomy.json`), and a venue can extend
+// it at runtime with the same JSON shape (see `Taxonomy::extended`).
+//
+
+use crate::error::{AppError, AppResult};
+use crate::model::RatedBeer;
+use crate::normalize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// The built-in mapping.
+const BUILTIN_JSON: &str = include_str!("data/style_taxonomy.json");
+
+/// Joins family and subfamily in a group label ("IPA / Hazy").
+pub const SUBFAMILY_SEPARATOR: &str = " / ";
+
+/// Family for a beer nothing in the taxonomy recognizes.
+const OTHER_FAMILY: &str = "Other";
+
+/// Where a beer sits in the hierarchy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleFamily {
+    /// Broad family ("IPA").
+    pub family: String,
+    /// Narrower style within it ("Hazy"), when one is known.
+    pub sub: Option<String>,
+}
+
+impl StyleFamily {
+    /// "IPA / Hazy", or just "IPA" without a subfamily.
+    pub fn label(&self) -> String {
+        match &self.sub {
+            Some(sub) => format!("{}{SUBFAMILY_SEPARATOR}{sub}", self.family),
+            None => self.family.clone(),
+        }
+    }
+
+    /// True if a filter term names this family or subfamily: "ipa", "hazy",
+    /// "hazy ipa" and "IPA / Hazy" all match IPA > Hazy.
+    pub fn matches(&self, term: &str) -> bool {
+        let term = normalize::normalize(term);
+        let family = normalize::normalize(&self.family);
+        if term.is_empty() {
+            return false;
+        }
+        if term == family {
+            return true;
+        }
+        self.sub.as_deref().is_some_and(|sub| {
+            let sub = normalize::normalize(sub);
+            term == sub || term == format!("{sub} {family}") || term == format!("{family} {sub}")
+        })
+    }
+}
+
+/// The family half of a group label ("IPA / Hazy" → "IPA"); labels without a
+/// subfamily are returned unchanged.
+pub fn family_part(label: &str) -> &str {
+    label
+        .split_once(SUBFAMILY_SEPARATOR)
+        .map_or(label, |(family, _)| family)
+}
+
+/// One mapping rule: matches when the style text contains any `any` phrase and
+/// every `all` phrase, as whole normalized words.
+#[derive(Debug, Clone)]
+struct Rule {
+    family: String,
+    sub: Option<String>,
+    any: Vec<String>,
+    all: Vec<String>,
+}
+
+impl Rule {
+    fn matches(&self, padded: &str) -> bool {
+        let has = |phrase: &String| padded.contains(&format!(" {phrase} "));
+        self.any.iter().any(has) && self.all.iter().all(has)
+    }
+}
+
+/// An ordered rule list plus a `style_category` fallback table.
+#[derive(Debug, Clone)]
+pub struct Taxonomy {
+    rules: Vec<Rule>,
+    /// Normalized TapHunter category → family.
+    categories: HashMap<String, String>,
+}
+
+impl Taxonomy {
+    /// The mapping shipped in `data/style_taxonomy.json`.
+    pub fn builtin() -> Taxonomy {
+        Taxonomy::from_json(BUILTIN_JSON).expect("built-in style taxonomy is valid")
+    }
+
+    /// Parse a taxonomy document: `{"rules": [{"family", "sub"?, "any": [..],
+    /// "all"?: [..]}], "categories": {"<style_category>": "<family>"}}`. Both
+    /// keys are optional.
+    pub fn from_json(json: &str) -> AppResult<Taxonomy> {
+        let doc: Value = serde_json::from_str(json)
+            .map_err(|e| AppError::Parse(format!("Failed to parse style taxonomy: {e}")))?;
+        let phrases = |value: &Value| -> Vec<String> {
+            value
+                .as_array()
+                .map(|a| {
+                    a.iter()
+                        .filter_map(Value::as_str)
+                        .map(normalize::normalize)
+                        .filter(|p| !p.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mut rules = Vec::new();
+        for rule in doc["rules"].as_array().map_or(&[][..], Vec::as_slice) {
+            let family = rule["family"]
+                .as_str()
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .ok_or_else(|| AppError::Parse("Style taxonomy rule has no family".into()))?;
+            let any = phrases(&rule["any"]);
+            if any.is_empty() {
+                return Err(AppError::Parse(format!(
+                    "Style taxonomy rule for {family} has no `any` phrases"
+                )));
+            }
+            rules.push(Rule {
+                family: family.to_string(),
+                sub: rule["sub"].as_str().map(|s| s.trim().to_string()),
+                any,
+                all: phrases(&rule["all"]),
+            });
+        }
+
+        let categories = doc["categories"]
+            .as_object()
+            .map(|m| {
+                m.iter()
+                    .filter_map(|(category, family)| {
+                        Some((normalize::normalize(category), family.as_str()?.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(Taxonomy { rules, categories })
+    }
+
+    /// This taxonomy with `extra` layered on top: its rules are tried first and
+    /// its category mappings win.
+    pub fn extended(mut self, extra: Taxonomy) -> Taxonomy {
+        let mut rules = extra.rules;
+        rules.append(&mut self.rules);
+        self.rules = rules;
+        self.categories.extend(extra.categories);
+        self
+    }
+
+    /// Classify free-form style text by the first matching rule.
+    pub fn classify_style(&self, style: &str) -> Option<StyleFamily> {
+        let padded = format!(" {} ", normalize::normalize(style));
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(&padded))
+            .map(|rule| StyleFamily {
+                family: rule.family.clone(),
+                sub: rule.sub.clone(),
+            })
+    }
+
+    /// Classify a menu beer: Untappd's style (most specific) first, then the
+    /// menu's style, then its TapHunter category. An unmapped category becomes
+    /// its own family so venue-specific sections survive regrouping.
+    pub fn classify(&self, beer: &RatedBeer) -> StyleFamily {
+        let untappd = beer
+            .rating
+            .as_ref()
+            .and_then(|r| r.details.style.as_deref());
+        if let Some(family) = untappd
+            .into_iter()
+            .chain([beer.entry.style.as_str()])
+            .find_map(|style| self.classify_style(style))
+        {
+            return family;
+        }
+        let category = beer.entry.category.trim();
+        let family = self
+            .categories
+            .get(&normalize::normalize(category))
+            .map(String::as_str)
+            .unwrap_or(if category.is_empty() {
+                OTHER_FAMILY
+            } else {
+                category
+            });
+        StyleFamily {
+            family: family.to_string(),
+            sub: None,
+        }
+    }
+}
+
+/// What the menu's category column groups by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GroupBy {
+    /// TapHunter's `style_category`, as-is.
+    #[default]
+    Category,
+    /// Taxonomy family ("IPA").
+    Family,
+    /// Family and subfamily ("IPA / Hazy").
+    Subfamily,
+}
+
+impl GroupBy {
+    /// Parse a config/query value: "category", "family" or "subfamily".
+    pub fn parse(value: &str) -> Option<GroupBy> {
+        match value.trim() {
+            "category" => Some(GroupBy::Category),
+            "family" => Some(GroupBy::Family),
+            "subfamily" => Some(GroupBy::Subfamily),
+            _ => None,
+        }
+    }
+}
+
+/// Taxonomy-driven view of the menu: regrouping plus an optional style filter.
+#[derive(Debug, Clone, Default)]
+pub struct StyleView {
+    pub group_by: GroupBy,
+    /// Keep only beers matching one of these terms (see `StyleFamily::matches`).
+    pub filter: Vec<String>,
+}
+
+impl StyleView {
+    /// Override from a request query string (`group=family&style=hazy,gose`).
+    /// Unknown keys and values are ignored.
+    pub fn with_query(mut self, query: &str) -> StyleView {
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "group" => {
+                    if let Some(group_by) = GroupBy::parse(&value) {
+                        self.group_by = group_by;
+                    }
+                }
+                "style" => {
+                    self.filter = value
+                        .split(',')
+                        .map(|t| t.trim().to_string())
+                        .filter(|t| !t.is_empty())
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        self
+    }
+
+    /// Filter `beers` and, unless grouping by category, replace each beer's
+    /// category with its family (or family / subfamily) label so sorting,
+    /// rendering and the per-category statistics all follow the taxonomy.
+    pub fn apply(&self, beers: Vec<RatedBeer>, taxonomy: &Taxonomy) -> Vec<RatedBeer> {
+        beers
+            .into_iter()
+            .filter_map(|mut b| {
+                let family = taxonomy.classify(&b);
+                if !self.filter.is_empty() && !self.filter.iter().any(|t| family.matches(t)) {
+                    return None;
+                }
+                match self.group_by {
+                    GroupBy::Category => {}
+                    GroupBy::Family => b.entry.category = family.family,
+                    GroupBy::Subfamily => b.entry.category = family.label(),
+                }
+                Some(b)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{BeerDetails, BeerEntry, LookupOutcome, RatingResult};
+
+    fn beer(category: &str, style: &str, untappd_style: Option<&str>) -> RatedBeer {
+        RatedBeer {
+            entry: BeerEntry {
+                tap_number: 1,
+                brewery: "B".into(),
+                brewery_full: "B".into(),
+                name: "N".into(),
+                full_name: "B N".into(),
+                abv: Some(6.0),
+                ibu: None,
+                category: category.into(),
+                category_order: None,
+                origin: String::new(),
+                style: style.into(),
+                date_added: None,
+                pours: Vec::new(),
+            },
+            rating: untappd_style.map(|style| RatingResult {
+                rating: "3.90".into(),
+                url: String::new(),
+                rating_count: 100,
+                matched_by: None,
+                details: BeerDetails {
+                    style: Some(style.into()),
+                    ..Default::default()
+                },
+                fetched_at: None,
+            }),
+            outcome: LookupOutcome::Cached,
+        }
+    }
+
+    fn label(taxonomy: &Taxonomy, b: &RatedBeer) -> String {
+        taxonomy.classify(b).label()
+    }
+
+    #[test]
+    fn maps_untappd_and_menu_styles_into_families() {
+        let t = Taxonomy::builtin();
+        let cases = [
+            ("IPA - New England / Hazy", "IPA / Hazy"),
+            ("IPA - Imperial / Double New England / Hazy", "IPA / Double"),
+            ("IPA - American", "IPA / West Coast"),
+            ("Sour - Fruited Gose", "Sour / Gose"),
+            ("Sour - Berliner Weisse", "Sour / Berliner"),
+            (
+                "Stout - Imperial / Double Pastry",
+                "Stout & Porter / Imperial Stout",
+            ),
+            ("Pilsner - German", "Lager / Pilsner"),
+            ("Märzen", "Lager / Amber"),
+            ("Pale Ale - American", "Pale Ale"),
+            ("Barrel-Aged Belgian Tripel", "Belgian & Farmhouse / Tripel"),
+        ];
+        for (style, expected) in cases {
+            let family = t.classify_style(style).map(|f| f.label());
+            assert_eq!(family.as_deref(), Some(expected), "{style}");
+        }
+        // Whole words only: "Capital" is not an "apa".
+        assert_eq!(t.classify_style("Capital Special"), None);
+    }
+
+    #[test]
+    fn prefers_untappd_then_menu_style_then_category() {
+        let t = Taxonomy::builtin();
+        let both = beer("IPAs", "IPA", Some("IPA - New England / Hazy"));
+        assert_eq!(label(&t, &both), "IPA / Hazy");
+        let menu_only = beer("Sour and Fruit Beers", "Gose", None);
+        assert_eq!(label(&t, &menu_only), "Sour / Gose");
+        let category_only = beer("Porters and Stouts", "", None);
+        assert_eq!(label(&t, &category_only), "Stout & Porter");
+        let unknown = beer("Guest Taps", "Mystery", None);
+        assert_eq!(label(&t, &unknown), "Guest Taps");
+        assert_eq!(label(&t, &beer("", "", None)), "Other");
+    }
+
+    #[test]
+    fn extensions_take_precedence() {
+        let extra = Taxonomy::from_json(
+            r#"{"rules": [{"family": "IPA", "sub": "Cold", "any": ["cold"]}],
+                "categories": {"Guest Taps": "Guest"}}"#,
+        )
+        .unwrap();
+        let t = Taxonomy::builtin().extended(extra);
+        assert_eq!(
+            t.classify_style("Cold IPA Lager").map(|f| f.label()),
+            Some("IPA / Cold".to_string())
+        );
+        assert_eq!(label(&t, &beer("Guest Taps", "", None)), "Guest");
+        // The built-in rules still apply underneath.
+        assert_eq!(
+            t.classify_style("Gose").map(|f| f.label()),
+            Some("Sour / Gose".to_string())
+        );
+        assert!(Taxonomy::from_json(r#"{"rules": [{"family": "IPA"}]}"#).is_err());
+        assert!(Taxonomy::from_json("not json").is_err());
+    }
+
+    #[test]
+    fn view_filters_and_regroups() {
+        let t = Taxonomy::builtin();
+        let menu = || {
+            vec![
+                beer("IPAs", "Hazy IPA", None),
+                beer("IPAs", "West Coast IPA", None),
+                beer("Sour and Fruit Beers", "Gose", None),
+                beer("Porters and Stouts", "Milk Stout", None),
+            ]
+        };
+        let categories = |beers: &[RatedBeer]| -> Vec<String> {
+            beers.iter().map(|b| b.entry.category.clone()).collect()
+        };
+
+        let hazy = StyleView::default()
+            .with_query("style=hazy+ipa,gose&group=subfamily")
+            .apply(menu(), &t);
+        assert_eq!(categories(&hazy), ["IPA / Hazy", "Sour / Gose"]);
+
+        let families = StyleView::default()
+            .with_query("group=family&style=ipa")
+            .apply(menu(), &t);
+        assert_eq!(categories(&families), ["IPA", "IPA"]);
+
+        let unchanged = StyleView::default().apply(menu(), &t);
+        assert_eq!(categories(&unchanged)[0], "IPAs");
+        assert_eq!(family_part("IPA / Hazy"), "IPA");
+        assert_eq!(family_part("Strong Ales"), "Strong Ales");
+    }
+}
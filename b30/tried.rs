@@ -0,0 +1,369 @@
+//
+// Per-user "tried it" lists. A user is just an opaque token kept in a cookie
+// (shareable as `?user=<token>` to carry the list to another device); their
+// list is the set of Untappd beer ids (`bid`s) they've had, stored in KV by the
+// worker glue. Lists can be seeded from an Untappd check-in export (CSV or
+// JSON). The token is random, so a list can't be found by guessing, but it is
+// not an auth mechanism: anyone holding it can see and edit that list.
+//
+
+use crate::error::{AppError, AppResult};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+/// Cookie carrying the user token.
+pub const USER_COOKIE: &str = "b30_user";
+/// Cookie lifetime (a year; every menu visit refreshes it).
+const COOKIE_MAX_AGE_SECONDS: u64 = 365 * 24 * 60 * 60;
+
+/// True for a token we minted or would accept from a `?user=` link:
+/// 16-64 ASCII letters, digits, '-' or '_'.
+pub fn valid_user_id(id: &str) -> bool {
+    (16..=64).contains(&id.len())
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// The user token from a `Cookie` request header, if present and well-formed.
+pub fn user_from_cookie(header: &str) -> Option<String> {
+    header
+        .split(';')
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == USER_COOKIE)
+        .map(|(_, value)| value.trim().to_string())
+        .filter(|id| valid_user_id(id))
+}
+
+/// Whose list a request acts on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Identity {
+    /// The browser's cookie. `offered` is a different token from a `?user=`
+    /// link, which only replaces the cookie through an explicit switch.
+    Cookie {
+        user: String,
+        offered: Option<String>,
+    },
+    /// A `?user=` link's token, adopted by a browser without a cookie.
+    Shared(String),
+    /// Neither: the caller mints a fresh token.
+    New,
+}
+
+/// Resolve a request's identity from its `Cookie` header and `?user=` value.
+/// An existing cookie always wins, so following someone's shared link can't
+/// silently swap out this browser's list.
+pub fn identify(cookie_header: Option<&str>, shared: Option<&str>) -> Identity {
+    let shared = shared.filter(|u| valid_user_id(u)).map(str::to_string);
+    match cookie_header.and_then(user_from_cookie) {
+        Some(user) => Identity::Cookie {
+            offered: shared.filter(|s| *s != user),
+            user,
+        },
+        None => shared.map_or(Identity::New, Identity::Shared),
+    }
+}
+
+/// `Set-Cookie` value persisting `user`.
+pub fn set_cookie(user: &str) -> String {
+    format!(
+        "{USER_COOKIE}={user}; Path=/; Max-Age={COOKIE_MAX_AGE_SECONDS}; SameSite=Lax; Secure; HttpOnly"
+    )
+}
+
+/// A 32-hex-digit token from 128 random bits (the worker passes bytes from
+/// `crypto.getRandomValues`). Being unguessable is what keeps lists private;
+/// see the module note.
+pub fn new_user_id(random: [u8; 16]) -> String {
+    random.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// KV key holding `user`'s list.
+pub fn kv_key(user: &str) -> String {
+    format!("tried:{user}")
+}
+
+/// The set of Untappd beer ids a user has tried.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TriedList {
+    bids: BTreeSet<u64>,
+}
+
+impl TriedList {
+    /// Parse the stored form (a JSON array of bids). Anything unreadable is an
+    /// empty list rather than an error, so a bad entry can't break the menu.
+    pub fn from_json(json: &str) -> TriedList {
+        let bids = serde_json::from_str::<Value>(json)
+            .ok()
+            .and_then(|v| {
+                v.as_array()
+                    .map(|a| a.iter().filter_map(Value::as_u64).collect())
+            })
+            .unwrap_or_default();
+        TriedList { bids }
+    }
+
+    /// The stored form: a sorted JSON array of bids.
+    pub fn to_json(&self) -> String {
+        Value::from(self.bids.iter().copied().collect::<Vec<u64>>()).to_string()
+    }
+
+    pub fn contains(&self, bid: u64) -> bool {
+        self.bids.contains(&bid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.bids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty()
+    }
+
+    /// Flip `bid`; returns whether it is now marked tried.
+    pub fn toggle(&mut self, bid: u64) -> bool {
+        if self.bids.remove(&bid) {
+            false
+        } else {
+            self.bids.insert(bid)
+        }
+    }
+
+    /// Mark every check-in's beer as tried; returns how many were new.
+    pub fn import(&mut self, checkins: &[Checkin]) -> usize {
+        let before = self.bids.len();
+        self.bids.extend(checkins.iter().map(|c| c.bid));
+        self.bids.len() - before
+    }
+}
+
+/// One check-in from an Untappd export. Only the fields we use are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkin {
+    pub bid: u64,
+    pub beer_name: String,
+    pub brewery_name: String,
+    /// Untappd style (`beer_type`, e.g. "IPA - New England / Hazy").
+    pub style: Option<String>,
+    pub abv: Option<f64>,
+    pub ibu: Option<u32>,
+    /// The user's own rating (`rating_score`, 0.25-5), if they gave one.
+    pub rating: Option<f64>,
+}
+
+/// Parse an Untappd check-in export: the JSON array or the CSV file (header
+/// row required). Check-ins without a usable `bid` (or `beer_url` to take it
+/// from) are skipped; an export with none at all is an error.
+pub fn parse_export(body: &str) -> AppResult<Vec<Checkin>> {
+    let body = body.trim_start_matches('\u{feff}').trim();
+    let records: Vec<Vec<(String, String)>> = if body.starts_with('[') {
+        let items: Vec<Value> = serde_json::from_str(body)
+            .map_err(|e| AppError::Parse(format!("Failed to parse check-in JSON: {e}")))?;
+        items
+            .iter()
+            .filter_map(Value::as_object)
+            .map(|o| o.iter().map(|(k, v)| (k.clone(), json_text(v))).collect())
+            .collect()
+    } else {
+        let mut rows = parse_csv(body).into_iter();
+        let header = rows
+            .next()
+            .ok_or_else(|| AppError::Parse("Empty check-in export".into()))?;
+        rows.map(|row| header.iter().cloned().zip(row).collect())
+            .collect()
+    };
+
+    let checkins: Vec<Checkin> = records.iter().filter_map(|r| checkin(r)).collect();
+    if checkins.is_empty() {
+        return Err(AppError::Parse(
+            "No check-ins with a beer id (`bid`) in the export".into(),
+        ));
+    }
+    Ok(checkins)
+}
+
+fn json_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn checkin(record: &[(String, String)]) -> Option<Checkin> {
+    let field = |name: &str| {
+        record
+            .iter()
+            .find(|(k, _)| k.trim() == name)
+            .map(|(_, v)| v.trim())
+            .filter(|v| !v.is_empty())
+    };
+    let bid = field("bid").and_then(|b| b.parse().ok()).or_else(|| {
+        field("beer_url")?
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()?
+            .parse()
+            .ok()
+    })?;
+    Some(Checkin {
+        bid,
+        beer_name: field("beer_name").unwrap_or_default().to_string(),
+        brewery_name: field("brewery_name").unwrap_or_default().to_string(),
+        style: field("beer_type").map(str::to_string),
+        abv: field("beer_abv").and_then(|v| v.parse().ok()),
+        ibu: field("beer_ibu")
+            .and_then(|v| v.parse::<f64>().ok())
+            .filter(|&v| v > 0.0)
+            .map(|v| v.round() as u32),
+        rating: field("rating_score")
+            .and_then(|v| v.parse().ok())
+            .filter(|&v: &f64| v > 0.0),
+    })
+}
+
+/// Minimal RFC 4180 reader: comma-separated, `"`-quoted fields with `""`
+/// escapes and embedded newlines. Blank lines are skipped.
+fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(std::mem::take(&mut field)),
+            ('\r', false) => {}
+            ('\n', false) => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|f| !f.is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                }
+                row.clear();
+            }
+            (c, _) => field.push(c),
+        }
+    }
+    row.push(field);
+    if row.iter().any(|f| !f.is_empty()) {
+        rows.push(row);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV_EXPORT: &str = "\u{feff}beer_name,brewery_name,beer_type,beer_abv,beer_ibu,comment,rating_score,beer_url,bid\r\n\
+        Pliny the Elder,Russian River Brewing Company,IPA - Imperial / Double,8,100,\"Great, \"\"piney\"\"\nstuff\",4.5,https://untappd.com/b/russian-river-brewing-company-pliny-the-elder/7936,7936\r\n\
+        Curieux,Allagash Brewing Company,Belgian Tripel,10.2,,,,https://untappd.com/b/allagash-brewing-company-curieux/11994,\r\n\
+        Mystery,Nobody,,,,,,,\r\n";
+
+    #[test]
+    fn parses_csv_export_with_quotes_and_url_fallback() {
+        let checkins = parse_export(CSV_EXPORT).unwrap();
+        assert_eq!(checkins.len(), 2);
+        let pliny = &checkins[0];
+        assert_eq!(pliny.bid, 7936);
+        assert_eq!(pliny.style.as_deref(), Some("IPA - Imperial / Double"));
+        assert_eq!(
+            (pliny.abv, pliny.ibu, pliny.rating),
+            (Some(8.0), Some(100), Some(4.5))
+        );
+        // No `bid` column value: taken from the beer URL.
+        assert_eq!(checkins[1].bid, 11994);
+        assert_eq!(checkins[1].rating, None);
+    }
+
+    #[test]
+    fn parses_json_export() {
+        let json = r#"[
+            {"beer_name": "Pliny the Elder", "bid": 7936, "beer_type": "IPA - Imperial / Double",
+             "beer_abv": "8", "rating_score": "4.25"},
+            {"beer_name": "No id"}
+        ]"#;
+        let checkins = parse_export(json).unwrap();
+        assert_eq!(checkins.len(), 1);
+        assert_eq!(checkins[0].bid, 7936);
+        assert_eq!(checkins[0].rating, Some(4.25));
+        assert!(parse_export("beer_name\nFoo\n").is_err());
+        assert!(parse_export("[").is_err());
+    }
+
+    #[test]
+    fn tried_list_toggles_imports_and_round_trips() {
+        let mut list = TriedList::from_json("[3, 1]");
+        assert!(list.contains(1));
+        assert!(!list.toggle(1));
+        assert!(list.toggle(2));
+        assert_eq!(list.to_json(), "[2,3]");
+        let added = list.import(&parse_export(CSV_EXPORT).unwrap());
+        assert_eq!(added, 2);
+        assert_eq!(TriedList::from_json(&list.to_json()), list);
+        assert!(TriedList::from_json("garbage").is_empty());
+    }
+
+    #[test]
+    fn user_tokens_come_from_cookies() {
+        let id = new_user_id([
+            0x8f, 0x1c, 0x2a, 0x3b, 0x4d, 0x5e, 0x6f, 0x70, 0, 1, 2, 3, 4, 5, 6, 0xff,
+        ]);
+        assert_eq!(id, "8f1c2a3b4d5e6f7000010203040506ff");
+        assert!(valid_user_id(&id));
+        let header = format!("theme=dark; {USER_COOKIE}={id}; other=1");
+        assert_eq!(user_from_cookie(&header), Some(id.clone()));
+        assert_eq!(user_from_cookie(&format!("{USER_COOKIE}=short")), None);
+        assert_eq!(
+            user_from_cookie(&format!("{USER_COOKIE}=bad;token<script>")),
+            None
+        );
+        assert!(set_cookie(&id).starts_with(&format!("{USER_COOKIE}={id}; Path=/")));
+    }
+
+    #[test]
+    fn cookie_beats_a_shared_link() {
+        let mine = "8f1c2a3b4d5e6f7000010203040506ff";
+        let theirs = "0123456789abcdef0123456789abcdef";
+        let cookie = format!("{USER_COOKIE}={mine}");
+
+        // A link is adopted only by a browser without a list of its own...
+        assert_eq!(
+            identify(None, Some(theirs)),
+            Identity::Shared(theirs.to_string())
+        );
+        // ...and otherwise just offered for an explicit switch.
+        assert_eq!(
+            identify(Some(&cookie), Some(theirs)),
+            Identity::Cookie {
+                user: mine.to_string(),
+                offered: Some(theirs.to_string()),
+            }
+        );
+        assert_eq!(
+            identify(Some(&cookie), Some(mine)),
+            Identity::Cookie {
+                user: mine.to_string(),
+                offered: None,
+            }
+        );
+        // Malformed tokens count as absent.
+        assert_eq!(
+            identify(Some(&cookie), Some("short")),
+            Identity::Cookie {
+                user: mine.to_string(),
+                offered: None,
+            }
+        );
+        assert_eq!(
+            identify(Some("theme=dark"), Some("bad<token>0123456789")),
+            Identity::New
+        );
+    }
+}
@@ -12,8 +12,11 @@
 //
 
 use crate::error::{AppError, AppResult};
-use crate::model::RatingResult;
+use crate::model::{BeerDetails, BeerEntry, RatingResult, SearchVariant};
+use crate::normalize;
 use regex::Regex;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
 
 pub const BASE_UNTAPPD_URL: &str = "https://untappd.com";
 
@@ -34,10 +37,18 @@ pub struct AlgoliaQuery {
     pub body: String,
 }
 
-/// Build an Algolia query for a beer search string, using the given credentials.
-pub fn build_query_with(app_id: &str, api_key: &str, search: &str) -> AlgoliaQuery {
+/// Hits requested for the full/common-name variants: specific enough that the
+/// right brewery is near the top, but not always first.
+const NAMED_VARIANT_HITS: usize = 3;
+/// Hits requested for the bare beer-name variant, filtered to the brewery on our
+/// side (generic names like "Pale Ale" match many breweries).
+const BREWERY_FILTERED_HITS: usize = 10;
+
+/// Build an Algolia query for a beer search string, using the given credentials
+/// and asking for the top `hits` results.
+pub fn build_query_with(app_id: &str, api_key: &str, search: &str, hits: usize) -> AlgoliaQuery {
     let url = format!("https://{app_id}-dsn.algolia.net/1/indexes/{ALGOLIA_INDEX}/query");
-    // Only need the top hit; serde_json keeps the body simple and injection-safe.
+    // serde_json keeps the body simple and injection-safe.
     //
     // `ignorePlurals` handles imprecise menu names like "Strawberries" vs
     // "Strawberry" with no downside. We deliberately do NOT use
@@ -46,7 +57,7 @@ pub fn build_query_with(app_id: &str, api_key: &str, search: &str) -> AlgoliaQue
     // a stray word with a real beer), and a wrong rating is worse than "N/A".
     let body = serde_json::json!({
         "query": search,
-        "hitsPerPage": 1,
+        "hitsPerPage": hits,
         "ignorePlurals": true,
     })
     .to_string();
@@ -58,9 +69,248 @@ pub fn build_query_with(app_id: &str, api_key: &str, search: &str) -> AlgoliaQue
     }
 }
 
-/// Build an Algolia query using the hardcoded credentials.
+/// Build a top-hit Algolia query using the hardcoded credentials.
 pub fn build_query(search: &str) -> AlgoliaQuery {
-    build_query_with(ALGOLIA_APP_ID, ALGOLIA_API_KEY, search)
+    build_query_with(ALGOLIA_APP_ID, ALGOLIA_API_KEY, search, 1)
+}
+
+/// Most queries sent in one multi-query request. Algolia has no hard cap, but
+/// this keeps the body of even a very long menu well under its size limit.
+pub const BATCH_LIMIT: usize = 50;
+
+/// Combine single-index queries (as built by `build_query_with`) into one POST
+/// to Algolia's multi-query endpoint, `/1/indexes/*/queries`. The response's
+/// `results` come back in the same order (see `parse_batch`).
+pub fn build_batch(queries: &[AlgoliaQuery]) -> AlgoliaQuery {
+    let (app_id, api_key) = queries
+        .first()
+        .map_or((ALGOLIA_APP_ID, ALGOLIA_API_KEY), |q| {
+            (q.app_id.as_str(), q.api_key.as_str())
+        });
+    let requests: Vec<Value> = queries
+        .iter()
+        .map(|q| json!({"indexName": ALGOLIA_INDEX, "params": query_params(&q.body)}))
+        .collect();
+    AlgoliaQuery {
+        url: format!("https://{app_id}-dsn.algolia.net/1/indexes/*/queries"),
+        app_id: app_id.to_string(),
+        api_key: api_key.to_string(),
+        body: json!({ "requests": requests }).to_string(),
+    }
+}
+
+/// A single query's JSON body as the URL-encoded `params` string the
+/// multi-query endpoint takes.
+fn query_params(body: &str) -> String {
+    let mut params = url::form_urlencoded::Serializer::new(String::new());
+    if let Ok(Value::Object(fields)) = serde_json::from_str::<Value>(body) {
+        for (name, value) in &fields {
+            match value {
+                Value::String(s) => params.append_pair(name, s),
+                other => params.append_pair(name, &other.to_string()),
+            };
+        }
+    }
+    params.finish()
+}
+
+/// The query variants to try for a menu entry, in order: TapHunter's full
+/// display name, the normalized short brewery plus beer name, then the bare
+/// beer name (brewery-filtered on our side). Empty or duplicate search strings
+/// are skipped.
+pub fn search_plan(entry: &BeerEntry) -> Vec<(SearchVariant, AlgoliaQuery)> {
+    let candidates = [
+        (
+            SearchVariant::FullName,
+            normalize::normalize(&entry.full_name),
+            NAMED_VARIANT_HITS,
+        ),
+        (
+            SearchVariant::CommonName,
+            normalize::search_string(&entry.brewery, &entry.name),
+            NAMED_VARIANT_HITS,
+        ),
+        (
+            SearchVariant::BreweryFiltered,
+            normalize::normalize(&entry.name),
+            BREWERY_FILTERED_HITS,
+        ),
+    ];
+
+    let mut seen: Vec<String> = Vec::new();
+    let mut plan = Vec::new();
+    for (variant, search, hits) in candidates {
+        if search.is_empty() || seen.contains(&search) {
+            continue;
+        }
+        plan.push((
+            variant,
+            build_query_with(ALGOLIA_APP_ID, ALGOLIA_API_KEY, &search, hits),
+        ));
+        seen.push(search);
+    }
+    plan
+}
+
+/// Runs an entry's `search_plan` to completion, one Algolia response at a time.
+/// The runners own the HTTP; this owns the "is that the right beer?" decision:
+/// a hit is confident when its `brewery_name` matches the entry's brewery (short
+/// or full name, per `normalize::same_brewery`).
+pub struct RatingSearch<'a> {
+    entry: &'a BeerEntry,
+    /// Unconfirmed top hit of the first named variant to return any (full or
+    /// common name, which `search_plan` may have merged) — what we used to
+    /// accept unconditionally, kept as the last resort.
+    fallback: Option<RatingResult>,
+}
+
+impl<'a> RatingSearch<'a> {
+    pub fn new(entry: &'a BeerEntry) -> Self {
+        RatingSearch {
+            entry,
+            fallback: None,
+        }
+    }
+
+    /// Feed one variant's Algolia response. `Ok(Some(_))` is a confident match
+    /// (stop searching); `Ok(None)` means try the next variant. Blocked/parse
+    /// errors are returned as-is so the caller can abort (and not cache).
+    pub fn offer(&mut self, variant: SearchVariant, body: &str) -> AppResult<Option<RatingResult>> {
+        self.offer_hits(variant, parse_hits(body))
+    }
+
+    fn offer_hits(
+        &mut self,
+        variant: SearchVariant,
+        hits: Hits,
+    ) -> AppResult<Option<RatingResult>> {
+        let hits = match hits {
+            Ok(hits) => hits,
+            Err(AppError::NotFound) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let confident = hits.iter().find(|(brewery, _)| {
+            normalize::same_brewery(brewery, &self.entry.brewery)
+                || normalize::same_brewery(brewery, &self.entry.brewery_full)
+        });
+        if let Some((_, result)) = confident {
+            return Ok(Some(RatingResult {
+                matched_by: Some(variant),
+                ..result.clone()
+            }));
+        }
+
+        if variant != SearchVariant::BreweryFiltered && self.fallback.is_none() {
+            self.fallback = hits.into_iter().next().map(|(_, result)| result);
+        }
+        Ok(None)
+    }
+
+    /// No variant was confident: the unconfirmed named-variant hit, or `NotFound`.
+    pub fn finish(self) -> AppResult<RatingResult> {
+        self.finish_or(AppError::NotFound)
+    }
+
+    /// The search stopped on `error`: the unconfirmed hit if an earlier variant
+    /// found one, else `error`.
+    fn finish_or(self, error: AppError) -> AppResult<RatingResult> {
+        self.fallback.ok_or(error)
+    }
+}
+
+/// One query's hits as `(brewery_name, rating)` pairs, in relevance order.
+pub type Hits = AppResult<Vec<(String, RatingResult)>>;
+
+/// An entry's search in progress within a `BatchLookup`.
+struct PendingSearch<'a> {
+    id: usize,
+    search: RatingSearch<'a>,
+    /// Variants not yet sent; the front one is next.
+    plan: VecDeque<(SearchVariant, AlgoliaQuery)>,
+}
+
+/// Runs many entries' `search_plan`s together, a round at a time: each round
+/// sends every unresolved entry's next variant in one multi-query request
+/// (`build_batch`), so a cold menu costs about three Algolia calls rather than
+/// up to three per beer. Entries are identified by the caller's `id`s.
+pub struct BatchLookup<'a> {
+    pending: Vec<PendingSearch<'a>>,
+    /// How many of `pending` (from the front) the last batch was built from.
+    in_flight: usize,
+    done: Vec<(usize, AppResult<RatingResult>)>,
+}
+
+impl<'a> BatchLookup<'a> {
+    pub fn new(entries: impl IntoIterator<Item = (usize, &'a BeerEntry)>) -> Self {
+        let mut lookup = BatchLookup {
+            pending: Vec::new(),
+            in_flight: 0,
+            done: Vec::new(),
+        };
+        for (id, entry) in entries {
+            let plan: VecDeque<_> = search_plan(entry).into();
+            if plan.is_empty() {
+                lookup.done.push((id, Err(AppError::NotFound)));
+            } else {
+                lookup.pending.push(PendingSearch {
+                    id,
+                    search: RatingSearch::new(entry),
+                    plan,
+                });
+            }
+        }
+        lookup
+    }
+
+    /// The next request to send: the next variant of up to `BATCH_LIMIT`
+    /// unresolved entries. `None` once every entry is resolved.
+    pub fn next_batch(&mut self) -> Option<AlgoliaQuery> {
+        self.in_flight = self.pending.len().min(BATCH_LIMIT);
+        let queries: Vec<AlgoliaQuery> = self.pending[..self.in_flight]
+            .iter()
+            .filter_map(|p| p.plan.front().map(|(_, q)| q.clone()))
+            .collect();
+        (!queries.is_empty()).then(|| build_batch(&queries))
+    }
+
+    /// Feed the response to the last `next_batch`. A failed request (or an
+    /// unreadable response) resolves every entry in it: to the unconfirmed hit
+    /// an earlier round found, else to that error. Entries whose variant
+    /// wasn't confident go on to their next one.
+    pub fn offer(&mut self, response: AppResult<String>) {
+        let sent: Vec<PendingSearch<'a>> = self.pending.drain(..self.in_flight).collect();
+        self.in_flight = 0;
+        let results = match response.and_then(|body| parse_batch(&body, sent.len())) {
+            Ok(results) => results,
+            Err(e) => {
+                // Entries holding an unconfirmed hit from an earlier round keep
+                // it; only those with nothing fail.
+                self.done.extend(
+                    sent.into_iter()
+                        .map(|p| (p.id, p.search.finish_or(e.clone()))),
+                );
+                return;
+            }
+        };
+        for (mut p, hits) in sent.into_iter().zip(results) {
+            let Some((variant, _)) = p.plan.pop_front() else {
+                self.done.push((p.id, p.search.finish()));
+                continue;
+            };
+            match p.search.offer_hits(variant, hits) {
+                Ok(Some(rating)) => self.done.push((p.id, Ok(rating))),
+                Err(e) => self.done.push((p.id, p.search.finish_or(e))),
+                Ok(None) if p.plan.is_empty() => self.done.push((p.id, p.search.finish())),
+                Ok(None) => self.pending.push(p),
+            }
+        }
+    }
+
+    /// Every resolved entry's result, by id.
+    pub fn finish(self) -> Vec<(usize, AppResult<RatingResult>)> {
+        self.done
+    }
 }
 
 /// Parse an Algolia beer-query response into a rating + review link.
@@ -70,7 +320,41 @@ pub fn build_query(search: &str) -> AlgoliaQuery {
 /// - No hits → `NotFound`. A hit with no rating yet still resolves (link to the
 ///   page, with "N/A" as the rating text).
 pub fn parse_rating(body: &str) -> AppResult<RatingResult> {
-    let value: serde_json::Value = serde_json::from_str(body)
+    parse_hits(body)?
+        .into_iter()
+        .next()
+        .map(|(_, result)| result)
+        .ok_or(AppError::NotFound)
+}
+
+/// Parse every hit of an Algolia response into `(brewery_name, rating)` pairs,
+/// in relevance order. Errors as `parse_rating`; an empty hit list is `NotFound`.
+fn parse_hits(body: &str) -> Hits {
+    let value = parse_envelope(body)?;
+    hits_of(&value)
+}
+
+/// Split a multi-query response into each query's hits, in request order, as
+/// `parse_hits` would give for the query alone. A response that isn't the
+/// `expected` number of results is a `Parse` error for the whole batch.
+pub fn parse_batch(body: &str, expected: usize) -> AppResult<Vec<Hits>> {
+    let value = parse_envelope(body)?;
+    let results = value
+        .get("results")
+        .and_then(|r| r.as_array())
+        .ok_or_else(|| AppError::Parse("Algolia response missing 'results'".into()))?;
+    if results.len() != expected {
+        return Err(AppError::Parse(format!(
+            "Algolia answered {} of {expected} queries",
+            results.len()
+        )));
+    }
+    Ok(results.iter().map(hits_of).collect())
+}
+
+/// Parse an Algolia response body, turning its error envelope into `Blocked`.
+fn parse_envelope(body: &str) -> AppResult<Value> {
+    let value: Value = serde_json::from_str(body)
         .map_err(|e| AppError::Parse(format!("Failed to parse Algolia JSON: {e}")))?;
 
     // Algolia error envelopes carry a non-2xx `status` and a `message`.
@@ -83,14 +367,38 @@ pub fn parse_rating(body: &str) -> AppResult<RatingResult> {
             return Err(AppError::Blocked(format!("Algolia status {status}: {msg}")));
         }
     }
+    Ok(value)
+}
 
+/// One query's hits from its parsed response.
+fn hits_of(value: &Value) -> Hits {
     let hits = value
         .get("hits")
         .and_then(|h| h.as_array())
         .ok_or_else(|| AppError::Parse("Algolia response missing 'hits'".into()))?;
 
-    let hit = hits.first().ok_or(AppError::NotFound)?;
+    if hits.is_empty() {
+        return Err(AppError::NotFound);
+    }
+    let mut parsed = Vec::with_capacity(hits.len());
+    for (idx, hit) in hits.iter().enumerate() {
+        let brewery = hit
+            .get("brewery_name")
+            .and_then(|b| b.as_str())
+            .unwrap_or("")
+            .to_string();
+        match parse_hit(hit) {
+            Ok(result) => parsed.push((brewery, result)),
+            // A malformed top hit is a real parse failure; a malformed runner-up
+            // is just skipped.
+            Err(e) if idx == 0 => return Err(e),
+            Err(_) => {}
+        }
+    }
+    Ok(parsed)
+}
 
+fn parse_hit(hit: &Value) -> AppResult<RatingResult> {
     // The beer exists on Untappd, so always build a link to its page.
     let slug = hit.get("beer_slug").and_then(|s| s.as_str()).unwrap_or("");
     let bid = hit
@@ -106,7 +414,19 @@ pub fn parse_rating(body: &str) -> AppResult<RatingResult> {
         _ => "N/A".to_string(),
     };
 
-    Ok(RatingResult { rating, url })
+    let rating_count = hit
+        .get("rating_count")
+        .and_then(|c| c.as_u64())
+        .unwrap_or(0);
+
+    Ok(RatingResult {
+        rating,
+        url,
+        rating_count,
+        matched_by: None,
+        details: BeerDetails::from_json(hit),
+        fetched_at: None,
+    })
 }
 
 /// URL of the Untappd search page (used only by the credential-refresh fallback).
@@ -152,6 +472,7 @@ mod tests {
         let body = include_str!("fixtures/algolia_beer_query.json");
         let result = parse_rating(body).expect("should extract a rating");
         assert_eq!(result.rating, "3.62");
+        assert_eq!(result.rating_count, 548317);
         assert_eq!(
             result.url,
             "https://untappd.com/b/sierra-nevada-brewing-co-pale-ale/6284"
@@ -161,6 +482,23 @@ mod tests {
             .to_cell()
             .starts_with("<a href=\"https://untappd.com/b/"));
         assert!(result.to_cell().ends_with("</a>"));
+        // Untappd metadata rides along with the rating.
+        let d = &result.details;
+        assert_eq!(
+            d.beer_label.as_deref(),
+            Some("https://assets.untappd.com/site/beer_logos/beer-6284_5418f_sm.jpeg")
+        );
+        assert!(d.beer_label_hd.as_deref().unwrap().contains("_hd"));
+        assert!(d.brewery_label.is_some());
+        assert_eq!(d.ibu, Some(38));
+        assert_eq!(d.style.as_deref(), Some("Pale Ale - American"));
+        assert_eq!(d.in_production, Some(true));
+        assert!(!d.has_community_award);
+        assert_eq!(d.geoloc, Some((39.7242, -121.815)));
+        // ... with the check-in count beside the score.
+        assert!(result
+            .to_cell()
+            .contains("3.62 <span class=\"rating-count\">(548k)</span>"));
     }
 
     #[test]
@@ -194,6 +532,229 @@ mod tests {
         }
     }
 
+    fn entry(brewery: &str, brewery_full: &str, name: &str, full_name: &str) -> BeerEntry {
+        BeerEntry {
+            tap_number: 1,
+            brewery: brewery.into(),
+            brewery_full: brewery_full.into(),
+            name: name.into(),
+            full_name: full_name.into(),
+            abv: Some(5.6),
+            ibu: None,
+            category: "Blonde and Pale Ales".into(),
+            category_order: None,
+            origin: "Chico, CA".into(),
+            style: "Pale Ale".into(),
+            date_added: None,
+            pours: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn search_plan_orders_and_dedupes_variants() {
+        let e = entry(
+            "Allagash",
+            "Allagash Brewing Company",
+            "Curieux",
+            "Allagash Curieux",
+        );
+        let plan = search_plan(&e);
+        let variants: Vec<SearchVariant> = plan.iter().map(|(v, _)| *v).collect();
+        // "allagash curieux" is both the full name and the common-name string.
+        assert_eq!(
+            variants,
+            vec![SearchVariant::FullName, SearchVariant::BreweryFiltered]
+        );
+        assert!(plan[0].1.body.contains("allagash curieux"));
+        assert!(plan[1].1.body.contains("\"query\":\"curieux\""));
+        assert!(plan[1].1.body.contains("\"hitsPerPage\":10"));
+    }
+
+    #[test]
+    fn confident_match_requires_the_entry_brewery() {
+        let body = include_str!("fixtures/algolia_beer_query.json");
+        let e = entry(
+            "Sierra Nevada",
+            "Sierra Nevada Brewing Co.",
+            "Pale Ale",
+            "Sierra Nevada Pale Ale",
+        );
+        let mut search = RatingSearch::new(&e);
+        let hit = search
+            .offer(SearchVariant::FullName, body)
+            .unwrap()
+            .expect("brewery matches");
+        assert_eq!(hit.rating, "3.62");
+        assert_eq!(hit.matched_by, Some(SearchVariant::FullName));
+
+        // Same hits for a different brewery: not confident, only a fallback.
+        let other = entry("Moonlight", "Moonlight Brewing Company", "Pale Ale", "");
+        let mut search = RatingSearch::new(&other);
+        assert_eq!(search.offer(SearchVariant::FullName, body), Ok(None));
+        assert_eq!(search.finish().unwrap().matched_by, None);
+    }
+
+    #[test]
+    fn brewery_filtered_variant_skips_other_breweries() {
+        let body = include_str!("fixtures/algolia_mixed_breweries.json");
+        let e = entry("Bike Dog", "Bike Dog Brewing Co.", "Milk Stout", "");
+        let mut search = RatingSearch::new(&e);
+        let hit = search
+            .offer(SearchVariant::BreweryFiltered, body)
+            .unwrap()
+            .expect("second hit is Bike Dog's");
+        assert_eq!(
+            hit.url,
+            "https://untappd.com/b/bike-dog-brewing-co-milk-stout/1234567"
+        );
+        assert_eq!(hit.matched_by, Some(SearchVariant::BreweryFiltered));
+    }
+
+    #[test]
+    fn unconfirmed_common_name_hit_is_the_fallback() {
+        let body = include_str!("fixtures/algolia_mixed_breweries.json");
+        let e = entry("Mystery", "Mystery Brewing", "Milk Stout", "");
+        let mut search = RatingSearch::new(&e);
+        assert_eq!(search.offer(SearchVariant::CommonName, body), Ok(None));
+        let fallback = search.finish().expect("top hit kept as fallback");
+        assert_eq!(fallback.rating, "3.71");
+        assert_eq!(fallback.matched_by, None);
+    }
+
+    #[test]
+    fn merged_full_name_variant_keeps_its_fallback() {
+        let body = include_str!("fixtures/algolia_mixed_breweries.json");
+        // The full name is brewery + name, so `search_plan` has no separate
+        // common-name variant; the full-name hit has to stand in for it.
+        let e = entry(
+            "Mystery",
+            "Mystery Brewing",
+            "Milk Stout",
+            "Mystery Milk Stout",
+        );
+        let variants: Vec<SearchVariant> = search_plan(&e).iter().map(|(v, _)| *v).collect();
+        assert!(!variants.contains(&SearchVariant::CommonName));
+        let mut search = RatingSearch::new(&e);
+        assert_eq!(search.offer(SearchVariant::FullName, body), Ok(None));
+        let none = include_str!("fixtures/algolia_no_results.json");
+        assert_eq!(search.offer(SearchVariant::BreweryFiltered, none), Ok(None));
+        let fallback = search.finish().expect("top hit kept as fallback");
+        assert_eq!(fallback.rating, "3.71");
+        assert_eq!(fallback.matched_by, None);
+    }
+
+    /// A multi-query response from single-query response bodies.
+    fn batch_response(bodies: &[&str]) -> String {
+        format!("{{\"results\":[{}]}}", bodies.join(","))
+    }
+
+    #[test]
+    fn batch_query_sends_each_query_as_params() {
+        let batch = build_batch(&[build_query("sierra nevada pale ale"), build_query("gose")]);
+        assert_eq!(
+            batch.url,
+            "https://9WBO4RQ3HO-dsn.algolia.net/1/indexes/*/queries"
+        );
+        let body: Value = serde_json::from_str(&batch.body).unwrap();
+        let requests = body["requests"].as_array().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0]["indexName"], "beer");
+        let params = requests[0]["params"].as_str().unwrap();
+        assert!(params.contains("query=sierra+nevada+pale+ale"), "{params}");
+        assert!(params.contains("hitsPerPage=1"));
+        assert!(params.contains("ignorePlurals=true"));
+    }
+
+    #[test]
+    fn batch_results_map_back_in_order() {
+        let body = batch_response(&[
+            include_str!("fixtures/algolia_no_results.json"),
+            include_str!("fixtures/algolia_beer_query.json"),
+        ]);
+        let results = parse_batch(&body, 2).unwrap();
+        assert_eq!(results[0], Err(AppError::NotFound));
+        assert_eq!(results[1].as_ref().unwrap()[0].1.rating, "3.62");
+
+        assert!(matches!(parse_batch(&body, 3), Err(AppError::Parse(_))));
+        let auth = include_str!("fixtures/algolia_auth_error.json");
+        assert!(matches!(parse_batch(auth, 1), Err(AppError::Blocked(_))));
+    }
+
+    #[test]
+    fn batch_lookup_runs_plans_a_round_at_a_time() {
+        let sierra = entry(
+            "Sierra Nevada",
+            "Sierra Nevada Brewing Co.",
+            "Pale Ale",
+            "Sierra Nevada Pale Ale",
+        );
+        let mystery = entry("Mystery", "Mystery Brewing", "Milk Stout", "");
+        let blank = entry("", "", "", "");
+        let mut lookup = BatchLookup::new([(0, &sierra), (1, &mystery), (2, &blank)]);
+
+        // Round one: both entries' first variants in one request.
+        let batch = lookup.next_batch().unwrap();
+        assert_eq!(batch.body.matches("indexName").count(), 2);
+        lookup.offer(Ok(batch_response(&[
+            include_str!("fixtures/algolia_beer_query.json"),
+            include_str!("fixtures/algolia_mixed_breweries.json"),
+        ])));
+
+        // Round two: only the unconfirmed entry's bare-name variant.
+        let batch = lookup.next_batch().unwrap();
+        assert!(batch.body.contains("query=milk+stout"), "{}", batch.body);
+        lookup.offer(Ok(batch_response(&[include_str!(
+            "fixtures/algolia_no_results.json"
+        )])));
+        assert!(lookup.next_batch().is_none());
+
+        let mut results = lookup.finish();
+        results.sort_by_key(|(id, _)| *id);
+        assert_eq!(results[0].1.as_ref().unwrap().rating, "3.62");
+        // No confident match: the unconfirmed common-name hit.
+        assert_eq!(results[1].1.as_ref().unwrap().rating, "3.71");
+        assert_eq!(results[2].1, Err(AppError::NotFound));
+    }
+
+    #[test]
+    fn failed_batch_fails_every_entry_in_it() {
+        let a = entry("Allagash", "Allagash Brewing Company", "White", "");
+        let b = entry("Russian River", "", "Pliny the Elder", "");
+        let mut lookup = BatchLookup::new([(7, &a), (9, &b)]);
+        lookup.next_batch().unwrap();
+        lookup.offer(Err(AppError::Network("timed out".into())));
+        assert!(lookup.next_batch().is_none());
+        let results = lookup.finish();
+        assert_eq!(results.len(), 2);
+        assert!(results
+            .iter()
+            .all(|(_, r)| matches!(r, Err(AppError::Network(_)))));
+    }
+
+    #[test]
+    fn failed_later_round_keeps_earlier_fallbacks() {
+        let mystery = entry("Mystery", "Mystery Brewing", "Milk Stout", "");
+        let ghost = entry("Nobody", "Nobody Brewing", "Ghost Ale", "");
+        let mut lookup = BatchLookup::new([(0, &mystery), (1, &ghost)]);
+        lookup.next_batch().unwrap();
+        lookup.offer(Ok(batch_response(&[
+            include_str!("fixtures/algolia_mixed_breweries.json"),
+            include_str!("fixtures/algolia_no_results.json"),
+        ])));
+
+        // Round two fails outright.
+        let batch = lookup.next_batch().unwrap();
+        assert_eq!(batch.body.matches("indexName").count(), 2);
+        lookup.offer(Err(AppError::Network("timed out".into())));
+        assert!(lookup.next_batch().is_none());
+
+        let mut results = lookup.finish();
+        results.sort_by_key(|(id, _)| *id);
+        // Round one's unconfirmed hit survives; the entry with nothing fails.
+        assert_eq!(results[0].1.as_ref().unwrap().rating, "3.71");
+        assert!(matches!(results[1].1, Err(AppError::Network(_))));
+    }
+
     #[test]
     fn recovers_credentials_from_search_page() {
         let html = include_str!("fixtures/untappd_search.html");